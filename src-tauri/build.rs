@@ -1,3 +1,11 @@
 fn main() {
+    // The app can only load the Python pyo3 links against; see `python_env::LINKED_PYTHON`
+    let python = pyo3_build_config::get();
+    println!(
+        "cargo:rustc-env=PAREDROS_LINKED_PYTHON={}.{}",
+        python.version.major, python.version.minor
+    );
+    println!("cargo:rerun-if-env-changed=PYO3_PYTHON");
+    println!("cargo:rerun-if-env-changed=PYO3_CONFIG_FILE");
     tauri_build::build()
 }
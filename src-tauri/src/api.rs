@@ -0,0 +1,525 @@
+// ==============================
+// src-tauri/src/api.rs
+// ==============================
+
+//! Optional JSON-RPC 2.0 API over HTTP on localhost, so other tools can
+//! drive the debugger. It runs on the same worker pool and `ParseInfoStore`
+//! as the Tauri commands, so sessions opened by a script show up in the UI.
+//!
+//! Every request is a `POST /rpc` with `Authorization: Bearer <token>`; the
+//! token is handed out by `start_api_server` and written to `api.json` in the
+//! app data directory, next to the URL, for scripts to pick up.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use pyo3::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::breakpoints::BreakpointSpec;
+use crate::commands;
+use crate::error::{CommandError, CommandResult};
+use crate::session::ParseSession;
+use crate::watcher::GrammarWatcher;
+use crate::worker::PythonWorker;
+
+/// Larger request bodies are refused
+const MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Any `CommandError`; `data` holds it as the frontend would see it
+const COMMAND_FAILED: i64 = -32000;
+
+/// Where the API can be reached; returned by `start_api_server`
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiServerInfo {
+    pub url: String,
+    pub token: String,
+}
+
+/// Payload of the `api/request` event, sent after every successful call so
+/// the UI can refresh what a script changed
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiRequest {
+    pub method: String,
+    /// The session the call was about, if any
+    pub id: Option<usize>,
+}
+
+struct Running {
+    info: ApiServerInfo,
+    server: Arc<tiny_http::Server>,
+    thread: JoinHandle<()>,
+    /// `api.json`, removed again on stop
+    info_file: Option<PathBuf>,
+}
+
+/// At most one server per app
+pub struct ApiServer {
+    app: AppHandle,
+    running: Mutex<Option<Running>>,
+}
+
+/// Params of every method that works on a session
+#[derive(Deserialize)]
+struct SessionParams {
+    id: usize,
+    op_id: Option<String>,
+}
+
+impl ApiServer {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            running: Mutex::new(None),
+        }
+    }
+
+    pub fn info(&self) -> CommandResult<Option<ApiServerInfo>> {
+        Ok(self.running.lock()?.as_ref().map(|r| r.info.clone()))
+    }
+
+    /// Listen on `127.0.0.1:port`, or on any free port if `port` is `None`.
+    /// A server that is already running is kept, token and all.
+    pub fn start(&self, port: Option<u16>) -> CommandResult<ApiServerInfo> {
+        let mut running = self.running.lock()?;
+        if let Some(running) = running.as_ref() {
+            return Ok(running.info.clone());
+        }
+
+        let address = format!("127.0.0.1:{}", port.unwrap_or(0));
+        let server = tiny_http::Server::http(&address).map_err(|e| CommandError::Io {
+            path: address.clone(),
+            message: e.to_string(),
+        })?;
+        let url = match server.server_addr().to_ip() {
+            Some(addr) => format!("http://{addr}/rpc"),
+            None => format!("http://{address}/rpc"),
+        };
+        let info = ApiServerInfo {
+            url,
+            token: uuid::Uuid::new_v4().simple().to_string(),
+        };
+
+        let server = Arc::new(server);
+        let app = self.app.clone();
+        let token = info.token.clone();
+        let accepting = server.clone();
+        let thread = thread::Builder::new()
+            .name("api-server".into())
+            .spawn(move || {
+                // Ends once `unblock` is called
+                while let Ok(request) = accepting.recv() {
+                    let app = app.clone();
+                    let token = token.clone();
+                    tauri::async_runtime::spawn(async move { respond(app, &token, request).await });
+                }
+            })
+            .map_err(|e| CommandError::io(Path::new(&address), e))?;
+
+        let info_file = self.app.path().app_data_dir().ok().map(|dir| dir.join("api.json"));
+        if let Some(path) = &info_file {
+            write_info_file(path, &info)?;
+        }
+
+        *running = Some(Running {
+            info: info.clone(),
+            server,
+            thread,
+            info_file,
+        });
+        Ok(info)
+    }
+
+    /// Returns false if no server was running.
+    pub fn stop(&self) -> CommandResult<bool> {
+        let Some(running) = self.running.lock()?.take() else {
+            return Ok(false);
+        };
+        running.server.unblock();
+        let _ = running.thread.join();
+        if let Some(path) = &running.info_file {
+            let _ = fs::remove_file(path);
+        }
+        Ok(true)
+    }
+}
+
+/// Only readable by the user, as the token grants full access
+fn write_info_file(path: &Path, info: &ApiServerInfo) -> CommandResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| CommandError::io(dir, e))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let json = serde_json::to_vec_pretty(info).map_err(|e| CommandError::invalid_argument(e.to_string()))?;
+    let mut file = options.open(path).map_err(|e| CommandError::io(path, e))?;
+    std::io::Write::write_all(&mut file, &json).map_err(|e| CommandError::io(path, e))
+}
+
+async fn respond(app: AppHandle, token: &str, mut request: tiny_http::Request) {
+    let (status, body) = match check_request(&request, token) {
+        Err(status) => (status, Value::Null),
+        Ok(()) => match read_body(&mut request) {
+            Ok(Some(body)) => (200, handle_body(&app, &body).await),
+            // Chunked requests declare no length, so the limit is checked here as well
+            Ok(None) => (413, Value::Null),
+            Err(e) => (200, error_response(Value::Null, PARSE_ERROR, e.to_string(), None)),
+        },
+    };
+
+    let response = if body.is_null() {
+        tiny_http::Response::from_string("").with_status_code(status)
+    } else {
+        let header = tiny_http::Header::from_bytes("Content-Type", "application/json").expect("valid header");
+        tiny_http::Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header)
+    };
+    let _ = request.respond(response);
+}
+
+/// The body, or `None` if it is longer than `MAX_BODY_BYTES`
+fn read_body(request: &mut tiny_http::Request) -> std::io::Result<Option<String>> {
+    let mut body = String::new();
+    request.as_reader().take(MAX_BODY_BYTES + 1).read_to_string(&mut body)?;
+    Ok((body.len() as u64 <= MAX_BODY_BYTES).then_some(body))
+}
+
+/// HTTP status for requests that don't get as far as JSON-RPC
+fn check_request(request: &tiny_http::Request, token: &str) -> Result<(), u16> {
+    if request.url() != "/rpc" {
+        return Err(404);
+    }
+    if *request.method() != tiny_http::Method::Post {
+        return Err(405);
+    }
+    let expected = format!("Bearer {token}");
+    let authorized = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .is_some_and(|h| constant_time_eq(h.value.as_str().as_bytes(), expected.as_bytes()));
+    if !authorized {
+        return Err(401);
+    }
+    if request.body_length().is_some_and(|len| len as u64 > MAX_BODY_BYTES) {
+        return Err(413);
+    }
+    Ok(())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn handle_body(app: &AppHandle, body: &str) -> Value {
+    let message: Value = match serde_json::from_str(body) {
+        Ok(message) => message,
+        Err(e) => return error_response(Value::Null, PARSE_ERROR, e.to_string(), None),
+    };
+    match message {
+        Value::Array(batch) => {
+            let mut responses = Vec::with_capacity(batch.len());
+            for call in batch {
+                responses.push(handle_call(app, call).await);
+            }
+            responses.into()
+        }
+        call => handle_call(app, call).await,
+    }
+}
+
+async fn handle_call(app: &AppHandle, call: Value) -> Value {
+    let id = call.get("id").cloned().unwrap_or(Value::Null);
+    let (Some(method), true) = (call["method"].as_str(), call["jsonrpc"] == "2.0") else {
+        return error_response(id, INVALID_REQUEST, "Expected a JSON-RPC 2.0 request".into(), None);
+    };
+    let params = call.get("params").cloned().unwrap_or_else(|| json!({}));
+
+    match dispatch(app, method, params.clone()).await {
+        Ok(result) => {
+            // A new session's id is the result
+            let session_id = match method {
+                "get_parse_info" => result.as_u64(),
+                _ => params["id"].as_u64(),
+            };
+            let event = ApiRequest {
+                method: method.to_string(),
+                id: session_id.map(|id| id as usize),
+            };
+            let _ = app.emit("api/request", event);
+            json!({ "jsonrpc": "2.0", "id": id, "result": result })
+        }
+        Err(ApiError::MethodNotFound) => {
+            error_response(id, METHOD_NOT_FOUND, format!("Unknown method {method:?}"), None)
+        }
+        Err(ApiError::InvalidParams(message)) => error_response(id, INVALID_PARAMS, message, None),
+        Err(ApiError::Command(e)) => {
+            let data = serde_json::to_value(&e).ok();
+            error_response(id, COMMAND_FAILED, e.to_string(), data)
+        }
+    }
+}
+
+fn error_response(id: Value, code: i64, message: String, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+enum ApiError {
+    MethodNotFound,
+    InvalidParams(String),
+    /// Boxed, as `CommandError` is large
+    Command(Box<CommandError>),
+}
+
+impl From<CommandError> for ApiError {
+    fn from(err: CommandError) -> Self {
+        ApiError::Command(Box::new(err))
+    }
+}
+
+fn params<T: DeserializeOwned>(params: &Value) -> Result<T, ApiError> {
+    serde_json::from_value(params.clone()).map_err(|e| ApiError::InvalidParams(e.to_string()))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, ApiError> {
+    serde_json::to_value(value).map_err(|e| CommandError::invalid_argument(e.to_string()).into())
+}
+
+#[derive(Deserialize)]
+struct StepParams {
+    step_id: usize,
+}
+
+/// Run `f` on the session named in `params`, on the worker pool
+async fn on_session<T, F>(app: &AppHandle, name: &'static str, params: &Value, f: F) -> Result<Value, ApiError>
+where
+    T: Serialize + Send + 'static,
+    F: FnOnce(Python<'_>, &mut ParseSession) -> CommandResult<T> + Send + 'static,
+{
+    let SessionParams { id, op_id } = self::params(params)?;
+    let worker = app.state::<PythonWorker>();
+    let result = worker
+        .run(name, op_id, move |store| commands::with_session(store, id, f))
+        .await?;
+    to_value(result)
+}
+
+/// The methods mirror the Tauri commands of the same name, and so do their params.
+async fn dispatch(app: &AppHandle, method: &str, p: Value) -> Result<Value, ApiError> {
+    let worker = app.state::<PythonWorker>();
+    match method {
+        "get_parse_info" => {
+            #[derive(Deserialize)]
+            struct Params {
+                grammar: String,
+                op_id: Option<String>,
+            }
+            let Params { grammar, op_id } = params(&p)?;
+            to_value(
+                worker
+                    .run("get_parse_info", op_id, move |store| commands::open(store, grammar))
+                    .await?,
+            )
+        }
+        "close_parse_info" => {
+            let SessionParams { id, .. } = params(&p)?;
+            app.state::<GrammarWatcher>().unwatch(id)?;
+            to_value(
+                worker
+                    .run("close_parse_info", None, move |store| store.remove(id))
+                    .await?,
+            )
+        }
+        "list_parse_infos" => to_value(worker.run("list_parse_infos", None, |store| store.list()).await?),
+        "cancel_operation" => {
+            #[derive(Deserialize)]
+            struct Params {
+                op_id: String,
+            }
+            let Params { op_id } = params(&p)?;
+            to_value(worker.cancel(&op_id)?)
+        }
+        "generate_parser" => {
+            on_session(app, "generate_parser", &p, |py, session| {
+                commands::generate_parser(py, session)
+            })
+            .await
+        }
+        "parse_input" => {
+            #[derive(Deserialize)]
+            struct Params {
+                input: String,
+            }
+            let Params { input } = params(&p)?;
+            on_session(app, "parse_input", &p, move |py, session| {
+                commands::parse_input(py, session, input)
+            })
+            .await
+        }
+        "go_to_step" => {
+            let StepParams { step_id } = params(&p)?;
+            on_session(app, "go_to_step", &p, move |py, session| {
+                commands::go_to_step(py, session, step_id)
+            })
+            .await
+        }
+        "step_forwards" => {
+            #[derive(Deserialize)]
+            struct Params {
+                step: usize,
+            }
+            let Params { step } = params(&p)?;
+            on_session(app, "step_forwards", &p, move |py, session| {
+                commands::step_forwards(py, session, step)
+            })
+            .await
+        }
+        "step_backwards" => {
+            on_session(app, "step_backwards", &p, |py, session| {
+                commands::step_backwards(py, session)
+            })
+            .await
+        }
+        "step_until_next_decision" => {
+            on_session(app, "step_until_next_decision", &p, |py, session| {
+                commands::step_until_next_decision(py, session)
+            })
+            .await
+        }
+        "step_back_until_previous_decision" => {
+            on_session(app, "step_back_until_previous_decision", &p, |py, session| {
+                commands::step_back_until_previous_decision(py, session)
+            })
+            .await
+        }
+        "get_user_grammar" => {
+            on_session(app, "get_user_grammar", &p, |py, session| {
+                commands::user_grammar(py, session)
+            })
+            .await
+        }
+        "get_current_parse_step_info" => {
+            on_session(app, "get_current_parse_step_info", &p, |py, session| {
+                commands::current_step(py, session)
+            })
+            .await
+        }
+        "get_next_parse_step_info" => {
+            on_session(app, "get_next_parse_step_info", &p, |py, session| {
+                commands::next_step(py, session)
+            })
+            .await
+        }
+        "get_parse_step_info" => {
+            let StepParams { step_id } = params(&p)?;
+            on_session(app, "get_parse_step_info", &p, move |py, session| {
+                commands::step_info(py, session, step_id)
+            })
+            .await
+        }
+        "get_parse_step_range" => {
+            #[derive(Deserialize)]
+            struct Params {
+                from: usize,
+                to: usize,
+            }
+            let Params { from, to } = params(&p)?;
+            on_session(app, "get_parse_step_range", &p, move |py, session| {
+                commands::step_range(py, session, from, to)
+            })
+            .await
+        }
+        "get_full_trace" => {
+            on_session(app, "get_full_trace", &p, |py, session| {
+                commands::full_trace(py, session)
+            })
+            .await
+        }
+        "get_token_list" => {
+            on_session(app, "get_token_list", &p, |py, session| {
+                commands::token_list(py, session)
+            })
+            .await
+        }
+        "get_json_parse_tree" => {
+            on_session(app, "get_json_parse_tree", &p, |py, session| {
+                commands::parse_tree(py, session)
+            })
+            .await
+        }
+        "set_breakpoint" => {
+            #[derive(Deserialize)]
+            struct Params {
+                breakpoint: BreakpointSpec,
+            }
+            let Params { breakpoint } = params(&p)?;
+            on_session(app, "set_breakpoint", &p, move |_py, session| {
+                commands::set_breakpoint(session, breakpoint)
+            })
+            .await
+        }
+        "remove_breakpoint" => {
+            #[derive(Deserialize)]
+            struct Params {
+                breakpoint_id: u32,
+            }
+            let Params { breakpoint_id } = params(&p)?;
+            on_session(app, "remove_breakpoint", &p, move |_py, session| {
+                Ok(commands::remove_breakpoint(session, breakpoint_id))
+            })
+            .await
+        }
+        "list_breakpoints" => {
+            on_session(app, "list_breakpoints", &p, |_py, session| {
+                Ok(commands::list_breakpoints(session))
+            })
+            .await
+        }
+        "continue_to_breakpoint" => {
+            on_session(app, "continue_to_breakpoint", &p, |py, session| {
+                commands::continue_to_breakpoint(py, session)
+            })
+            .await
+        }
+        "reverse_continue" => {
+            on_session(app, "reverse_continue", &p, |py, session| {
+                commands::reverse_continue(py, session)
+            })
+            .await
+        }
+        "evaluate_watches" => {
+            #[derive(Deserialize)]
+            struct Params {
+                expressions: Vec<String>,
+                step_id: Option<usize>,
+            }
+            let Params { expressions, step_id } = params(&p)?;
+            on_session(app, "evaluate_watches", &p, move |py, session| {
+                commands::evaluate_watches(py, session, &expressions, step_id)
+            })
+            .await
+        }
+        _ => Err(ApiError::MethodNotFound),
+    }
+}
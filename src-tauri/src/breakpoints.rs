@@ -0,0 +1,346 @@
+// ==============================
+// src-tauri/src/breakpoints.rs
+// ==============================
+
+//! Breakpoints of a session and the check whether a step hits one of them.
+//! `continue_to_breakpoint` and `reverse_continue` run that check on every
+//! step they pass, so the frontend doesn't have to page through the trace.
+//! A breakpoint with a condition (see `expr`) only stops where it holds.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::debugger::{self, ParseStepInfo};
+use crate::error::CommandResult;
+use crate::expr::{self, Expr};
+
+const RULE_ENTRY: &str = "Rule entry";
+const RULE_EXIT: &str = "Rule exit";
+const TOKEN_CONSUME: &str = "Token consume";
+
+/// Where a breakpoint stops
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum BreakpointKind {
+    /// Entering the rule
+    RuleEnter { rule: String },
+    /// Leaving the rule
+    RuleExit { rule: String },
+    /// Consuming a token of this type, e.g. `KOMMA`
+    TokenType { type_name: String },
+    /// Consuming the token at this position of the token stream
+    TokenIndex { index: usize },
+    /// Entering a rule whose definition spans this line. `file` may be just
+    /// the file name or any trailing part of the path, and the other way round.
+    GrammarLine { file: String, line: usize },
+    /// Every step; meant to be combined with a condition
+    AnyStep,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub id: u32,
+    #[serde(flatten)]
+    pub kind: BreakpointKind,
+    pub enabled: bool,
+    /// Expression that must hold as well, e.g. `lookahead_repr[0] == "KOMMA"`
+    #[serde(default)]
+    pub condition: Option<String>,
+}
+
+/// What `set_breakpoint` takes: a new breakpoint, or a replacement for the
+/// one with the given id
+#[derive(Debug, Clone, Deserialize)]
+pub struct BreakpointSpec {
+    pub id: Option<u32>,
+    #[serde(flatten)]
+    pub kind: BreakpointKind,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub condition: Option<String>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// What the continue commands hand back
+#[derive(Debug, Serialize)]
+pub struct ContinueResult {
+    /// The step the cursor ended up on
+    pub step: ParseStepInfo,
+    /// Ids of the breakpoints hit there; empty if the run reached the end
+    /// (or the start) of the trace instead
+    pub hit: Vec<u32>,
+}
+
+/// All breakpoints of one session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Breakpoints {
+    next_id: u32,
+    list: Vec<Breakpoint>,
+}
+
+impl Breakpoints {
+    pub fn list(&self) -> &[Breakpoint] {
+        &self.list
+    }
+
+    /// Add a breakpoint, or replace the one with `spec.id` if there is one.
+    /// Fails if the condition doesn't parse.
+    pub fn set(&mut self, spec: BreakpointSpec) -> CommandResult<Breakpoint> {
+        // An empty condition field in the frontend means none
+        let condition = spec.condition.filter(|c| !c.trim().is_empty());
+        if let Some(condition) = &condition {
+            Expr::parse(condition)?;
+        }
+
+        if let Some(existing) = spec
+            .id
+            .and_then(|id| self.list.iter_mut().find(|b| b.id == id))
+        {
+            existing.kind = spec.kind;
+            existing.enabled = spec.enabled;
+            existing.condition = condition;
+            return Ok(existing.clone());
+        }
+
+        self.next_id += 1;
+        let breakpoint = Breakpoint {
+            id: self.next_id,
+            kind: spec.kind,
+            enabled: spec.enabled,
+            condition,
+        };
+        self.list.push(breakpoint.clone());
+        Ok(breakpoint)
+    }
+
+    /// Returns false if there was no breakpoint with that id.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let before = self.list.len();
+        self.list.retain(|b| b.id != id);
+        self.list.len() != before
+    }
+
+    /// Prepare checking steps against the enabled breakpoints.
+    pub fn matcher(&self, py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<Matcher> {
+        let active = self
+            .list
+            .iter()
+            .filter(|b| b.enabled)
+            .map(|b| {
+                // Parsed again as a project file may have been edited by hand
+                let condition = b.condition.as_deref().map(Expr::parse).transpose()?;
+                Ok((b.clone(), condition))
+            })
+            .collect::<CommandResult<Vec<_>>>()?;
+
+        // Steps only carry the token index; look the types up once
+        let needs_types = active
+            .iter()
+            .any(|(b, _)| matches!(b.kind, BreakpointKind::TokenType { .. }));
+        let token_types = if needs_types {
+            debugger::token_list(py, parse_info)?
+                .into_iter()
+                .filter_map(|t| Some((usize::try_from(t.token_index).ok()?, t.type_name)))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Matcher { active, token_types })
+    }
+}
+
+/// The enabled breakpoints with their parsed conditions, plus what is
+/// needed to check them
+pub struct Matcher {
+    active: Vec<(Breakpoint, Option<Expr>)>,
+    token_types: HashMap<usize, String>,
+}
+
+impl Matcher {
+    /// Ids of the breakpoints `step` hits
+    pub fn hits(&self, step: &ParseStepInfo) -> Vec<u32> {
+        // Converted at most once, and only if a location matched
+        let mut value = None;
+        self.active
+            .iter()
+            .filter(|(b, condition)| {
+                self.is_hit(&b.kind, step)
+                    && condition.as_ref().is_none_or(|condition| {
+                        condition.holds(value.get_or_insert_with(|| expr::step_value(step)))
+                    })
+            })
+            .map(|(b, _)| b.id)
+            .collect()
+    }
+
+    fn is_hit(&self, kind: &BreakpointKind, step: &ParseStepInfo) -> bool {
+        let in_rule = |rule: &str| step.rule_name.as_deref() == Some(rule);
+        match kind {
+            BreakpointKind::RuleEnter { rule } => step.step_type == RULE_ENTRY && in_rule(rule),
+            BreakpointKind::RuleExit { rule } => step.step_type == RULE_EXIT && in_rule(rule),
+            BreakpointKind::TokenType { type_name } => {
+                step.step_type == TOKEN_CONSUME
+                    && self.token_types.get(&step.token_index) == Some(type_name)
+            }
+            BreakpointKind::TokenIndex { index } => {
+                step.step_type == TOKEN_CONSUME && step.token_index == *index
+            }
+            BreakpointKind::GrammarLine { file, line } => {
+                step.step_type == RULE_ENTRY
+                    && step.grammar_rule_location.as_ref().is_some_and(|location| {
+                        let path = Path::new(&location.file_path);
+                        (path.ends_with(file) || Path::new(file).ends_with(path))
+                            && (location.start_line..=location.end_line).contains(line)
+                    })
+            }
+            BreakpointKind::AnyStep => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::GrammarRuleLocation;
+
+    /// Only `get_token_list`, which the matcher needs for token types
+    const FAKE_PARSE_INFO: &std::ffi::CStr = cr#"
+class ParseInformation:
+    def get_token_list(self):
+        return [
+            {"text": text, "type_name": type_name, "type_id": type_id, "line": 1, "column": index,
+             "start_index": index, "stop_index": index, "token_index": index}
+            for index, (text, type_name, type_id) in enumerate([("a", "ID", 1), (",", "KOMMA", 2)])
+        ]
+"#;
+
+    fn step(step_type: &str, rule: &str, token_index: usize) -> ParseStepInfo {
+        ParseStepInfo {
+            step_id: "4".to_string(),
+            step_type: step_type.to_string(),
+            rule_name: Some(rule.to_string()),
+            rule_stack: vec!["regest".to_string(), rule.to_string()],
+            state: "12".to_string(),
+            current_token_repr: String::new(),
+            token_index,
+            chosen_transition_index: None,
+            input_text_context: String::new(),
+            next_token_stream_index: token_index,
+            lookahead_repr: Vec::new(),
+            matching_error: false,
+            is_error_node: false,
+            possible_transitions: None,
+            grammar_rule_location: Some(GrammarRuleLocation {
+                name: rule.to_string(),
+                content: String::new(),
+                file_path: "/grammars/Regest.g4".to_string(),
+                start_line: 10,
+                end_line: 14,
+                start_pos: 0,
+                end_pos: 0,
+            }),
+            input_context_snippet: None,
+        }
+    }
+
+    /// A matcher over breakpoints 1.., one per kind, all with the condition
+    fn matcher(kinds: Vec<BreakpointKind>, condition: Option<&str>) -> Matcher {
+        let mut breakpoints = Breakpoints::default();
+        for kind in kinds {
+            breakpoints
+                .set(BreakpointSpec {
+                    id: None,
+                    kind,
+                    enabled: true,
+                    condition: condition.map(str::to_string),
+                })
+                .unwrap();
+        }
+        Python::with_gil(|py| {
+            let module = PyModule::from_code(py, FAKE_PARSE_INFO, c"fake_parse_info.py", c"fake_parse_info").unwrap();
+            let parse_info = module.getattr("ParseInformation").unwrap().call0().unwrap().unbind();
+            breakpoints.matcher(py, &parse_info).unwrap()
+        })
+    }
+
+    #[test]
+    fn step_types_match_the_frontend() {
+        // The `step_type` values of src/interfaces/UserGrammar.ts
+        assert_eq!(RULE_ENTRY, "Rule entry");
+        assert_eq!(RULE_EXIT, "Rule exit");
+        assert_eq!(TOKEN_CONSUME, "Token consume");
+    }
+
+    #[test]
+    fn rule_breakpoints_hit_entry_or_exit_of_their_rule() {
+        let matcher = matcher(
+            vec![
+                BreakpointKind::RuleEnter { rule: "vita".to_string() },
+                BreakpointKind::RuleExit { rule: "vita".to_string() },
+                BreakpointKind::GrammarLine {
+                    file: "Regest.g4".to_string(),
+                    line: 12,
+                },
+            ],
+            None,
+        );
+        assert_eq!(matcher.hits(&step(RULE_ENTRY, "vita", 0)), [1, 3]);
+        assert_eq!(matcher.hits(&step(RULE_EXIT, "vita", 0)), [2]);
+        assert_eq!(matcher.hits(&step(RULE_ENTRY, "datum", 0)), [3]);
+        assert!(matcher.hits(&step(TOKEN_CONSUME, "vita", 0)).is_empty());
+    }
+
+    #[test]
+    fn token_breakpoints_hit_consuming_their_token() {
+        let matcher = matcher(
+            vec![
+                BreakpointKind::TokenType {
+                    type_name: "KOMMA".to_string(),
+                },
+                BreakpointKind::TokenIndex { index: 0 },
+            ],
+            None,
+        );
+        assert_eq!(matcher.hits(&step(TOKEN_CONSUME, "vita", 1)), [1]);
+        assert_eq!(matcher.hits(&step(TOKEN_CONSUME, "vita", 0)), [2]);
+        assert!(matcher.hits(&step(RULE_ENTRY, "vita", 1)).is_empty());
+    }
+
+    #[test]
+    fn decision_breakpoints_need_their_condition() {
+        let matcher = matcher(
+            vec![BreakpointKind::AnyStep],
+            Some(r#"step_type == "Decision" && chosen_transition_index == 1"#),
+        );
+        let decision = |chosen| ParseStepInfo {
+            chosen_transition_index: Some(chosen),
+            ..step("Decision", "vita", 0)
+        };
+        assert_eq!(matcher.hits(&decision(1)), [1]);
+        assert!(matcher.hits(&decision(0)).is_empty());
+        assert!(matcher.hits(&step(RULE_ENTRY, "vita", 0)).is_empty());
+    }
+
+    #[test]
+    fn disabled_breakpoints_never_hit() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints
+            .set(BreakpointSpec {
+                id: None,
+                kind: BreakpointKind::AnyStep,
+                enabled: false,
+                condition: None,
+            })
+            .unwrap();
+        let matcher = Python::with_gil(|py| breakpoints.matcher(py, &py.None()).unwrap());
+        assert!(matcher.hits(&step(RULE_ENTRY, "vita", 0)).is_empty());
+    }
+}
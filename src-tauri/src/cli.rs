@@ -0,0 +1,622 @@
+// ==============================
+// src-tauri/src/cli.rs
+// ==============================
+
+//! Headless mode: `paredros-app parse --grammar G.g4 --input input.txt`.
+//! Runs the same Python bootstrap and `ParseInformation` calls as the app,
+//! but never creates a webview, so it works on servers without a display.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::Context;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::corpus;
+use crate::dap;
+use crate::diagnostics;
+use crate::debugger::{self, ParseStepInfo};
+use crate::error::{CommandError, CommandResult};
+use crate::lsp;
+use crate::python_env::{self, EnvProviderConfig, PySetupLog, PySetupProgress, SetupHost};
+use crate::session::ParseSession;
+use crate::snapshot::{self, SnapshotResult, SnapshotStatus};
+use crate::traceback;
+
+/// Must match `identifier` in tauri.conf.json, so the CLI shares the venv
+/// and error log with the app.
+const APP_IDENTIFIER: &str = "de.hisqu.paredros";
+
+/// Exit code of `parse`, `corpus` and `snapshot` if an input has syntax
+/// errors or a changed tree
+const EXIT_SYNTAX_ERRORS: u8 = 2;
+
+#[derive(Parser)]
+#[command(name = "paredros-app", version, about = "Parse with an ANTLR grammar without opening a window")]
+struct Cli {
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Parse the input and summarise the result. Exits with 2 on syntax errors.
+    Parse(InputArgs),
+    /// Print every step of the parse
+    Trace(InputArgs),
+    /// Print the lexemes of the input
+    Tokens(InputArgs),
+    /// Print the complete parse tree
+    Tree(InputArgs),
+    /// Parse every input matching a glob and report which ones have syntax
+    /// errors. Exits with 2 if any input failed.
+    Corpus(CorpusArgs),
+    /// Compare the parse tree of every input matching a glob with its stored
+    /// snapshot. Exits with 2 if any tree changed or has no snapshot yet.
+    Snapshot(SnapshotArgs),
+    /// Serve the Debug Adapter Protocol on stdin/stdout, or on a TCP port
+    Dap(DapArgs),
+    /// Serve the Language Server Protocol for `.g4` files on stdin/stdout
+    Lsp(LspArgs),
+    /// Download and build wheels of all Python dependencies, for setting up
+    /// machines without network with `--wheelhouse`
+    Wheelhouse(WheelhouseArgs),
+    /// Check the Python environment, Java and git without changing anything,
+    /// and print a report for bug tickets. Exits with 1 if a check failed.
+    Doctor(DoctorArgs),
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// Main grammar file; imported grammars are found next to it
+    #[arg(long)]
+    grammar: PathBuf,
+    /// File to parse, `-` for stdin
+    #[arg(long)]
+    input: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct CorpusArgs {
+    /// Main grammar file; imported grammars are found next to it
+    #[arg(long)]
+    grammar: PathBuf,
+    /// Glob of the input files, e.g. 'examples/Regest/**/*.txt' (quote it)
+    #[arg(long)]
+    inputs: String,
+    /// Also write a JUnit XML report to this file
+    #[arg(long)]
+    junit: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct SnapshotArgs {
+    /// Main grammar file; imported grammars are found next to it
+    #[arg(long)]
+    grammar: PathBuf,
+    /// Glob of the input files, e.g. 'examples/Regest/**/*.txt' (quote it)
+    #[arg(long)]
+    inputs: String,
+    /// Where the snapshots live; by default `<input>.tree.json` next to each input
+    #[arg(long)]
+    snapshot_dir: Option<PathBuf>,
+    /// Write the current trees as the new snapshots
+    #[arg(long)]
+    update: bool,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct DapArgs {
+    /// Listen on 127.0.0.1:<PORT> instead of using stdin/stdout
+    #[arg(long)]
+    port: Option<u16>,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct LspArgs {
+    /// Main grammar of the workspace. Without it, each opened file that no
+    /// loaded grammar imports is taken as a main grammar.
+    #[arg(long)]
+    grammar: Option<PathBuf>,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct WheelhouseArgs {
+    /// Where to put the wheels
+    dir: PathBuf,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct DoctorArgs {
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[command(flatten)]
+    env: EnvArgs,
+}
+
+#[derive(Args)]
+struct EnvArgs {
+    /// Use this venv instead of the one shared with the app
+    #[arg(long)]
+    venv_dir: Option<PathBuf>,
+    /// Install the Python dependencies from this directory of wheels instead
+    /// of downloading them
+    #[arg(long)]
+    wheelhouse: Option<PathBuf>,
+    /// Make the venv with uv instead of `python -m venv` and pip
+    #[arg(long, conflicts_with_all = ["conda_env", "python"])]
+    uv: bool,
+    /// Install into this existing conda environment instead of a venv
+    #[arg(long, value_name = "PREFIX", conflicts_with = "python")]
+    conda_env: Option<PathBuf>,
+    /// Install into the environment of this interpreter instead of a venv
+    #[arg(long, value_name = "INTERPRETER")]
+    python: Option<PathBuf>,
+    /// Let `--python` install into an interpreter that is not in a virtual environment
+    #[arg(long, requires = "python")]
+    allow_system_python: bool,
+}
+
+impl EnvArgs {
+    /// Overrides the provider in the app's settings
+    fn provider(&self) -> Option<EnvProviderConfig> {
+        if self.uv {
+            Some(EnvProviderConfig::Uv)
+        } else if let Some(prefix) = &self.conda_env {
+            Some(EnvProviderConfig::Conda { prefix: prefix.clone() })
+        } else {
+            self.python.clone().map(|python| EnvProviderConfig::Interpreter {
+                python,
+                allow_system: self.allow_system_python,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+/// Summary printed by `parse`
+#[derive(Debug, Serialize)]
+struct ParseSummary {
+    grammar: String,
+    input: String,
+    steps: usize,
+    /// Ids of the steps that hit a syntax error
+    error_steps: Vec<usize>,
+}
+
+/// Run the CLI if the first argument names one of its subcommands.
+/// Returns `None` if the app should start as usual.
+pub fn run_from_args() -> Option<ExitCode> {
+    let first = std::env::args().nth(1)?;
+    let is_subcommand = first == "help"
+        || Cli::command()
+            .get_subcommands()
+            .any(|c| c.get_name() == first);
+    if !is_subcommand {
+        return None;
+    }
+    #[cfg(windows)]
+    attach_console();
+
+    let cli = Cli::parse();
+    Some(match run(cli.command) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            if let CommandError::PythonException {
+                traceback: Some(tb), ..
+            } = &e
+            {
+                eprintln!("{}", tb.trim_end());
+            }
+            ExitCode::FAILURE
+        }
+    })
+}
+
+/// Release builds are GUI programs on Windows and start without a console,
+/// so the CLI borrows the one of the terminal it was started from. Output
+/// redirected to a file or pipe works without it. The terminal doesn't wait
+/// for GUI programs, so its prompt may show up before the output.
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // SAFETY: AttachConsole takes a plain process id and only fails, e.g.
+    // without a parent console, which leaves the process as it was
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+fn run(command: CliCommand) -> CommandResult<ExitCode> {
+    match command {
+        CliCommand::Parse(args) => with_parsed(&args, |py, parse_info| {
+            let mut summary = ParseSummary {
+                grammar: args.grammar.display().to_string(),
+                input: args.input.display().to_string(),
+                steps: 0,
+                error_steps: Vec::new(),
+            };
+            debugger::visit_trace(py, parse_info, |step| {
+                summary.steps += 1;
+                if step.matching_error || step.is_error_node {
+                    summary.error_steps.push(step.index()?);
+                }
+                Ok(())
+            })?;
+            print_summary(args.format, &summary)?;
+            Ok(if summary.error_steps.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(EXIT_SYNTAX_ERRORS)
+            })
+        }),
+        CliCommand::Trace(args) => with_parsed(&args, |py, parse_info| {
+            let mut steps = Vec::new();
+            debugger::visit_trace(py, parse_info, |step| {
+                steps.push(step);
+                Ok(())
+            })?;
+            print_trace(args.format, &steps)?;
+            Ok(ExitCode::SUCCESS)
+        }),
+        CliCommand::Tokens(args) => with_parsed(&args, |py, parse_info| {
+            let tokens = debugger::token_list(py, parse_info)?;
+            match args.format {
+                Format::Json => print_json(&tokens)?,
+                Format::Text => {
+                    let mut out = io::stdout().lock();
+                    for t in &tokens {
+                        writeln!(out, "{}\t{}:{}\t{}\t{:?}", t.token_index, t.line, t.column, t.type_name, t.text)
+                            .map_err(stdout_error)?;
+                    }
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }),
+        CliCommand::Tree(args) => with_parsed(&args, |py, parse_info| {
+            let tree = debugger::final_parse_tree(py, parse_info)?;
+            match args.format {
+                Format::Json => print_json(&tree)?,
+                Format::Text => {
+                    let mut out = io::stdout().lock();
+                    print_tree(&mut out, &tree, 0).map_err(stdout_error)?;
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }),
+        CliCommand::Corpus(args) => {
+            let files = corpus::expand_inputs(&args.inputs, None)?;
+            start_python(&args.env)?;
+            let grammar = args.grammar.display().to_string();
+            let report = Python::with_gil(|py| {
+                keep_stdout_clean(py)?;
+                corpus::run(py, &grammar, &args.inputs, &files, |index, case| {
+                    if args.format == Format::Text {
+                        eprintln!("[{}/{}] {:?} {}", index + 1, files.len(), case.status, case.input);
+                    }
+                })
+            })?;
+
+            if let Some(path) = &args.junit {
+                std::fs::write(path, corpus::junit_xml(&report)).map_err(|e| CommandError::io(path, e))?;
+            }
+            match args.format {
+                Format::Json => print_json(&report)?,
+                Format::Text => print!("{}", corpus::summary_table(&report)),
+            }
+            Ok(if report.all_passed() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(EXIT_SYNTAX_ERRORS)
+            })
+        }
+        CliCommand::Snapshot(args) => {
+            let files = corpus::expand_inputs(&args.inputs, None)?;
+            start_python(&args.env)?;
+            let grammar = args.grammar.display().to_string();
+            let results = Python::with_gil(|py| {
+                keep_stdout_clean(py)?;
+                snapshot::check_inputs(py, &grammar, &files, args.snapshot_dir.as_deref(), args.update, |result| {
+                    if args.format == Format::Text {
+                        print_snapshot_result(result);
+                    }
+                })
+            })?;
+
+            if args.format == Format::Json {
+                print_json(&results)?;
+            }
+            let all_good = results
+                .iter()
+                .all(|r| matches!(r.status, SnapshotStatus::Unchanged | SnapshotStatus::Updated));
+            Ok(if all_good {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(EXIT_SYNTAX_ERRORS)
+            })
+        }
+        CliCommand::Dap(args) => {
+            // On stdio, stdout belongs to the protocol
+            let protocol = match args.port {
+                Some(_) => None,
+                None => Some(take_stdout()?),
+            };
+            start_python(&args.env)?;
+            Python::with_gil(keep_stdout_clean)?;
+            match (args.port, protocol) {
+                (Some(port), _) => dap::serve_tcp(port)?,
+                (None, protocol) => dap::serve_stdio(protocol.expect("taken above"))?,
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        CliCommand::Lsp(args) => {
+            let protocol = take_stdout()?;
+            start_python(&args.env)?;
+            Python::with_gil(keep_stdout_clean)?;
+            lsp::serve_stdio(protocol, args.grammar)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        CliCommand::Wheelhouse(args) => {
+            let report = python_env::build_wheelhouse(&HeadlessHost::new(&args.env), &args.dir)?;
+            println!("{} wheels in {}", report.wheels.len(), report.dir);
+            match &report.antlr_jar {
+                Some(jar) => println!("ANTLR tool: {jar}"),
+                None => eprintln!("No ANTLR jar found in ~/.m2 to copy; run the app once online first"),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        CliCommand::Doctor(args) => {
+            let report = diagnostics::run(&HeadlessHost::new(&args.env));
+            match args.format {
+                Format::Text => print!("{}", report.report),
+                Format::Json => print_json(&report)?,
+            }
+            Ok(if report.has_failures() {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            })
+        }
+    }
+}
+
+/// Set up Python the same way the app does.
+fn start_python(env: &EnvArgs) -> CommandResult<()> {
+    let host = HeadlessHost::new(env);
+    host.init_log_dir();
+    python_env::ensure_python_sync(&host)?;
+    Ok(())
+}
+
+/// Generate the parser, parse the input and hand the `ParseInformation` to `f`.
+fn with_parsed(
+    args: &InputArgs,
+    f: impl FnOnce(Python<'_>, &Py<PyAny>) -> CommandResult<ExitCode>,
+) -> CommandResult<ExitCode> {
+    let input = read_input(&args.input)?;
+    start_python(&args.env)?;
+
+    Python::with_gil(|py| {
+        keep_stdout_clean(py)?;
+        let mut session = ParseSession::open(py, args.grammar.display().to_string())?;
+        session.generate_parser(py)?;
+        session.parse(py, input)?;
+        f(py, session.parsed()?)
+    })
+}
+
+/// `SetupHost` for the terminal: progress goes to stderr, dialogs become messages.
+struct HeadlessHost {
+    venv_dir: Option<PathBuf>,
+    wheelhouse: Option<PathBuf>,
+    provider: Option<EnvProviderConfig>,
+}
+
+impl HeadlessHost {
+    fn new(args: &EnvArgs) -> Self {
+        Self {
+            venv_dir: args.venv_dir.clone(),
+            wheelhouse: args.wheelhouse.clone(),
+            provider: args.provider(),
+        }
+    }
+
+    /// Same place as the app's `<app data>/logs`
+    fn init_log_dir(&self) {
+        if let Some(dir) = dirs::data_dir() {
+            traceback::init_log_dir(&dir.join(APP_IDENTIFIER).join("logs"));
+        }
+    }
+}
+
+impl SetupHost for HeadlessHost {
+    fn venv_dir(&self) -> anyhow::Result<PathBuf> {
+        match &self.venv_dir {
+            Some(dir) => Ok(dir.clone()),
+            // Where Tauri puts `BaseDirectory::AppConfig`
+            None => dirs::config_dir()
+                .map(|dir| dir.join(APP_IDENTIFIER).join("pyenv"))
+                .context("cannot resolve the config dir"),
+        }
+    }
+
+    fn resource_path(&self, rel: &str) -> Option<PathBuf> {
+        let exe = std::env::current_exe().ok()?;
+        let dir = exe.parent()?;
+        if cfg!(target_os = "macos") {
+            Some(dir.join("../Resources").join(rel))
+        } else {
+            Some(dir.join(rel))
+        }
+    }
+
+    fn progress(&self, progress: PySetupProgress) {
+        // Errors are reported once, by whoever gives up; pip's progress is
+        // in its log lines already
+        if !matches!(progress, PySetupProgress::Error(_) | PySetupProgress::InstallingPackage { .. }) {
+            eprintln!("python setup: {progress:?}");
+        }
+    }
+
+    fn log(&self, log: PySetupLog) {
+        eprintln!("{}: {}", log.source, log.line);
+    }
+
+    fn show_dialog(&self, title: &str, message: &str) {
+        eprintln!("{title}: {message}");
+    }
+
+    fn wheelhouse(&self) -> Option<PathBuf> {
+        self.wheelhouse.clone()
+    }
+
+    fn provider(&self) -> Option<EnvProviderConfig> {
+        self.provider.clone()
+    }
+}
+
+fn read_input(path: &Path) -> CommandResult<String> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| CommandError::io(path, e))?;
+        return Ok(input);
+    }
+    std::fs::read_to_string(path).map_err(|e| CommandError::io(path, e))
+}
+
+/// Send anything the Python side prints to stderr, so stdout only carries
+/// our own output and can be piped into other tools.
+fn keep_stdout_clean(py: Python<'_>) -> CommandResult<()> {
+    let sys = py.import("sys")?;
+    sys.setattr("stdout", sys.getattr("stderr")?)?;
+    Ok(())
+}
+
+/// Point fd 1 at stderr and return the original stdout for a protocol to
+/// speak on. Unlike `keep_stdout_clean` this also catches what C extensions
+/// and child processes, e.g. antlr4 and java during `generate_parser`, print.
+#[cfg(unix)]
+fn take_stdout() -> CommandResult<Box<dyn Write + Send>> {
+    use std::os::fd::{AsFd, AsRawFd};
+
+    let stdout = io::stdout();
+    let stdout_error = |e| CommandError::io(Path::new("<stdout>"), e);
+    stdout.lock().flush().map_err(stdout_error)?;
+    // Close-on-exec, so children don't inherit the protocol stream
+    let protocol = stdout.as_fd().try_clone_to_owned().map_err(stdout_error)?;
+    // SAFETY: dup2 only swaps what fd 1 refers to; both fds stay open and
+    // nothing else owns fd 1 as a raw descriptor
+    if unsafe { libc::dup2(io::stderr().as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return Err(stdout_error(io::Error::last_os_error()));
+    }
+    Ok(Box::new(std::fs::File::from(protocol)))
+}
+
+/// Child processes write to the standard handles directly on Windows, so
+/// only Python's `sys.stdout` is kept off the protocol there.
+#[cfg(not(unix))]
+fn take_stdout() -> CommandResult<Box<dyn Write + Send>> {
+    Ok(Box::new(io::stdout()))
+}
+
+fn print_summary(format: Format, summary: &ParseSummary) -> CommandResult<()> {
+    if format == Format::Json {
+        return print_json(summary);
+    }
+    let status = match summary.error_steps.len() {
+        0 => "no syntax errors".to_string(),
+        n => format!("{n} steps with syntax errors, first at step {}", summary.error_steps[0]),
+    };
+    println!("{}: {} steps, {status}", summary.input, summary.steps);
+    Ok(())
+}
+
+fn print_trace(format: Format, steps: &[ParseStepInfo]) -> CommandResult<()> {
+    if format == Format::Json {
+        return print_json(steps);
+    }
+    let mut out = io::stdout().lock();
+    for step in steps {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}{}",
+            step.step_id,
+            step.step_type,
+            step.rule_name.as_deref().unwrap_or("-"),
+            step.current_token_repr,
+            if step.matching_error { "\terror" } else { "" },
+        )
+        .map_err(stdout_error)?;
+    }
+    Ok(())
+}
+
+fn print_snapshot_result(result: &SnapshotResult) {
+    println!("{:?}\t{}", result.status, result.input);
+    if !result.changes.is_empty() {
+        for line in snapshot::format_changes(&result.changes).lines() {
+            println!("    {line}");
+        }
+    }
+    if let Some(message) = &result.message {
+        println!("    {}", message.lines().next().unwrap_or_default());
+    }
+}
+
+/// One node per line, children indented below their parent
+fn print_tree(out: &mut impl Write, node: &serde_json::Value, depth: usize) -> io::Result<()> {
+    let label = node["rule_name"]
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| node["token"].as_str().map(|t| format!("{t:?}")))
+        .unwrap_or_else(|| node["node_type"].as_str().unwrap_or("?").to_string());
+    writeln!(out, "{:indent$}{label}", "", indent = depth * 2)?;
+    for child in node["children"].as_array().into_iter().flatten() {
+        print_tree(out, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> CommandResult<()> {
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, value)
+        .map_err(|e| stdout_error(e.into()))?;
+    writeln!(out).map_err(stdout_error)
+}
+
+fn stdout_error(err: io::Error) -> CommandError {
+    CommandError::io(Path::new("<stdout>"), err)
+}
@@ -0,0 +1,164 @@
+// ==============================
+// src-tauri/src/commands.rs
+// ==============================
+
+//! The bodies of the session commands. The Tauri commands in `main.rs`, the
+//! JSON-RPC API and the DAP server all call these, so they answer the same.
+
+use pyo3::prelude::*;
+
+use crate::breakpoints::{Breakpoint, BreakpointSpec, ContinueResult};
+use crate::debugger::{self, ParseStepInfo, TokenInfo};
+use crate::error::{CommandError, CommandResult};
+use crate::expr::{self, WatchValue};
+use crate::grammar::{self, UserGrammar};
+use crate::session::{ParseInfoStore, ParseSession};
+
+/// Lock the session `id` of the store and run `f` on it with the GIL held
+pub fn with_session<T>(
+    store: &ParseInfoStore,
+    id: usize,
+    f: impl FnOnce(Python<'_>, &mut ParseSession) -> CommandResult<T>,
+) -> CommandResult<T> {
+    let handle = store.session(id)?;
+    let mut session = handle.lock()?;
+    Python::with_gil(|py| f(py, &mut session))
+}
+
+/// Open a ParseInformation instance for the grammar; returns its id
+pub fn open(store: &ParseInfoStore, grammar: String) -> CommandResult<usize> {
+    let session = Python::with_gil(|py| ParseSession::open(py, grammar))?;
+    store.insert(session)
+}
+
+pub fn generate_parser(py: Python<'_>, session: &ParseSession) -> CommandResult<String> {
+    session.generate_parser(py)?;
+    Ok("Generated parser successfully".to_string())
+}
+
+pub fn parse_input(py: Python<'_>, session: &mut ParseSession, input: String) -> CommandResult<String> {
+    session.parse(py, input)?;
+    Ok("Parsed successfully".to_string())
+}
+
+pub fn go_to_step(py: Python<'_>, session: &ParseSession, step_id: usize) -> CommandResult<String> {
+    debugger::go_to_step(py, session.parsed()?, step_id)?;
+    Ok("Went to indicated step successfully".to_string())
+}
+
+pub fn step_forwards(py: Python<'_>, session: &ParseSession, step: usize) -> CommandResult<String> {
+    session.parsed()?.getattr(py, "step_forward")?.call1(py, (step,))?;
+    Ok("Stepped successfully".to_string())
+}
+
+pub fn step_backwards(py: Python<'_>, session: &ParseSession) -> CommandResult<String> {
+    session.parsed()?.getattr(py, "step_backwards")?.call0(py)?;
+    Ok("Stepped successfully".to_string())
+}
+
+pub fn step_until_next_decision(py: Python<'_>, session: &ParseSession) -> CommandResult<String> {
+    session.parsed()?.getattr(py, "step_until_next_decision")?.call0(py)?;
+    Ok("Went to indicated step successfully".to_string())
+}
+
+pub fn step_back_until_previous_decision(py: Python<'_>, session: &ParseSession) -> CommandResult<String> {
+    session
+        .parsed()?
+        .getattr(py, "step_back_until_previous_decision")?
+        .call0(py)?;
+    Ok("Went to indicated step successfully".to_string())
+}
+
+pub fn set_breakpoint(session: &mut ParseSession, breakpoint: BreakpointSpec) -> CommandResult<Breakpoint> {
+    session.breakpoints.set(breakpoint)
+}
+
+pub fn remove_breakpoint(session: &mut ParseSession, breakpoint_id: u32) -> bool {
+    session.breakpoints.remove(breakpoint_id)
+}
+
+pub fn list_breakpoints(session: &ParseSession) -> Vec<Breakpoint> {
+    session.breakpoints.list().to_vec()
+}
+
+/// Watch expressions at `step_id`, or at the current step
+pub fn evaluate_watches(
+    py: Python<'_>,
+    session: &ParseSession,
+    expressions: &[String],
+    step_id: Option<usize>,
+) -> CommandResult<Vec<WatchValue>> {
+    let parse_info = session.parsed()?;
+    let step = match step_id {
+        Some(step_id) => debugger::peek_step(py, parse_info, step_id)?,
+        None => debugger::current_step(py, parse_info)?,
+    };
+    Ok(expr::watch(expressions, &step))
+}
+
+/// Step forward until a step hits an enabled breakpoint, or to the last step
+pub fn continue_to_breakpoint(py: Python<'_>, session: &ParseSession) -> CommandResult<ContinueResult> {
+    run_to_breakpoint(py, session, true)
+}
+
+/// Step backward until a step hits an enabled breakpoint, or to the first step
+pub fn reverse_continue(py: Python<'_>, session: &ParseSession) -> CommandResult<ContinueResult> {
+    run_to_breakpoint(py, session, false)
+}
+
+fn run_to_breakpoint(py: Python<'_>, session: &ParseSession, forward: bool) -> CommandResult<ContinueResult> {
+    let parse_info = session.parsed()?;
+    let matcher = session.breakpoints.matcher(py, parse_info)?;
+    let stop = |step: &ParseStepInfo| Ok(!matcher.hits(step).is_empty());
+    let (step, _) = if forward {
+        debugger::run_forward(py, parse_info, stop)?
+    } else {
+        debugger::run_backward(py, parse_info, stop)?
+    };
+    let hit = matcher.hits(&step);
+    Ok(ContinueResult { step, hit })
+}
+
+pub fn user_grammar(py: Python<'_>, session: &ParseSession) -> CommandResult<UserGrammar> {
+    grammar::user_grammar(py, &session.parse_info)
+}
+
+pub fn current_step(py: Python<'_>, session: &ParseSession) -> CommandResult<ParseStepInfo> {
+    debugger::current_step(py, session.parsed()?)
+}
+
+/// The step after the current one, `None` at the last step; the cursor stays
+pub fn next_step(py: Python<'_>, session: &ParseSession) -> CommandResult<Option<ParseStepInfo>> {
+    let parse_info = session.parsed()?;
+    let current = debugger::current_step(py, parse_info)?.index()?;
+    match debugger::peek_step(py, parse_info, current + 1) {
+        Ok(step) => Ok(Some(step)),
+        Err(CommandError::StepOutOfRange { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn step_info(py: Python<'_>, session: &ParseSession, step_id: usize) -> CommandResult<ParseStepInfo> {
+    debugger::peek_step(py, session.parsed()?, step_id)
+}
+
+pub fn step_range(py: Python<'_>, session: &ParseSession, from: usize, to: usize) -> CommandResult<Vec<ParseStepInfo>> {
+    debugger::peek_steps(py, session.parsed()?, from, to)
+}
+
+pub fn full_trace(py: Python<'_>, session: &ParseSession) -> CommandResult<Vec<ParseStepInfo>> {
+    let mut steps = Vec::new();
+    debugger::visit_trace(py, session.parsed()?, |step| {
+        steps.push(step);
+        Ok(())
+    })?;
+    Ok(steps)
+}
+
+pub fn token_list(py: Python<'_>, session: &ParseSession) -> CommandResult<Vec<TokenInfo>> {
+    debugger::token_list(py, session.parsed()?)
+}
+
+pub fn parse_tree(py: Python<'_>, session: &ParseSession) -> CommandResult<serde_json::Value> {
+    debugger::parse_tree(py, session.parsed()?)
+}
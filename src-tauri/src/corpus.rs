@@ -0,0 +1,400 @@
+// ==============================
+// src-tauri/src/corpus.rs
+// ==============================
+
+//! Regression runs over a corpus: parse every input matching a glob with one
+//! generated parser and report which of them hit syntax errors.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::debugger;
+use crate::error::{CommandError, CommandResult};
+use crate::session::ParseSession;
+
+/// Error steps listed per input; the count covers all of them
+const MAX_REPORTED_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum CaseStatus {
+    Passed,
+    /// Parsed, but with `matching_error` or `is_error_node` steps
+    Failed,
+    /// The input could not be read or parsing raised
+    Errored,
+}
+
+/// One step at which the parser hit a syntax error
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseError {
+    pub step_id: usize,
+    pub rule_name: Option<String>,
+    pub token: String,
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorpusCase {
+    pub input: String,
+    pub status: CaseStatus,
+    pub steps: usize,
+    pub error_count: usize,
+    /// The first `MAX_REPORTED_ERRORS` error steps
+    pub errors: Vec<CaseError>,
+    /// Why the input errored
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorpusReport {
+    pub grammar: String,
+    pub pattern: String,
+    pub cases: Vec<CorpusCase>,
+    pub passed: usize,
+    pub failed: usize,
+    pub errored: usize,
+    pub duration_ms: u64,
+    /// RFC 3339 timestamp of the start of the run
+    pub started_at: String,
+}
+
+impl CorpusReport {
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0 && self.errored == 0
+    }
+}
+
+/// The files matching `pattern`, sorted. A relative pattern is taken relative to `base`.
+pub fn expand_inputs(pattern: &str, base: Option<&Path>) -> CommandResult<Vec<PathBuf>> {
+    let pattern = match base {
+        Some(base) if Path::new(pattern).is_relative() => base.join(pattern).display().to_string(),
+        _ => pattern.to_string(),
+    };
+    let paths = glob::glob(&pattern)
+        .map_err(|e| CommandError::invalid_argument(format!("Invalid input pattern {pattern:?}: {e}")))?;
+    let mut inputs: Vec<PathBuf> = paths.flatten().filter(|p| p.is_file()).collect();
+    if inputs.is_empty() {
+        return Err(CommandError::invalid_argument(format!("No input files match {pattern:?}")));
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Generate the parser once, then parse each input and check its trace.
+/// `on_case` sees every case as soon as it is done.
+pub fn run(
+    py: Python<'_>,
+    grammar: &str,
+    pattern: &str,
+    inputs: &[PathBuf],
+    mut on_case: impl FnMut(usize, &CorpusCase),
+) -> CommandResult<CorpusReport> {
+    let started = Instant::now();
+    let started_at = chrono::Local::now().to_rfc3339();
+    let mut session = ParseSession::open(py, grammar.to_string())?;
+    session.generate_parser(py)?;
+
+    let mut cases = Vec::with_capacity(inputs.len());
+    for (index, input) in inputs.iter().enumerate() {
+        let case_started = Instant::now();
+        let case = match check_input(py, &mut session, input) {
+            Ok((steps, error_count, errors)) => CorpusCase {
+                input: input.display().to_string(),
+                status: if error_count == 0 {
+                    CaseStatus::Passed
+                } else {
+                    CaseStatus::Failed
+                },
+                steps,
+                error_count,
+                errors,
+                message: None,
+                duration_ms: millis(case_started.elapsed()),
+            },
+            // A cancelled run stops here instead of failing every input left
+            Err(e) if e.is_interrupt() => return Err(e),
+            Err(e) => CorpusCase {
+                input: input.display().to_string(),
+                status: CaseStatus::Errored,
+                steps: 0,
+                error_count: 0,
+                errors: Vec::new(),
+                message: Some(e.to_string()),
+                duration_ms: millis(case_started.elapsed()),
+            },
+        };
+        on_case(index, &case);
+        cases.push(case);
+    }
+
+    let count = |status| cases.iter().filter(|c| c.status == status).count();
+    Ok(CorpusReport {
+        grammar: grammar.to_string(),
+        pattern: pattern.to_string(),
+        passed: count(CaseStatus::Passed),
+        failed: count(CaseStatus::Failed),
+        errored: count(CaseStatus::Errored),
+        cases,
+        duration_ms: millis(started.elapsed()),
+        started_at,
+    })
+}
+
+/// Parse one input; returns the step count, the number of error steps and
+/// the first few of them.
+fn check_input(
+    py: Python<'_>,
+    session: &mut ParseSession,
+    input: &Path,
+) -> CommandResult<(usize, usize, Vec<CaseError>)> {
+    let text = fs::read_to_string(input).map_err(|e| CommandError::io(input, e))?;
+    session.parse(py, text)?;
+
+    let mut steps = 0;
+    let mut error_count = 0;
+    let mut errors = Vec::new();
+    debugger::visit_trace(py, session.parsed()?, |step| {
+        steps += 1;
+        if step.matching_error || step.is_error_node {
+            error_count += 1;
+            if errors.len() < MAX_REPORTED_ERRORS {
+                errors.push(CaseError {
+                    step_id: step.index()?,
+                    rule_name: step.rule_name,
+                    token: step.current_token_repr,
+                    context: step.input_context_snippet,
+                });
+            }
+        }
+        Ok(())
+    })?;
+    Ok((steps, error_count, errors))
+}
+
+/// Plain-text table of all cases plus a closing summary line
+pub fn summary_table(report: &CorpusReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<7} {:>8} {:>7} {:>9}  INPUT", "RESULT", "STEPS", "ERRORS", "TIME");
+    for case in &report.cases {
+        let result = match case.status {
+            CaseStatus::Passed => "pass",
+            CaseStatus::Failed => "FAIL",
+            CaseStatus::Errored => "ERROR",
+        };
+        let _ = writeln!(
+            out,
+            "{result:<7} {:>8} {:>7} {:>6} ms  {}",
+            case.steps, case.error_count, case.duration_ms, case.input
+        );
+        if let Some(message) = &case.message {
+            let _ = writeln!(out, "        {}", message.lines().next().unwrap_or_default());
+        }
+    }
+    let _ = writeln!(
+        out,
+        "{} inputs: {} passed, {} failed, {} errored in {:.1} s",
+        report.cases.len(),
+        report.passed,
+        report.failed,
+        report.errored,
+        report.duration_ms as f64 / 1000.0
+    );
+    out
+}
+
+/// JUnit XML with one test case per input, as understood by CI servers
+pub fn junit_xml(report: &CorpusReport) -> String {
+    let suite = Path::new(&report.grammar)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| report.grammar.clone());
+    let attrs = format!(
+        r#"tests="{}" failures="{}" errors="{}" time="{}""#,
+        report.cases.len(),
+        report.failed,
+        report.errored,
+        seconds(report.duration_ms)
+    );
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(out, "<testsuites {attrs}>");
+    let _ = writeln!(
+        out,
+        r#"  <testsuite name="{}" timestamp="{}" {attrs}>"#,
+        xml_escape(&suite),
+        xml_escape(&report.started_at)
+    );
+    for case in &report.cases {
+        let _ = write!(
+            out,
+            r#"    <testcase name="{}" classname="{}" time="{}""#,
+            xml_escape(&case.input),
+            xml_escape(&suite),
+            seconds(case.duration_ms)
+        );
+        match case.status {
+            CaseStatus::Passed => out.push_str("/>\n"),
+            CaseStatus::Failed => {
+                let details: Vec<String> = case
+                    .errors
+                    .iter()
+                    .map(|e| {
+                        format!(
+                            "step {} in rule {} at {}{}",
+                            e.step_id,
+                            e.rule_name.as_deref().unwrap_or("?"),
+                            e.token,
+                            e.context.as_deref().map(|c| format!(": {c}")).unwrap_or_default()
+                        )
+                    })
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    ">\n      <failure type=\"SyntaxError\" message=\"{} steps with syntax errors\">{}</failure>\n    </testcase>",
+                    case.error_count,
+                    xml_escape(&details.join("\n"))
+                );
+            }
+            CaseStatus::Errored => {
+                let message = case.message.as_deref().unwrap_or_default();
+                let _ = writeln!(
+                    out,
+                    ">\n      <error message=\"{}\">{}</error>\n    </testcase>",
+                    xml_escape(message.lines().next().unwrap_or_default()),
+                    xml_escape(message)
+                );
+            }
+        }
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Not allowed in XML 1.0 at all
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+fn millis(d: Duration) -> u64 {
+    d.as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(input: &str, status: CaseStatus) -> CorpusCase {
+        CorpusCase {
+            input: input.to_string(),
+            status,
+            steps: 12,
+            error_count: 0,
+            errors: Vec::new(),
+            message: None,
+            duration_ms: 1500,
+        }
+    }
+
+    fn report() -> CorpusReport {
+        let failed = CorpusCase {
+            error_count: 2,
+            errors: vec![CaseError {
+                step_id: 7,
+                rule_name: Some("vita".to_string()),
+                token: "'<&>'".to_string(),
+                context: Some("expected \"KOMMA\"".to_string()),
+            }],
+            ..case("b & c.txt", CaseStatus::Failed)
+        };
+        let errored = CorpusCase {
+            message: Some("Could not read <c>\nsecond line".to_string()),
+            ..case("c.txt", CaseStatus::Errored)
+        };
+        CorpusReport {
+            grammar: "/grammars/Regest.g4".to_string(),
+            pattern: "*.txt".to_string(),
+            cases: vec![case("a.txt", CaseStatus::Passed), failed, errored],
+            passed: 1,
+            failed: 1,
+            errored: 1,
+            duration_ms: 4500,
+            started_at: "2024-05-01T12:00:00+02:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn escapes_xml_and_drops_control_characters() {
+        assert_eq!(xml_escape("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;");
+        assert_eq!(xml_escape("a\u{1}\tb\n"), "a\tb\n");
+    }
+
+    #[test]
+    fn writes_junit_counts_and_escaped_failures() {
+        let xml = junit_xml(&report());
+        assert!(xml.contains(concat!(
+            r#"<testsuite name="Regest.g4" timestamp="2024-05-01T12:00:00+02:00" "#,
+            r#"tests="3" failures="1" errors="1" time="4.500">"#
+        )));
+        assert!(xml.contains(r#"<testcase name="a.txt" classname="Regest.g4" time="1.500"/>"#));
+        assert!(xml.contains(r#"<testcase name="b &amp; c.txt""#));
+        assert!(xml.contains(concat!(
+            r#"<failure type="SyntaxError" message="2 steps with syntax errors">"#,
+            r#"step 7 in rule vita at &apos;&lt;&amp;&gt;&apos;: expected &quot;KOMMA&quot;</failure>"#
+        )));
+        assert!(xml.contains(r#"<error message="Could not read &lt;c&gt;">Could not read &lt;c&gt;"#));
+        assert!(xml.ends_with("  </testsuite>\n</testsuites>\n"));
+    }
+
+    #[test]
+    fn summarizes_every_case() {
+        let table = summary_table(&report());
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("RESULT"));
+        assert_eq!(lines[1], "pass          12       0   1500 ms  a.txt");
+        assert!(lines[2].starts_with("FAIL "));
+        assert_eq!(lines[4], "        Could not read <c>");
+        assert_eq!(lines[5], "3 inputs: 1 passed, 1 failed, 1 errored in 4.5 s");
+    }
+
+    #[test]
+    fn expands_relative_patterns_against_the_base() {
+        let base = std::env::temp_dir().join(format!("paredros-corpus-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(base.join("inputs.txt")).unwrap();
+        for name in ["b.txt", "a.txt", "c.md"] {
+            fs::write(base.join(name), "").unwrap();
+        }
+
+        let inputs = expand_inputs("*.txt", Some(&base));
+        let absolute = expand_inputs(&base.join("*.md").display().to_string(), Some(Path::new("/elsewhere")));
+        let nothing = expand_inputs("*.g4", Some(&base));
+        fs::remove_dir_all(&base).unwrap();
+
+        // Sorted, and without the directory that matches too
+        assert_eq!(inputs.unwrap(), [base.join("a.txt"), base.join("b.txt")]);
+        assert_eq!(absolute.unwrap(), [base.join("c.md")]);
+        assert!(nothing.is_err());
+    }
+}
@@ -0,0 +1,586 @@
+// ==============================
+// src-tauri/src/dap.rs
+// ==============================
+
+//! Debug Adapter Protocol server, so a parse can be stepped through from an
+//! editor: `paredros-app dap` speaks DAP on stdin/stdout, `--port` on a TCP
+//! port instead. Any local user can connect to the port, and grammar actions
+//! run as Python in this process, so `launch` there needs the token the
+//! server prints or takes from `PAREDROS_DAP_TOKEN`.
+//!
+//! The parse is the single thread `parser`; its rule stack is the call stack,
+//! with the grammar rules as source positions. `stepIn` takes one step, `next`
+//! and `stepBack` go to the next or previous decision, and line breakpoints in
+//! `.g4` files stop on entering the rule defined there.
+
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+
+use crate::breakpoints::{BreakpointKind, BreakpointSpec, ContinueResult};
+use crate::commands;
+use crate::debugger;
+use crate::error::{CommandError, CommandResult};
+use crate::expr::{self, Expr};
+use crate::files;
+use crate::framing;
+use crate::grammar;
+use crate::session::ParseSession;
+
+/// The parse is the only thread
+const THREAD_ID: i64 = 1;
+
+/// Filter of `setExceptionBreakpoints` that stops on syntax errors
+const SYNTAX_ERROR_FILTER: &str = "syntaxError";
+
+/// Token TCP clients must pass to `launch`; a random one is made without it
+pub const TOKEN_ENV: &str = "PAREDROS_DAP_TOKEN";
+
+/// Serve one client on stdin and `output`, the original stdout.
+pub fn serve_stdio(output: impl Write) -> CommandResult<()> {
+    let stdin = io::stdin();
+    Connection::new(stdin.lock(), output, None).serve()
+}
+
+/// Serve clients on `127.0.0.1:port`, one after the other. Each has to pass
+/// the token as `"token"` in its launch configuration.
+pub fn serve_tcp(port: u16) -> CommandResult<()> {
+    let address = format!("127.0.0.1:{port}");
+    let listener = TcpListener::bind(&address).map_err(|e| CommandError::io(Path::new(&address), e))?;
+    let token = match std::env::var(TOKEN_ENV) {
+        Ok(token) if !token.is_empty() => {
+            eprintln!("DAP server listening on {address}, launch needs the token in {TOKEN_ENV}");
+            token
+        }
+        _ => {
+            let token = uuid::Uuid::new_v4().simple().to_string();
+            eprintln!("DAP server listening on {address}, launch needs \"token\": \"{token}\"");
+            token
+        }
+    };
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| CommandError::io(Path::new(&address), e))?;
+        let reader = stream.try_clone().map_err(|e| CommandError::io(Path::new(&address), e))?;
+        if let Err(e) = Connection::new(reader, stream, Some(token.clone())).serve() {
+            eprintln!("DAP client failed: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Where a rule is defined, for stack frames and line breakpoints
+struct RuleSource {
+    path: String,
+    start_line: i64,
+    end_line: i64,
+}
+
+/// Arguments of the `launch` request
+struct Launch {
+    session: ParseSession,
+    input_path: PathBuf,
+    stop_on_entry: bool,
+    rules: HashMap<String, RuleSource>,
+    /// Line (1-based) and column (0-based) of each token, by token index
+    token_positions: HashMap<usize, (i32, i32)>,
+}
+
+struct Connection<R, W> {
+    reader: BufReader<R>,
+    writer: W,
+    /// Token `launch` must carry; `None` on stdio, where only the editor can talk
+    token: Option<String>,
+    seq: i64,
+    launch: Option<Launch>,
+    /// Breakpoint ids per source file, replaced by each `setBreakpoints`
+    source_breakpoints: HashMap<String, Vec<u32>>,
+    function_breakpoints: Vec<u32>,
+    exception_breakpoints: Vec<u32>,
+    /// Values behind `variablesReference` n + 1; reset whenever the cursor moves
+    variables: Vec<Value>,
+    /// Events to send after the response to the current request
+    pending: Vec<Value>,
+}
+
+impl<R: Read, W: Write> Connection<R, W> {
+    fn new(reader: R, writer: W, token: Option<String>) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+            token,
+            seq: 0,
+            launch: None,
+            source_breakpoints: HashMap::new(),
+            function_breakpoints: Vec::new(),
+            exception_breakpoints: Vec::new(),
+            variables: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn serve(mut self) -> CommandResult<()> {
+        while let Some(request) = framing::read_message(&mut self.reader).map_err(transport_error)? {
+            if request["type"] != "request" {
+                continue;
+            }
+            let command = request["command"].as_str().unwrap_or_default().to_string();
+            let arguments = request.get("arguments").cloned().unwrap_or(Value::Null);
+
+            let result = Python::with_gil(|py| self.handle(py, &command, &arguments));
+            let mut response = json!({
+                "type": "response",
+                "request_seq": request["seq"],
+                "command": command,
+                "success": result.is_ok(),
+            });
+            match result {
+                Ok(Value::Null) => {}
+                Ok(body) => response["body"] = body,
+                Err(e) => response["message"] = e.to_string().into(),
+            }
+            self.send(response).map_err(transport_error)?;
+            for event in std::mem::take(&mut self.pending) {
+                self.send(event).map_err(transport_error)?;
+            }
+
+            if matches!(command.as_str(), "disconnect" | "terminate") {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, py: Python<'_>, command: &str, args: &Value) -> CommandResult<Value> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsStepBack": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsEvaluateForHovers": true,
+                "supportsTerminateRequest": true,
+                "exceptionBreakpointFilters": [{
+                    "filter": SYNTAX_ERROR_FILTER,
+                    "label": "Syntax errors",
+                    "default": false,
+                }],
+            })),
+            "launch" => {
+                self.launch(py, args)?;
+                self.pending.push(event("initialized", Value::Null));
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setFunctionBreakpoints" => self.set_function_breakpoints(args),
+            "setExceptionBreakpoints" => self.set_exception_breakpoints(args),
+            "configurationDone" => {
+                let stop_on_entry = self.launched()?.stop_on_entry;
+                if stop_on_entry {
+                    self.stopped(py, "entry", Vec::new())?;
+                } else {
+                    self.run(py, true)?;
+                }
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "parser" }] })),
+            "stackTrace" => self.stack_trace(py),
+            "scopes" => {
+                let step = commands::current_step(py, &self.launched()?.session)?;
+                let reference = self.remember(expr::step_value(&step));
+                Ok(json!({ "scopes": [{
+                    "name": "Step",
+                    "variablesReference": reference,
+                    "expensive": false,
+                }] }))
+            }
+            "variables" => self.variables(args),
+            "evaluate" => {
+                let expression = args["expression"].as_str().unwrap_or_default();
+                let step = commands::current_step(py, &self.launched()?.session)?;
+                let value = Expr::parse(expression)?.eval(&expr::step_value(&step));
+                let reference = self.reference_for(&value);
+                Ok(json!({ "result": display(&value), "variablesReference": reference }))
+            }
+            "continue" => {
+                self.run(py, true)?;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "reverseContinue" => {
+                self.run(py, false)?;
+                Ok(Value::Null)
+            }
+            "stepIn" => {
+                commands::step_forwards(py, &self.launched()?.session, 1)?;
+                self.stopped(py, "step", Vec::new())?;
+                Ok(Value::Null)
+            }
+            "next" => {
+                commands::step_until_next_decision(py, &self.launched()?.session)?;
+                self.stopped(py, "step", Vec::new())?;
+                Ok(Value::Null)
+            }
+            "stepBack" => {
+                commands::step_back_until_previous_decision(py, &self.launched()?.session)?;
+                self.stopped(py, "step", Vec::new())?;
+                Ok(Value::Null)
+            }
+            "stepOut" => {
+                // Run until the innermost rule is left
+                let parse_info = self.parse_info()?;
+                let depth = debugger::current_step(py, parse_info)?.rule_stack.len();
+                debugger::run_forward(py, parse_info, |step| Ok(step.rule_stack.len() < depth))?;
+                self.stopped(py, "step", Vec::new())?;
+                Ok(Value::Null)
+            }
+            // Every request runs to completion before the next one is read
+            "pause" => Ok(Value::Null),
+            "disconnect" | "terminate" => {
+                if command == "terminate" {
+                    self.pending.push(event("terminated", Value::Null));
+                }
+                Ok(Value::Null)
+            }
+            _ => Err(CommandError::invalid_argument(format!("Unsupported request {command:?}"))),
+        }
+    }
+
+    /// Generate the parser and parse the input named in the launch arguments.
+    fn launch(&mut self, py: Python<'_>, args: &Value) -> CommandResult<()> {
+        if let Some(token) = &self.token {
+            if args["token"].as_str() != Some(token.as_str()) {
+                return Err(CommandError::invalid_argument(
+                    "The launch configuration needs the \"token\" the DAP server printed",
+                ));
+            }
+        }
+        let path_arg = |name: &str| {
+            args[name]
+                .as_str()
+                .map(PathBuf::from)
+                .ok_or_else(|| CommandError::invalid_argument(format!("The launch configuration needs \"{name}\"")))
+        };
+        let grammar_path = path_arg("grammar")?;
+        let input_path = path_arg("input")?;
+        let input = std::fs::read_to_string(&input_path).map_err(|e| CommandError::io(&input_path, e))?;
+
+        let mut session = ParseSession::open(py, grammar_path.display().to_string())?;
+        session.generate_parser(py)?;
+        session.parse(py, input)?;
+
+        let user_grammar = grammar::user_grammar(py, &session.parse_info)?;
+        let rules = user_grammar
+            .grammar_files
+            .values()
+            .flat_map(|file| {
+                file.rules.values().map(|rule| {
+                    let source = RuleSource {
+                        path: file.path.clone(),
+                        start_line: rule.start_line.into(),
+                        end_line: rule.end_line.into(),
+                    };
+                    (rule.name.clone(), source)
+                })
+            })
+            .collect();
+
+        let token_positions = commands::token_list(py, &session)?
+            .into_iter()
+            .filter_map(|t| Some((usize::try_from(t.token_index).ok()?, (t.line, t.column))))
+            .collect();
+
+        self.launch = Some(Launch {
+            session,
+            input_path,
+            stop_on_entry: args["stopOnEntry"].as_bool().unwrap_or(true),
+            rules,
+            token_positions,
+        });
+        Ok(())
+    }
+
+    /// Line breakpoints stop on entering the rule defined at that line.
+    fn set_breakpoints(&mut self, args: &Value) -> CommandResult<Value> {
+        let path = args["source"]["path"].as_str().unwrap_or_default().to_string();
+        let launch = self.launch.as_mut().ok_or(CommandError::NotParsedYet)?;
+        for id in self.source_breakpoints.remove(&path).unwrap_or_default() {
+            commands::remove_breakpoint(&mut launch.session, id);
+        }
+
+        let mut ids = Vec::new();
+        let mut results = Vec::new();
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_i64().unwrap_or_default();
+            let rule = launch.rules.values().find(|rule| {
+                files::same_file(Path::new(&rule.path), Path::new(&path)) && (rule.start_line..=rule.end_line).contains(&line)
+            });
+            let Some(rule) = rule else {
+                results.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "No grammar rule is defined at this line",
+                }));
+                continue;
+            };
+
+            let spec = BreakpointSpec {
+                id: None,
+                kind: BreakpointKind::GrammarLine {
+                    file: path.clone(),
+                    line: usize::try_from(line).unwrap_or_default(),
+                },
+                enabled: true,
+                condition: condition(requested),
+            };
+            match commands::set_breakpoint(&mut launch.session, spec) {
+                Ok(breakpoint) => {
+                    ids.push(breakpoint.id);
+                    results.push(json!({
+                        "id": breakpoint.id,
+                        "verified": true,
+                        // Shown where it actually stops
+                        "line": rule.start_line,
+                    }));
+                }
+                Err(e) => results.push(json!({ "verified": false, "line": line, "message": e.to_string() })),
+            }
+        }
+        self.source_breakpoints.insert(path, ids);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    /// Function breakpoints take a rule name and stop on entering it.
+    fn set_function_breakpoints(&mut self, args: &Value) -> CommandResult<Value> {
+        let launch = self.launch.as_mut().ok_or(CommandError::NotParsedYet)?;
+        for id in self.function_breakpoints.drain(..) {
+            commands::remove_breakpoint(&mut launch.session, id);
+        }
+
+        let mut results = Vec::new();
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let rule = requested["name"].as_str().unwrap_or_default().to_string();
+            let known = launch.rules.contains_key(&rule);
+            let spec = BreakpointSpec {
+                id: None,
+                kind: BreakpointKind::RuleEnter { rule },
+                enabled: true,
+                condition: condition(requested),
+            };
+            match commands::set_breakpoint(&mut launch.session, spec) {
+                Ok(breakpoint) => {
+                    self.function_breakpoints.push(breakpoint.id);
+                    results.push(json!({
+                        "id": breakpoint.id,
+                        "verified": known,
+                        "message": if known { Value::Null } else { "No rule with this name".into() },
+                    }));
+                }
+                Err(e) => results.push(json!({ "verified": false, "message": e.to_string() })),
+            }
+        }
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    /// The syntax error filter becomes a breakpoint on every error step.
+    fn set_exception_breakpoints(&mut self, args: &Value) -> CommandResult<Value> {
+        let launch = self.launch.as_mut().ok_or(CommandError::NotParsedYet)?;
+        for id in self.exception_breakpoints.drain(..) {
+            commands::remove_breakpoint(&mut launch.session, id);
+        }
+
+        let filters = args["filters"].as_array().into_iter().flatten();
+        if filters.filter_map(Value::as_str).any(|f| f == SYNTAX_ERROR_FILTER) {
+            let breakpoint = commands::set_breakpoint(&mut launch.session, BreakpointSpec {
+                id: None,
+                kind: BreakpointKind::AnyStep,
+                enabled: true,
+                condition: Some("matching_error || is_error_node".to_string()),
+            })?;
+            self.exception_breakpoints.push(breakpoint.id);
+        }
+        Ok(Value::Null)
+    }
+
+    /// `continue` or `reverseContinue`: run to the next breakpoint, or to the
+    /// end of the trace.
+    fn run(&mut self, py: Python<'_>, forward: bool) -> CommandResult<()> {
+        let session = &self.launched()?.session;
+        let ContinueResult { hit: hits, .. } = if forward {
+            commands::continue_to_breakpoint(py, session)?
+        } else {
+            commands::reverse_continue(py, session)?
+        };
+
+        let reason = if hits.is_empty() {
+            "step"
+        } else if hits.iter().any(|id| self.exception_breakpoints.contains(id)) {
+            "exception"
+        } else if hits.iter().any(|id| self.function_breakpoints.contains(id)) {
+            "function breakpoint"
+        } else {
+            "breakpoint"
+        };
+        self.stopped(py, reason, hits)
+    }
+
+    /// Queue the `stopped` event for wherever the cursor is now.
+    fn stopped(&mut self, py: Python<'_>, reason: &str, hits: Vec<u32>) -> CommandResult<()> {
+        self.variables.clear();
+        let step = commands::current_step(py, &self.launched()?.session)?;
+        let description = match (step.matching_error, reason) {
+            (true, _) => format!("Syntax error at {}", step.current_token_repr),
+            (false, "step") => format!("{} at step {}", step.step_type, step.step_id),
+            (false, _) => format!("Breakpoint at step {}", step.step_id),
+        };
+        self.pending.push(event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+                "hitBreakpointIds": hits,
+            }),
+        ));
+        Ok(())
+    }
+
+    /// One frame per entry of the rule stack, innermost first, plus the
+    /// position in the input at the bottom.
+    fn stack_trace(&self, py: Python<'_>) -> CommandResult<Value> {
+        let launch = self.launched()?;
+        let step = commands::current_step(py, &launch.session)?;
+
+        let mut frames: Vec<Value> = step
+            .rule_stack
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(depth, rule)| {
+                // The innermost rule has its exact location on the step itself
+                let location = step
+                    .grammar_rule_location
+                    .as_ref()
+                    .filter(|location| depth == 0 && location.name == *rule)
+                    .map(|location| (location.file_path.clone(), location.start_line as i64));
+                let location = location.or_else(|| {
+                    let rule_source = launch.rules.get(rule)?;
+                    Some((rule_source.path.clone(), rule_source.start_line))
+                });
+                let mut frame = json!({ "id": depth, "name": rule, "line": 0, "column": 0 });
+                if let Some((path, line)) = location {
+                    frame["source"] = source(&path);
+                    frame["line"] = line.into();
+                    frame["column"] = 1.into();
+                }
+                frame
+            })
+            .collect();
+
+        // DAP columns start at 1, ANTLR's at 0; the start of the input if the token is unknown
+        let (line, column) = launch.token_positions.get(&step.token_index).copied().unwrap_or((1, 0));
+        frames.push(json!({
+            "id": frames.len(),
+            "name": format!("input at {}", step.current_token_repr),
+            "source": source(&launch.input_path.display().to_string()),
+            "line": line,
+            "column": column + 1,
+            "presentationHint": "subtle",
+        }));
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&mut self, args: &Value) -> CommandResult<Value> {
+        let reference = args["variablesReference"].as_u64().unwrap_or_default() as usize;
+        let value = reference
+            .checked_sub(1)
+            .and_then(|i| self.variables.get(i))
+            .cloned()
+            .ok_or_else(|| CommandError::invalid_argument("Unknown variables reference"))?;
+
+        let children: Vec<(String, Value)> = match value {
+            Value::Object(fields) => fields.into_iter().collect(),
+            Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (format!("[{i}]"), v))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let variables: Vec<Value> = children
+            .into_iter()
+            .map(|(name, value)| {
+                let reference = self.reference_for(&value);
+                json!({ "name": name, "value": display(&value), "variablesReference": reference })
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// A `variablesReference` for lists and objects, 0 for everything else
+    fn reference_for(&mut self, value: &Value) -> usize {
+        match value {
+            Value::Array(items) if !items.is_empty() => self.remember(value.clone()),
+            Value::Object(fields) if !fields.is_empty() => self.remember(value.clone()),
+            _ => 0,
+        }
+    }
+
+    fn remember(&mut self, value: Value) -> usize {
+        self.variables.push(value);
+        self.variables.len()
+    }
+
+    fn launched(&self) -> CommandResult<&Launch> {
+        self.launch.as_ref().ok_or(CommandError::NotParsedYet)
+    }
+
+    fn parse_info(&self) -> CommandResult<&Py<PyAny>> {
+        self.launched()?.session.parsed()
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        framing::write_message(&mut self.writer, &message)
+    }
+}
+
+fn event(name: &str, body: Value) -> Value {
+    let mut event = json!({ "type": "event", "event": name });
+    if !body.is_null() {
+        event["body"] = body;
+    }
+    event
+}
+
+fn source(path: &str) -> Value {
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    json!({ "name": name, "path": path })
+}
+
+/// The condition of a requested breakpoint, if it has a non-empty one
+fn condition(requested: &Value) -> Option<String> {
+    requested["condition"]
+        .as_str()
+        .filter(|c| !c.trim().is_empty())
+        .map(str::to_string)
+}
+
+/// Strings as they are, everything else as JSON
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn transport_error(err: io::Error) -> CommandError {
+    CommandError::io(Path::new("<dap>"), err)
+}
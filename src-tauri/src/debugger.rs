@@ -0,0 +1,437 @@
+// ==============================
+// src-tauri/src/debugger.rs
+// ==============================
+
+//! Thin wrappers around the `ParseInformation` methods that read and move the
+//! step cursor, and the Rust mirrors of what they return.
+
+use pyo3::prelude::*;
+use pythonize::depythonize;
+use serde::Serialize;
+
+use crate::error::{CommandError, CommandResult};
+
+/// Steps per `trace/chunk` event unless the caller asks for another size
+pub const DEFAULT_TRACE_CHUNK: usize = 500;
+
+/// Payload of the `trace/chunk` event sent by `stream_full_trace`
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceChunk {
+    pub id: usize,
+    pub op_id: String,
+    /// Position of the first step of this chunk within the trace
+    pub offset: usize,
+    pub steps: Vec<ParseStepInfo>,
+    /// Set on the last chunk, which may be empty
+    pub done: bool,
+}
+
+/// Mirrors the `possible_transitions` entries
+#[derive(Debug, Clone, FromPyObject, Serialize)]
+#[pyo3(from_item_all)]
+pub struct Transition {
+    pub target_state: usize,
+    pub matches: Vec<String>,
+}
+
+/// Mirrors the `grammar_rule_location` sub-dict
+#[derive(Debug, Clone, FromPyObject, Serialize)]
+#[pyo3(from_item_all)]
+pub struct GrammarRuleLocation {
+    pub name: String,
+    pub content: String,
+    pub file_path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_pos: usize,
+    pub end_pos: usize,
+}
+
+/// Mirrors `ParseStepInfo`, return type of `get_current_parse_step_info`
+#[derive(Debug, Clone, FromPyObject, Serialize)]
+#[pyo3(from_item_all)]
+#[serde(rename_all = "snake_case")]
+pub struct ParseStepInfo {
+    pub step_id: String,
+    pub step_type: String,
+    pub rule_name: Option<String>,
+    pub rule_stack: Vec<String>,
+    pub state: String,
+    pub current_token_repr: String,
+    pub token_index: usize,
+    pub chosen_transition_index: Option<i32>,
+    pub input_text_context: String,
+    pub next_token_stream_index: usize,
+    pub lookahead_repr: Vec<String>,
+    pub matching_error: bool,
+    pub is_error_node: bool,
+    pub possible_transitions: Option<Vec<Transition>>,
+    pub grammar_rule_location: Option<GrammarRuleLocation>,
+    pub input_context_snippet: Option<String>,
+}
+
+/// Mirrors the dicts returned by `get_token_list`
+#[derive(Debug, Clone, FromPyObject, Serialize)]
+#[pyo3(from_item_all)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenInfo {
+    pub text: String,
+    pub type_name: String,
+    pub type_id: i32,
+    pub line: i32,
+    pub column: i32,
+    pub start_index: i32,
+    pub stop_index: i32,
+    pub token_index: i32,
+    /// Older paredros-debugger versions leave it out, which reads as the default channel
+    #[pyo3(default)]
+    pub channel: i32,
+}
+
+impl ParseStepInfo {
+    /// The numeric step id; `go_to_step` expects this.
+    pub fn index(&self) -> CommandResult<usize> {
+        self.step_id.parse().map_err(|_| CommandError::MalformedStep {
+            message: format!("step id {:?} is not a number", self.step_id),
+        })
+    }
+}
+
+pub fn current_step(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<ParseStepInfo> {
+    let py_step = parse_info
+        .getattr(py, "get_current_parse_step_info")?
+        .call0(py)?;
+    Ok(py_step.extract(py)?)
+}
+
+pub fn go_to_step(py: Python<'_>, parse_info: &Py<PyAny>, step_id: usize) -> CommandResult<()> {
+    parse_info.getattr(py, "go_to_step")?.call1(py, (step_id,))?;
+    Ok(())
+}
+
+/// Number of steps recorded by the `ParseTreeExplorer`, if the input was parsed.
+pub fn step_count(py: Python<'_>, parse_info: &Py<PyAny>) -> Option<usize> {
+    let explorer = parse_info.getattr(py, "explorer").ok()?;
+    if explorer.is_none(py) {
+        return None;
+    }
+    explorer.getattr(py, "steps").ok()?.bind(py).len().ok()
+}
+
+/// Id of the last step of the trace. Taken from `step_count` if the explorer
+/// has one, otherwise found by stepping forward to the end.
+pub fn last_step_index(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<usize> {
+    if let Some(count) = step_count(py, parse_info).filter(|&count| count > 0) {
+        return Ok(count - 1);
+    }
+    let guard = CursorGuard::new(py, parse_info)?;
+    let mut last = None;
+    let walked = current_step(py, parse_info).and_then(|current| {
+        walk_from(py, parse_info, current, usize::MAX, |step| {
+            last = Some(step.index()?);
+            Ok(())
+        })
+    });
+    guard.restore()?;
+    walked?;
+    last.ok_or_else(|| CommandError::MalformedStep {
+        message: "the trace has no steps".to_string(),
+    })
+}
+
+/// Move the cursor to the end of the trace, where the parse tree is complete.
+pub fn go_to_last_step(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<()> {
+    let last = last_step_index(py, parse_info)?;
+    go_to_step(py, parse_info, last)
+}
+
+/// The lexemes of the parsed input and where they are located in it
+pub fn token_list(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<Vec<TokenInfo>> {
+    let py_tokens = parse_info.getattr(py, "get_token_list")?.call0(py)?;
+    Ok(py_tokens.extract(py)?)
+}
+
+/// The parse tree as far as it is built at the current step
+pub fn parse_tree(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<serde_json::Value> {
+    let dict = parse_info.getattr(py, "get_current_tree_dict")?.call0(py)?;
+    Ok(depythonize(dict.bind(py))?)
+}
+
+/// The complete parse tree, i.e. the one at the last step, without moving the cursor
+pub fn final_parse_tree(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<serde_json::Value> {
+    let guard = CursorGuard::new(py, parse_info)?;
+    let tree = go_to_last_step(py, parse_info).and_then(|_| parse_tree(py, parse_info));
+    guard.restore()?;
+    tree
+}
+
+/// Remembers the current step and jumps back to it when dropped, so reading
+/// other steps never leaves the cursor moved, not even on error.
+pub struct CursorGuard<'a, 'py> {
+    py: Python<'py>,
+    parse_info: &'a Py<PyAny>,
+    origin: usize,
+    restored: bool,
+}
+
+impl<'a, 'py> CursorGuard<'a, 'py> {
+    pub fn new(py: Python<'py>, parse_info: &'a Py<PyAny>) -> CommandResult<Self> {
+        let origin = current_step(py, parse_info)?.index()?;
+        Ok(Self {
+            py,
+            parse_info,
+            origin,
+            restored: false,
+        })
+    }
+
+    /// Restore explicitly to get to see a failing `go_to_step`.
+    pub fn restore(mut self) -> CommandResult<()> {
+        self.restored = true;
+        go_to_step(self.py, self.parse_info, self.origin)
+    }
+
+    /// Leave the cursor where it is now.
+    pub fn keep(mut self) {
+        self.restored = true;
+    }
+}
+
+impl Drop for CursorGuard<'_, '_> {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = go_to_step(self.py, self.parse_info, self.origin);
+        }
+    }
+}
+
+/// Read the step with the given id without moving the cursor.
+pub fn peek_step(py: Python<'_>, parse_info: &Py<PyAny>, step_id: usize) -> CommandResult<ParseStepInfo> {
+    let guard = CursorGuard::new(py, parse_info)?;
+    let step = step_at(py, parse_info, step_id);
+    guard.restore()?;
+    step
+}
+
+/// Read the steps `from..to` without moving the cursor. The range is cut
+/// short at the last step of the trace, so it is empty if `from` is past it.
+pub fn peek_steps(
+    py: Python<'_>,
+    parse_info: &Py<PyAny>,
+    from: usize,
+    to: usize,
+) -> CommandResult<Vec<ParseStepInfo>> {
+    let guard = CursorGuard::new(py, parse_info)?;
+    let steps = walk_steps(py, parse_info, from, to);
+    guard.restore()?;
+    steps
+}
+
+/// Step forward until `stop` holds for a step or the trace ends, and leave
+/// the cursor there. Returns that step and whether `stop` held for it.
+/// The current step itself is not checked.
+pub fn run_forward(
+    py: Python<'_>,
+    parse_info: &Py<PyAny>,
+    mut stop: impl FnMut(&ParseStepInfo) -> CommandResult<bool>,
+) -> CommandResult<(ParseStepInfo, bool)> {
+    let guard = CursorGuard::new(py, parse_info)?;
+    let step_forward = parse_info.getattr(py, "step_forward")?;
+    let mut last = current_step(py, parse_info)?;
+    loop {
+        step_forward.call1(py, (1,))?;
+        let step = current_step(py, parse_info)?;
+        if step.index()? <= last.index()? {
+            guard.keep();
+            return Ok((last, false));
+        }
+        if stop(&step)? {
+            guard.keep();
+            return Ok((step, true));
+        }
+        last = step;
+    }
+}
+
+/// Like `run_forward`, but towards the first step, which is where the
+/// cursor ends up if `stop` never holds.
+pub fn run_backward(
+    py: Python<'_>,
+    parse_info: &Py<PyAny>,
+    mut stop: impl FnMut(&ParseStepInfo) -> CommandResult<bool>,
+) -> CommandResult<(ParseStepInfo, bool)> {
+    let guard = CursorGuard::new(py, parse_info)?;
+    let origin = guard.origin;
+
+    // Steps can only be read forwards; remember the last match before the origin
+    let mut found = None;
+    go_to_step(py, parse_info, 0)?;
+    let first = current_step(py, parse_info)?;
+    let first_index = first.index()?;
+    if first_index < origin {
+        walk_from(py, parse_info, first, origin, |step| {
+            if stop(&step)? {
+                found = Some(step);
+            }
+            Ok(())
+        })?;
+    }
+
+    let hit = found.is_some();
+    let target = match &found {
+        Some(step) => step.index()?,
+        None => first_index,
+    };
+    go_to_step(py, parse_info, target)?;
+    let step = current_step(py, parse_info)?;
+    guard.keep();
+    Ok((step, hit))
+}
+
+/// Jump to `step_id` and read it, failing if the trace is shorter.
+fn step_at(py: Python<'_>, parse_info: &Py<PyAny>, step_id: usize) -> CommandResult<ParseStepInfo> {
+    go_to_step(py, parse_info, step_id)?;
+    let step = current_step(py, parse_info)?;
+    if step.index()? != step_id {
+        return Err(CommandError::StepOutOfRange {
+            step_id,
+            last_step: step.index()?,
+        });
+    }
+    Ok(step)
+}
+
+/// Collect the steps `from..to` by stepping forward one at a time.
+/// Leaves the cursor wherever it ends up.
+fn walk_steps(
+    py: Python<'_>,
+    parse_info: &Py<PyAny>,
+    from: usize,
+    to: usize,
+) -> CommandResult<Vec<ParseStepInfo>> {
+    // Not sized from `to`, which callers may set far past the end
+    let mut steps = Vec::new();
+    if from >= to {
+        return Ok(steps);
+    }
+
+    let first = match step_at(py, parse_info, from) {
+        Ok(step) => step,
+        Err(CommandError::StepOutOfRange { .. }) => return Ok(steps),
+        Err(e) => return Err(e),
+    };
+    walk_from(py, parse_info, first, to, |step| {
+        steps.push(step);
+        Ok(())
+    })?;
+    Ok(steps)
+}
+
+/// Hand every step of the trace to `visit`, in order, without moving the
+/// cursor. Each step is read exactly once.
+pub fn visit_trace(
+    py: Python<'_>,
+    parse_info: &Py<PyAny>,
+    visit: impl FnMut(ParseStepInfo) -> CommandResult<()>,
+) -> CommandResult<()> {
+    let guard = CursorGuard::new(py, parse_info)?;
+    // The first step is whatever `go_to_step(0)` lands on
+    let walked = go_to_step(py, parse_info, 0)
+        .and_then(|_| current_step(py, parse_info))
+        .and_then(|first| walk_from(py, parse_info, first, usize::MAX, visit));
+    guard.restore()?;
+    walked
+}
+
+/// Step forward from `first` until step `to` (exclusive) or the end of the
+/// trace, handing each step to `visit`.
+fn walk_from(
+    py: Python<'_>,
+    parse_info: &Py<PyAny>,
+    first: ParseStepInfo,
+    to: usize,
+    mut visit: impl FnMut(ParseStepInfo) -> CommandResult<()>,
+) -> CommandResult<()> {
+    let step_forward = parse_info.getattr(py, "step_forward")?;
+    let mut step = first;
+    loop {
+        let index = step.index()?;
+        visit(step)?;
+        if index.saturating_add(1) >= to {
+            break;
+        }
+        step_forward.call1(py, (1,))?;
+        step = current_step(py, parse_info)?;
+        if step.index()? <= index {
+            // The cursor didn't move: end of the trace
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyModule;
+
+    /// Just enough of `ParseInformation` to move the cursor over `steps` steps
+    const FAKE_PARSE_INFO: &std::ffi::CStr = cr#"
+class ParseInformation:
+    def __init__(self, steps):
+        self.steps, self.cursor = steps, 0
+
+    def go_to_step(self, step_id):
+        self.cursor = min(step_id, self.steps - 1)
+
+    def step_forward(self, n):
+        self.cursor = min(self.cursor + n, self.steps - 1)
+
+    def get_current_parse_step_info(self):
+        return {
+            "step_id": str(self.cursor), "step_type": "Token consume", "rule_name": None,
+            "rule_stack": [], "state": "1", "current_token_repr": "", "token_index": 0,
+            "chosen_transition_index": None, "input_text_context": "", "next_token_stream_index": 0,
+            "lookahead_repr": [], "matching_error": False, "is_error_node": False,
+            "possible_transitions": None, "grammar_rule_location": None, "input_context_snippet": None,
+        }
+"#;
+
+    fn parse_info(py: Python<'_>, steps: usize) -> Py<PyAny> {
+        let module = PyModule::from_code(py, FAKE_PARSE_INFO, c"fake_parse_info.py", c"fake_parse_info").unwrap();
+        module.getattr("ParseInformation").unwrap().call1((steps,)).unwrap().unbind()
+    }
+
+    fn ids(steps: &[ParseStepInfo]) -> Vec<usize> {
+        steps.iter().map(|s| s.index().unwrap()).collect()
+    }
+
+    #[test]
+    fn peek_steps_reads_the_range_and_keeps_the_cursor() {
+        Python::with_gil(|py| {
+            let info = parse_info(py, 10);
+            go_to_step(py, &info, 7).unwrap();
+            assert_eq!(ids(&peek_steps(py, &info, 2, 5).unwrap()), [2, 3, 4]);
+            assert_eq!(current_step(py, &info).unwrap().index().unwrap(), 7);
+        });
+    }
+
+    #[test]
+    fn peek_steps_cuts_the_range_at_the_last_step() {
+        Python::with_gil(|py| {
+            let info = parse_info(py, 10);
+            assert_eq!(ids(&peek_steps(py, &info, 8, usize::MAX).unwrap()), [8, 9]);
+            assert_eq!(ids(&peek_steps(py, &info, 20, usize::MAX).unwrap()), Vec::<usize>::new());
+            assert_eq!(ids(&peek_steps(py, &info, 5, 5).unwrap()), Vec::<usize>::new());
+        });
+    }
+
+    #[test]
+    fn last_step_index_walks_to_the_end_without_an_explorer() {
+        Python::with_gil(|py| {
+            let info = parse_info(py, 4);
+            assert_eq!(last_step_index(py, &info).unwrap(), 3);
+            assert_eq!(current_step(py, &info).unwrap().index().unwrap(), 0);
+        });
+    }
+}
@@ -0,0 +1,303 @@
+// ==============================
+// src-tauri/src/diagnostics.rs
+// ==============================
+
+//! `paredros-app doctor`: checks everything the Python setup depends on and
+//! sums it up as a report to paste into a bug ticket. Nothing is installed or
+//! changed on the way.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::python_env::{self, EnvInspection, SetupHost};
+
+/// Environment variables that decide which Python and libraries get loaded
+const ENV_VARS: [&str; 6] = [
+    "PYTHONPATH",
+    "PYTHONHOME",
+    "VIRTUAL_ENV",
+    "LD_LIBRARY_PATH",
+    "DYLD_LIBRARY_PATH",
+    "PAREDROS_WHEELHOUSE",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CheckStatus {
+    Ok,
+    /// Works, but not as intended, or couldn't be checked
+    Warning,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    pub app_version: String,
+    pub os: String,
+    pub generated_at: String,
+    pub checks: Vec<Check>,
+    /// Variable and value, `None` if unset
+    pub env: Vec<(String, Option<String>)>,
+    /// All of the above as plain text
+    pub report: String,
+}
+
+impl DiagnosticsReport {
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Failed)
+    }
+}
+
+fn check(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Check {
+    Check {
+        name,
+        status,
+        detail: detail.into(),
+    }
+}
+
+pub fn run(host: &impl SetupHost) -> DiagnosticsReport {
+    let mut checks = vec![check_build()];
+    match python_env::inspect_env(host) {
+        Ok(env) => check_env(&env, &mut checks),
+        Err(e) => checks.push(check("Settings", CheckStatus::Failed, format!("{e:#}"))),
+    }
+    checks.push(check_java());
+    checks.push(check_git());
+
+    let env: Vec<(String, Option<String>)> = ENV_VARS
+        .iter()
+        .map(|var| (var.to_string(), std::env::var(var).ok()))
+        .collect();
+    let mut report = DiagnosticsReport {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        generated_at: chrono::Local::now().to_rfc3339(),
+        checks,
+        env,
+        report: String::new(),
+    };
+    report.report = render(&report);
+    report
+}
+
+/// The Python the app is linked against, `.python-version` and the supported range agree
+fn check_build() -> Check {
+    match python_env::check_linked_python() {
+        Ok(()) => check(
+            "Build",
+            CheckStatus::Ok,
+            format!("linked against Python {}", python_env::LINKED_PYTHON),
+        ),
+        Err(e) => check("Build", CheckStatus::Failed, e.to_string()),
+    }
+}
+
+fn check_env(env: &EnvInspection, checks: &mut Vec<Check>) {
+    checks.push(match &env.base_python {
+        Ok((path, version)) if python_env::is_compatible(version) => check(
+            "Python interpreter",
+            CheckStatus::Ok,
+            format!("{} ({version})", path.display()),
+        ),
+        Ok((path, version)) => check(
+            "Python interpreter",
+            CheckStatus::Failed,
+            format!(
+                "{} ({}), but the app is built against Python {}",
+                path.display(),
+                or_unknown(version),
+                python_env::LINKED_PYTHON
+            ),
+        ),
+        Err(e) => check("Python interpreter", CheckStatus::Failed, format!("{e:#}")),
+    });
+
+    let env_dir = match &env.env_dir {
+        Ok(dir) => dir.display().to_string(),
+        Err(e) => format!("{e:#}"),
+    };
+    checks.push(match (&env.env_python, &env.state_differences) {
+        (None, _) => check(
+            "Environment",
+            CheckStatus::Failed,
+            format!("{} by {}: no interpreter in it", env_dir, env.provider),
+        ),
+        (Some(_), None) => check(
+            "Environment",
+            CheckStatus::Warning,
+            format!("{} by {}: never set up by the app", env_dir, env.provider),
+        ),
+        (Some(_), Some(differences)) if differences.is_empty() => {
+            check("Environment", CheckStatus::Ok, format!("{} by {}", env_dir, env.provider))
+        }
+        (Some(_), Some(differences)) => check(
+            "Environment",
+            CheckStatus::Warning,
+            format!("{} by {}: {}; it is rebuilt on the next start", env_dir, env.provider, differences.join(", ")),
+        ),
+    });
+
+    checks.push(match &env.env_python {
+        Some(python) => check_debugger(python),
+        None => check("paredros_debugger", CheckStatus::Failed, "there is no environment to import it from"),
+    });
+
+    checks.push(match &env.antlr4 {
+        Some(antlr4) => check("antlr4 launcher", CheckStatus::Ok, antlr4.display().to_string()),
+        None => check("antlr4 launcher", CheckStatus::Failed, "not in the environment"),
+    });
+
+    checks.push(match &env.env_python {
+        Some(python) => check_libpython(python),
+        None => check("libpython", CheckStatus::Failed, "there is no environment to check it against"),
+    });
+}
+
+fn or_unknown(version: &str) -> &str {
+    if version.is_empty() {
+        "unknown version"
+    } else {
+        version
+    }
+}
+
+/// Runs `program args`; stdout and stderr together, or why it didn't run
+fn output_of(program: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program).args(args).output().map_err(|e| e.to_string())?;
+    let mut text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(stderr.trim());
+    }
+    if output.status.success() {
+        Ok(text)
+    } else {
+        Err(text)
+    }
+}
+
+fn check_debugger(python: &Path) -> Check {
+    let script = "import importlib.metadata as m, paredros_debugger; print(m.version('paredros-debugger'))";
+    match output_of(python, &["-c", script]) {
+        Ok(version) => check("paredros_debugger", CheckStatus::Ok, version),
+        Err(output) => check(
+            "paredros_debugger",
+            CheckStatus::Failed,
+            // The exception, without the traceback
+            output.lines().last().unwrap_or("could not be imported").to_string(),
+        ),
+    }
+}
+
+/// The shared library pyo3 embeds, as the environment's interpreter names it.
+/// Its version must be the one pyo3 was built against.
+fn check_libpython(python: &Path) -> Check {
+    let env_version = python_env::get_python_version(python).unwrap_or_default();
+    if !python_env::is_compatible(&env_version) {
+        return check(
+            "libpython",
+            CheckStatus::Failed,
+            format!(
+                "the app is built against Python {}, but the environment is {}",
+                python_env::LINKED_PYTHON,
+                or_unknown(&env_version)
+            ),
+        );
+    }
+
+    // Empty for interpreters built without a shared libpython
+    let script = "import os, sys, sysconfig\n\
+        v, var = sys.version_info, sysconfig.get_config_var\n\
+        if os.name == 'nt':\n    print(os.path.join(sys.base_prefix, f'python{v.major}{v.minor}.dll'))\n\
+        elif var('PYTHONFRAMEWORK'):\n    print(os.path.join(var('PYTHONFRAMEWORKPREFIX'), var('LDLIBRARY')))\n\
+        elif var('Py_ENABLE_SHARED'):\n    print(os.path.join(var('LIBDIR'), var('INSTSONAME') or var('LDLIBRARY')))";
+    let library = match output_of(python, &["-c", script]) {
+        Ok(library) if library.is_empty() => {
+            return check("libpython", CheckStatus::Warning, "the environment's Python has no shared libpython")
+        }
+        Ok(library) => library,
+        Err(output) => return check("libpython", CheckStatus::Warning, format!("could not be located: {output}")),
+    };
+    let exists = Path::new(&library).is_file();
+
+    // Starting it here would set it up before the environment is configured.
+    // SAFETY: Py_IsInitialized only reads a flag and may be called at any
+    // time, before initialization included
+    let running = unsafe { pyo3::ffi::Py_IsInitialized() } != 0;
+    if running {
+        let version = Python::with_gil(|py| {
+            let info = py.version_info();
+            format!("{}.{}", info.major, info.minor)
+        });
+        check("libpython", CheckStatus::Ok, format!("Python {version} is running, {library}"))
+    } else if exists {
+        check("libpython", CheckStatus::Ok, library)
+    } else {
+        check("libpython", CheckStatus::Warning, format!("{library} does not exist"))
+    }
+}
+
+fn check_java() -> Check {
+    let Ok(java) = which::which("java") else {
+        return check("Java", CheckStatus::Failed, "java is not on PATH; ANTLR needs it to generate parsers");
+    };
+    // `java -version` prints to stderr
+    match output_of(&java, &["-version"]) {
+        Ok(output) => check(
+            "Java",
+            CheckStatus::Ok,
+            format!("{} ({})", java.display(), output.lines().next().unwrap_or_default()),
+        ),
+        Err(output) => check("Java", CheckStatus::Failed, format!("{} did not run: {output}", java.display())),
+    }
+}
+
+fn check_git() -> Check {
+    match which::which("git") {
+        Ok(git) => {
+            let version = output_of(&git, &["--version"]).unwrap_or_default();
+            check("git", CheckStatus::Ok, format!("{} ({version})", git.display()))
+        }
+        Err(_) => check(
+            "git",
+            CheckStatus::Warning,
+            "not on PATH; only needed to install paredros-debugger without a wheelhouse",
+        ),
+    }
+}
+
+fn render(report: &DiagnosticsReport) -> String {
+    let mut text = format!(
+        "Paredros {} on {}, {}\n\n",
+        report.app_version, report.os, report.generated_at
+    );
+    for c in &report.checks {
+        let status = match c.status {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warn",
+            CheckStatus::Failed => "FAIL",
+        };
+        // Multi-line details are indented under their check
+        let detail = c.detail.replace('\n', "\n       ");
+        let _ = writeln!(text, "[{status:>4}] {}: {detail}", c.name);
+    }
+    text.push('\n');
+    for (var, value) in &report.env {
+        let _ = writeln!(text, "{var}={}", value.as_deref().unwrap_or("(unset)"));
+    }
+    text
+}
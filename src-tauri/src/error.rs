@@ -0,0 +1,225 @@
+// ==============================
+// src-tauri/src/error.rs
+// ==============================
+
+use std::sync::PoisonError;
+
+use pyo3::exceptions::PyKeyboardInterrupt;
+use pyo3::prelude::*;
+use serde::{ser::Error as _, Serialize, Serializer};
+
+use crate::python_env::{self, IncompatiblePython, PythonCandidate};
+use crate::traceback::{self, ChainedException, TracebackFrame};
+
+/// Error type returned by every Tauri command.
+///
+/// Serialized as `{ "kind": "<Variant>", "display": "<Display>", ...fields }`
+/// so the frontend can switch on `kind` and still show `display` as is.
+#[derive(Debug, Clone, thiserror::Error, Serialize)]
+#[serde(tag = "kind", remote = "Self")]
+pub enum CommandError {
+    #[error("Invalid parse info id {id}")]
+    UnknownSession { id: usize },
+
+    #[error("{type_name}: {message}")]
+    PythonException {
+        type_name: String,
+        message: String,
+        /// The traceback as Python prints it, chained exceptions included
+        traceback: Option<String>,
+        frames: Vec<TracebackFrame>,
+        chain: Vec<ChainedException>,
+    },
+
+    #[error("ANTLR failed to generate the parser:\n{tool_output}")]
+    GrammarGenerationFailed { tool_output: String },
+
+    #[error("No input has been parsed yet")]
+    NotParsedYet,
+
+    #[error("Step {step_id} does not exist, the last step is {last_step}")]
+    StepOutOfRange { step_id: usize, last_step: usize },
+
+    #[error("Unexpected parse step: {message}")]
+    MalformedStep { message: String },
+
+    #[error("Internal lock poisoned: {what}")]
+    LockPoisoned { what: String },
+
+    #[error("{message}")]
+    InvalidArgument { message: String },
+
+    #[error("Operation {op_id} was cancelled")]
+    Cancelled { op_id: String },
+
+    #[error("The Python worker thread is not available")]
+    WorkerUnavailable,
+
+    #[error("Python setup failed: {message}")]
+    PythonSetup { message: String },
+
+    #[error("{}", python_env::incompatible_message(wanted, linked, min, max, found))]
+    IncompatiblePython {
+        wanted: String,
+        linked: String,
+        min: String,
+        max: String,
+        found: Vec<PythonCandidate>,
+    },
+
+    #[error("Could not access {path}: {message}")]
+    Io { path: String, message: String },
+
+    #[error("The rule {rule} in {path} changed on disk since the grammar was loaded")]
+    StaleGrammarFile { path: String, rule: String },
+
+    #[error("{path} is not a valid project file: {message}")]
+    InvalidProjectFile { path: String, message: String },
+
+    #[error("Invalid expression at column {column}: {message}")]
+    InvalidExpression {
+        expression: String,
+        /// 1-based, counted in characters
+        column: usize,
+        message: String,
+    },
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
+
+impl CommandError {
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        CommandError::InvalidArgument {
+            message: message.into(),
+        }
+    }
+
+    /// A `KeyboardInterrupt`, i.e. the operation was cancelled while in Python.
+    pub fn is_interrupt(&self) -> bool {
+        matches!(self, CommandError::PythonException { type_name, .. } if type_name == "KeyboardInterrupt")
+    }
+
+    pub fn io(path: &std::path::Path, err: std::io::Error) -> Self {
+        CommandError::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    /// Like `From<PyErr>`, but recognises a failed ANTLR run
+    /// (`subprocess.CalledProcessError`) and keeps the tool output.
+    pub fn from_generation_error(py: Python<'_>, err: PyErr) -> Self {
+        let called_process_error = py
+            .import("subprocess")
+            .and_then(|m| m.getattr("CalledProcessError"));
+        if let Ok(cls) = called_process_error {
+            if err.get_type(py).is_subclass(&cls).unwrap_or(false) {
+                let value = err.value(py);
+                let tool_output = ["stderr", "output"]
+                    .iter()
+                    .filter_map(|attr| value.getattr(*attr).ok())
+                    .filter_map(|out| stringify_output(&out))
+                    .filter(|out| !out.trim().is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                return CommandError::GrammarGenerationFailed {
+                    tool_output: if tool_output.is_empty() {
+                        err.to_string()
+                    } else {
+                        tool_output
+                    },
+                };
+            }
+        }
+        CommandError::from_py(py, err)
+    }
+
+    /// Captures the full traceback and writes it to the error log, unless
+    /// it is the `KeyboardInterrupt` of a cancelled operation.
+    fn from_py(py: Python<'_>, err: PyErr) -> Self {
+        let report = traceback::capture(py, &err);
+        // What `is_interrupt` checks; cancelling is not an error worth logging
+        if !err.is_instance_of::<PyKeyboardInterrupt>(py) {
+            traceback::log_report(&report);
+        }
+        CommandError::PythonException {
+            type_name: report.type_name,
+            message: report.message,
+            traceback: Some(report.formatted),
+            frames: report.frames,
+            chain: report.chain,
+        }
+    }
+}
+
+/// `CalledProcessError.output` is `bytes` unless the process ran in text mode.
+fn stringify_output(out: &Bound<'_, PyAny>) -> Option<String> {
+    if out.is_none() {
+        return None;
+    }
+    if let Ok(bytes) = out.extract::<Vec<u8>>() {
+        return Some(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    out.extract::<String>().ok()
+}
+
+impl From<PyErr> for CommandError {
+    fn from(err: PyErr) -> Self {
+        Python::with_gil(|py| CommandError::from_py(py, err))
+    }
+}
+
+impl From<pythonize::PythonizeError> for CommandError {
+    fn from(err: pythonize::PythonizeError) -> Self {
+        CommandError::PythonException {
+            type_name: "PythonizeError".to_string(),
+            message: err.to_string(),
+            traceback: None,
+            frames: Vec::new(),
+            chain: Vec::new(),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for CommandError {
+    fn from(err: PoisonError<T>) -> Self {
+        CommandError::LockPoisoned {
+            what: err.to_string(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<IncompatiblePython>() {
+            Ok(IncompatiblePython {
+                wanted,
+                linked,
+                min,
+                max,
+                found,
+            }) => CommandError::IncompatiblePython {
+                wanted,
+                linked,
+                min,
+                max,
+                found,
+            },
+            Err(err) => CommandError::PythonSetup {
+                message: format!("{err:#}"),
+            },
+        }
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = CommandError::serialize(self, serde_json::value::Serializer)
+            .map_err(S::Error::custom)?;
+        if let Some(fields) = value.as_object_mut() {
+            // Not `message`, which several variants have as a field of their own
+            fields.insert("display".to_string(), self.to_string().into());
+        }
+        value.serialize(serializer)
+    }
+}
@@ -0,0 +1,576 @@
+// ==============================
+// src-tauri/src/expr.rs
+// ==============================
+
+//! A small expression language over the fields of a `ParseStepInfo`, used for
+//! breakpoint conditions and watch expressions.
+//!
+//! ```text
+//! rule_stack contains "vita" && matching_error
+//! lookahead_repr[0] == "KOMMA"
+//! grammar_rule_location.start_line >= 40 || len(rule_stack) > 5
+//! ```
+//!
+//! Operators, loosest first: `||`, `&&`, `!`, the comparisons `== != < <= > >=`
+//! and `contains` (element of a list or substring of a string), then indexing
+//! `[n]` and field access `.name`. Literals are `"strings"`, integers, `true`,
+//! `false` and `null`. `len(x)` counts list elements or characters.
+//!
+//! Evaluation never fails: an index past the end or a field of `null` gives
+//! `null`, and comparing values of different types is simply false. Unknown
+//! step fields are rejected when the expression is parsed, and so is nesting
+//! deeper than `MAX_DEPTH`.
+
+use std::cmp::Ordering;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::debugger::ParseStepInfo;
+use crate::error::{CommandError, CommandResult};
+
+/// Top-level names an expression may refer to
+const STEP_FIELDS: &[&str] = &[
+    "step_id",
+    "step_type",
+    "rule_name",
+    "rule_stack",
+    "state",
+    "current_token_repr",
+    "token_index",
+    "chosen_transition_index",
+    "input_text_context",
+    "next_token_stream_index",
+    "lookahead_repr",
+    "matching_error",
+    "is_error_node",
+    "possible_transitions",
+    "grammar_rule_location",
+    "input_context_snippet",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Literal(Value),
+    Field(String),
+    Member(Box<Node>, String),
+    Index(Box<Node>, Box<Node>),
+    Len(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+/// A parsed expression, ready to be evaluated against any number of steps
+#[derive(Debug, Clone)]
+pub struct Expr {
+    root: Node,
+}
+
+/// The value of a watch expression at one step
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchValue {
+    pub expression: String,
+    pub value: Option<Value>,
+    /// Set instead of `value` if the expression doesn't parse
+    pub error: Option<CommandError>,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> CommandResult<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            source,
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let root = parser.or()?;
+        if let Some((column, token)) = parser.tokens.get(parser.pos) {
+            return Err(parser.error_at(*column, format!("unexpected {}", token.describe())));
+        }
+        Ok(Self { root })
+    }
+
+    /// Evaluate against a step already converted with `step_value`.
+    pub fn eval(&self, step: &Value) -> Value {
+        eval(&self.root, step)
+    }
+
+    /// Whether the expression holds for the step; see `is_truthy`.
+    pub fn holds(&self, step: &Value) -> bool {
+        is_truthy(&self.eval(step))
+    }
+}
+
+/// The step as the JSON object expressions are evaluated against
+pub fn step_value(step: &ParseStepInfo) -> Value {
+    let mut value = serde_json::to_value(step).unwrap_or(Value::Null);
+    // Numeric, so `step_id > 100` compares numbers
+    if let (Some(fields), Ok(index)) = (value.as_object_mut(), step.index()) {
+        fields.insert("step_id".to_string(), index.into());
+    }
+    value
+}
+
+/// Evaluate each of `expressions` against `step`. An expression that doesn't
+/// parse gets an error rather than failing the whole list.
+pub fn watch(expressions: &[String], step: &ParseStepInfo) -> Vec<WatchValue> {
+    let step = step_value(step);
+    expressions
+        .iter()
+        .map(|expression| match Expr::parse(expression) {
+            Ok(expr) => WatchValue {
+                expression: expression.clone(),
+                value: Some(expr.eval(&step)),
+                error: None,
+            },
+            Err(error) => WatchValue {
+                expression: expression.clone(),
+                value: None,
+                error: Some(error),
+            },
+        })
+        .collect()
+}
+
+/// `false`, `null`, `0`, `""` and `[]` are false, everything else is true
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+fn eval(node: &Node, step: &Value) -> Value {
+    match node {
+        Node::Literal(value) => value.clone(),
+        Node::Field(name) => step.get(name).cloned().unwrap_or(Value::Null),
+        Node::Member(target, name) => eval(target, step).get(name).cloned().unwrap_or(Value::Null),
+        Node::Index(target, index) => {
+            let target = eval(target, step);
+            match eval(index, step) {
+                Value::Number(n) => n
+                    .as_u64()
+                    .and_then(|i| target.get(i as usize))
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                Value::String(key) => target.get(&key).cloned().unwrap_or(Value::Null),
+                _ => Value::Null,
+            }
+        }
+        Node::Len(target) => match eval(target, step) {
+            Value::Array(a) => a.len().into(),
+            Value::String(s) => s.chars().count().into(),
+            _ => Value::Null,
+        },
+        Node::Not(inner) => (!is_truthy(&eval(inner, step))).into(),
+        Node::Binary(BinaryOp::Or, left, right) => {
+            (is_truthy(&eval(left, step)) || is_truthy(&eval(right, step))).into()
+        }
+        Node::Binary(BinaryOp::And, left, right) => {
+            (is_truthy(&eval(left, step)) && is_truthy(&eval(right, step))).into()
+        }
+        Node::Binary(op, left, right) => {
+            let (left, right) = (eval(left, step), eval(right, step));
+            let result = match op {
+                BinaryOp::Eq => equals(&left, &right),
+                BinaryOp::Ne => !equals(&left, &right),
+                BinaryOp::Lt => compare(&left, &right) == Some(Ordering::Less),
+                BinaryOp::Le => matches!(compare(&left, &right), Some(Ordering::Less | Ordering::Equal)),
+                BinaryOp::Gt => compare(&left, &right) == Some(Ordering::Greater),
+                BinaryOp::Ge => matches!(compare(&left, &right), Some(Ordering::Greater | Ordering::Equal)),
+                BinaryOp::Contains => match (&left, &right) {
+                    (Value::Array(items), needle) => items.iter().any(|item| equals(item, needle)),
+                    (Value::String(s), Value::String(needle)) => s.contains(needle.as_str()),
+                    _ => false,
+                },
+                BinaryOp::Or | BinaryOp::And => unreachable!("handled above"),
+            };
+            result.into()
+        }
+    }
+}
+
+/// Like `==` on `Value`, but `1 == 1.0`
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Op(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("`{name}`"),
+            Token::Str(s) => format!("{s:?}"),
+            Token::Int(n) => n.to_string(),
+            Token::Op(op) => format!("`{op}`"),
+        }
+    }
+}
+
+/// How deeply expressions may nest, counting brackets, `!` and chained
+/// operators. Parsing and evaluating both recurse once per level.
+const MAX_DEPTH: usize = 64;
+
+/// Longest first, so `<=` isn't read as `<`
+const OPERATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")", "[", "]", ".", ",",
+];
+
+/// Tokens with their 1-based column
+fn tokenize(source: &str) -> CommandResult<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let error = |column: usize, message: String| CommandError::InvalidExpression {
+        expression: source.to_string(),
+        column,
+        message,
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(error(column, "unterminated string".to_string())),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        let escaped = match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&other) => other,
+                            None => return Err(error(column, "unterminated string".to_string())),
+                        };
+                        text.push(escaped);
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((column, Token::Str(text)));
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            let start = i;
+            i += 1;
+            while chars.get(i).is_some_and(char::is_ascii_digit) {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| error(column, format!("number {text} is too large")))?;
+            tokens.push((column, Token::Int(n)));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while chars.get(i).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                i += 1;
+            }
+            tokens.push((column, Token::Ident(chars[start..i].iter().collect())));
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| op.chars().enumerate().all(|(k, oc)| chars.get(i + k) == Some(&oc)))
+                .ok_or_else(|| error(column, format!("unexpected character {c:?}")))?;
+            i += op.chars().count();
+            tokens.push((column, Token::Op(op)));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Levels entered so far, see `MAX_DEPTH`
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> CommandResult<Node> {
+        let depth = self.depth;
+        let mut node = self.and()?;
+        while self.eat_op("||") {
+            self.descend()?;
+            node = Node::Binary(BinaryOp::Or, Box::new(node), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(node)
+    }
+
+    fn and(&mut self) -> CommandResult<Node> {
+        let depth = self.depth;
+        let mut node = self.not()?;
+        while self.eat_op("&&") {
+            self.descend()?;
+            node = Node::Binary(BinaryOp::And, Box::new(node), Box::new(self.not()?));
+        }
+        self.depth = depth;
+        Ok(node)
+    }
+
+    fn not(&mut self) -> CommandResult<Node> {
+        if self.eat_op("!") {
+            self.descend()?;
+            let inner = self.not()?;
+            self.depth -= 1;
+            return Ok(Node::Not(Box::new(inner)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> CommandResult<Node> {
+        let left = self.postfix()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => BinaryOp::Eq,
+            Some(Token::Op("!=")) => BinaryOp::Ne,
+            Some(Token::Op("<")) => BinaryOp::Lt,
+            Some(Token::Op("<=")) => BinaryOp::Le,
+            Some(Token::Op(">")) => BinaryOp::Gt,
+            Some(Token::Op(">=")) => BinaryOp::Ge,
+            Some(Token::Ident(word)) if word == "contains" => BinaryOp::Contains,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.postfix()?;
+        Ok(Node::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn postfix(&mut self) -> CommandResult<Node> {
+        let depth = self.depth;
+        let mut node = self.primary()?;
+        loop {
+            if self.eat_op("[") {
+                self.descend()?;
+                let index = self.or()?;
+                self.expect_op("]")?;
+                node = Node::Index(Box::new(node), Box::new(index));
+            } else if self.eat_op(".") {
+                self.descend()?;
+                let name = self.ident()?;
+                node = Node::Member(Box::new(node), name);
+            } else {
+                self.depth = depth;
+                return Ok(node);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> CommandResult<Node> {
+        let column = self.column();
+        let Some((_, token)) = self.tokens.get(self.pos).cloned() else {
+            return Err(self.error_at(column, "unexpected end of expression".to_string()));
+        };
+        self.pos += 1;
+        match token {
+            Token::Str(s) => Ok(Node::Literal(s.into())),
+            Token::Int(n) => Ok(Node::Literal(n.into())),
+            Token::Op("(") => {
+                self.descend()?;
+                let inner = self.or()?;
+                self.expect_op(")")?;
+                self.depth -= 1;
+                Ok(inner)
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Node::Literal(true.into())),
+                "false" => Ok(Node::Literal(false.into())),
+                "null" => Ok(Node::Literal(Value::Null)),
+                "len" if self.eat_op("(") => {
+                    self.descend()?;
+                    let inner = self.or()?;
+                    self.expect_op(")")?;
+                    self.depth -= 1;
+                    Ok(Node::Len(Box::new(inner)))
+                }
+                _ if STEP_FIELDS.contains(&name.as_str()) => Ok(Node::Field(name)),
+                _ => Err(self.error_at(column, format!("unknown field `{name}`"))),
+            },
+            other => Err(self.error_at(column, format!("unexpected {}", other.describe()))),
+        }
+    }
+
+    /// Enter one more level for the token just taken, or fail at it past
+    /// `MAX_DEPTH`
+    fn descend(&mut self) -> CommandResult<()> {
+        if self.depth == MAX_DEPTH {
+            let column = self.tokens[self.pos - 1].0;
+            return Err(self.error_at(column, format!("nested more than {MAX_DEPTH} levels deep")));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn ident(&mut self) -> CommandResult<String> {
+        match self.tokens.get(self.pos) {
+            Some((_, Token::Ident(name))) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error_at(self.column(), "expected a field name".to_string())),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Op(o)) if *o == op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_op(&mut self, op: &str) -> CommandResult<()> {
+        if self.eat_op(op) {
+            Ok(())
+        } else {
+            Err(self.error_at(self.column(), format!("expected `{op}`")))
+        }
+    }
+
+    /// Column of the next token, or just past the end
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(column, _)| *column)
+            .unwrap_or(self.source.chars().count() + 1)
+    }
+
+    fn error_at(&self, column: usize, message: String) -> CommandError {
+        CommandError::InvalidExpression {
+            expression: self.source.to_string(),
+            column,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step() -> Value {
+        json!({
+            "step_id": 7,
+            "rule_name": "vita",
+            "rule_stack": ["start", "vita"],
+            "lookahead_repr": ["KOMMA", "WORT"],
+            "matching_error": false,
+            "grammar_rule_location": { "start_line": 42 },
+        })
+    }
+
+    fn eval(source: &str) -> Value {
+        Expr::parse(source).unwrap().eval(&step())
+    }
+
+    fn error_column(source: &str) -> usize {
+        match Expr::parse(source) {
+            Err(CommandError::InvalidExpression { column, .. }) => column,
+            other => panic!("expected InvalidExpression for {source:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(eval("true || false && false"), json!(true));
+        assert_eq!(eval("(true || false) && false"), json!(false));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_but_looser_than_comparisons() {
+        assert_eq!(eval("!matching_error && true"), json!(true));
+        assert_eq!(eval("!step_id == 7"), json!(false));
+        assert_eq!(eval("!(step_id == 8)"), json!(true));
+    }
+
+    #[test]
+    fn postfix_binds_tighter_than_comparisons() {
+        assert_eq!(eval("lookahead_repr[0] == \"KOMMA\""), json!(true));
+        assert_eq!(eval("grammar_rule_location.start_line >= 40"), json!(true));
+        assert_eq!(eval("len(rule_stack) > 1 && rule_stack contains \"vita\""), json!(true));
+    }
+
+    #[test]
+    fn evaluates_missing_values_to_null() {
+        assert_eq!(eval("lookahead_repr[5]"), Value::Null);
+        assert_eq!(eval("rule_name.start_line"), Value::Null);
+        assert_eq!(eval("len(step_id)"), Value::Null);
+    }
+
+    #[test]
+    fn compares_across_types_as_false() {
+        assert_eq!(eval("step_id == \"7\""), json!(false));
+        assert_eq!(eval("step_id < \"8\""), json!(false));
+        assert_eq!(eval("step_id != \"7\""), json!(true));
+        assert_eq!(eval("rule_name contains \"it\""), json!(true));
+    }
+
+    #[test]
+    fn reports_the_column_of_the_offending_token() {
+        assert_eq!(error_column("step_id == 7 &&"), 16);
+        assert_eq!(error_column("rule_name == bogus"), 14);
+        assert_eq!(error_column("step_id @ 7"), 9);
+        assert_eq!(error_column("(step_id"), 9);
+        assert_eq!(error_column("'open"), 1);
+    }
+
+    #[test]
+    fn rejects_nesting_past_the_limit() {
+        let nested = |n: usize| format!("{}true{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(eval(&nested(MAX_DEPTH)), json!(true));
+        assert_eq!(error_column(&nested(MAX_DEPTH + 1)), MAX_DEPTH + 1);
+        assert!(Expr::parse(&"!".repeat(10_000)).is_err());
+        assert!(Expr::parse(&vec!["true"; 10_000].join(" || ")).is_err());
+        assert!(Expr::parse(&format!("rule_stack{}", "[0]".repeat(10_000))).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+// ==============================
+// src-tauri/src/files.rs
+// ==============================
+
+//! Helpers for writing and comparing files the user cares about.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult};
+
+/// Write to a temporary file next to `path` and rename it into place, so a
+/// failed write never leaves a truncated file behind.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> CommandResult<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| CommandError::invalid_argument(format!("{} is not a file path", path.display())))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+    fs::write(&tmp, contents).map_err(|e| CommandError::io(&tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        CommandError::io(path, e)
+    })
+}
+
+/// Copy `path` to `<path>.bak`, replacing an older backup.
+/// Returns `None` if there was nothing to back up.
+pub fn backup(path: &Path) -> CommandResult<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let backup = PathBuf::from(format!("{}.bak", path.display()));
+    fs::copy(path, &backup).map_err(|e| CommandError::io(&backup, e))?;
+    Ok(Some(backup))
+}
+
+/// Whether two paths name the same file. Paths that don't exist (or not yet)
+/// count as the same if one is a trailing part of the other, since editors
+/// send absolute paths where the grammar may know relative ones.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.ends_with(b) || b.ends_with(a),
+    }
+}
@@ -0,0 +1,72 @@
+// ==============================
+// src-tauri/src/framing.rs
+// ==============================
+
+//! The base protocol shared by the DAP and LSP servers: JSON messages, each
+//! preceded by a `Content-Length` header and a blank line.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// Larger messages are refused rather than allocated, whatever the header says
+const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Read one message; `None` once the other side closed the connection.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    if length > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content-Length {length} exceeds the limit of {MAX_MESSAGE_BYTES} bytes"),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_written_message() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &serde_json::json!({ "seq": 1 })).unwrap();
+        let message = read_message(&mut buffer.as_slice()).unwrap();
+        assert_eq!(message, Some(serde_json::json!({ "seq": 1 })));
+        assert_eq!(read_message(&mut &b""[..]).unwrap(), None);
+    }
+
+    #[test]
+    fn refuses_a_content_length_over_the_limit() {
+        let header = format!("Content-Length: {}\r\n\r\n{{}}", MAX_MESSAGE_BYTES + 1);
+        let error = read_message(&mut header.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,210 @@
+// ==============================
+// src-tauri/src/grammar.rs
+// ==============================
+
+//! Rust mirrors of the grammar model of `paredros_debugger` (`ParseInformation.grammar`).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::error::{CommandError, CommandResult};
+use crate::files;
+use crate::session::ParseSession;
+
+/// This class mirrors the Python class
+#[derive(Debug, FromPyObject, Serialize)]
+pub struct GrammarRule {
+    #[pyo3(attribute)]
+    pub name: String,
+    #[pyo3(attribute)]
+    pub content: String,
+    #[pyo3(attribute("start_line"))]
+    pub start_line: i32,
+    #[pyo3(attribute("end_line"))]
+    pub end_line: i32,
+    #[pyo3(attribute("start_pos"))]
+    pub start_pos: i32,
+    #[pyo3(attribute("end_pos"))]
+    pub end_pos: i32,
+}
+
+/// This class mirrors the Python class
+#[derive(Debug, FromPyObject, Serialize)]
+pub struct GrammarFile {
+    #[pyo3(attribute)]
+    pub path: String,
+    #[pyo3(attribute)]
+    pub directory: String,
+    #[pyo3(attribute)]
+    pub rules: HashMap<String, GrammarRule>,
+    #[pyo3(attribute)]
+    pub imports: Vec<String>,
+    #[pyo3(attribute)]
+    pub content: String,
+}
+
+/// This class mirrors the Python class
+#[derive(Debug, FromPyObject, Serialize)]
+pub struct UserGrammar {
+    #[pyo3(attribute("grammar_files"))]
+    pub grammar_files: HashMap<String, GrammarFile>,
+    #[pyo3(attribute("processed_files"))]
+    pub processed_files: HashSet<String>,
+}
+
+impl UserGrammar {
+    /// Every grammar file, imported ones included
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.grammar_files.values().map(|f| PathBuf::from(&f.path)).collect();
+        paths.sort();
+        paths
+    }
+
+    /// The file with the given `grammar_files` key or path
+    pub fn file(&self, file: &str) -> Option<&GrammarFile> {
+        self.grammar_files
+            .get(file)
+            .or_else(|| self.grammar_files.values().find(|f| f.path == file))
+    }
+
+    /// All rules of all files by name
+    pub fn rules(&self) -> BTreeMap<&str, &GrammarRule> {
+        self.grammar_files
+            .values()
+            .flat_map(|f| f.rules.values())
+            .map(|r| (r.name.as_str(), r))
+            .collect()
+    }
+}
+
+pub fn user_grammar(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<UserGrammar> {
+    Ok(parse_info.getattr(py, "grammar")?.extract(py)?)
+}
+
+/// Re-read the grammar files and hand the result to the session's
+/// `ParseInformation`. The generated parser and the trace stay as they are.
+pub fn refresh(py: Python<'_>, session: &ParseSession) -> CommandResult<UserGrammar> {
+    let fresh = ParseSession::open(py, session.grammar_path.clone())?;
+    let grammar = fresh.parse_info.getattr(py, "grammar")?;
+    session.parse_info.setattr(py, "grammar", &grammar)?;
+    Ok(grammar.extract(py)?)
+}
+
+/// Replace the text of `rule` in `content`, the current text of the file at
+/// `path`. Fails if the recorded offsets no longer point at the rule.
+pub fn splice_rule(path: &str, content: &str, rule: &GrammarRule, new_text: &str) -> CommandResult<String> {
+    let stale = || CommandError::StaleGrammarFile {
+        path: path.to_string(),
+        rule: rule.name.clone(),
+    };
+
+    // The offsets count characters of the file as Python reads it, i.e.
+    // with `\r\n` turned into `\n`
+    let crlf = content.contains("\r\n");
+    let text = content.replace("\r\n", "\n");
+    let start = usize::try_from(rule.start_pos)
+        .ok()
+        .and_then(|p| byte_offset(&text, p))
+        .ok_or_else(stale)?;
+    let end_pos = usize::try_from(rule.end_pos).map_err(|_| stale())?;
+    // Depending on the grammar parser `end_pos` is inclusive or exclusive
+    let end = [end_pos, end_pos + 1]
+        .into_iter()
+        .filter_map(|p| byte_offset(&text, p))
+        .find(|&end| end >= start && text[start..end].trim() == rule.content.trim())
+        .ok_or_else(stale)?;
+
+    let spliced = format!("{}{}{}", &text[..start], new_text.replace("\r\n", "\n"), &text[end..]);
+    Ok(if crlf {
+        spliced.replace('\n', "\r\n")
+    } else {
+        spliced
+    })
+}
+
+/// Save a grammar file, keeping the previous version as `<file>.bak`.
+pub fn save_file(path: &Path, content: &str) -> CommandResult<()> {
+    files::backup(path)?;
+    files::write_atomically(path, content.as_bytes())
+}
+
+/// Byte offset of the `chars`-th character; the end of `text` counts as well.
+fn byte_offset(text: &str, chars: usize) -> Option<usize> {
+    text.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .nth(chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `name` as found in `content`; `end_pos` exclusive, or inclusive if `inclusive`
+    fn rule(content: &str, name: &str, text: &str, inclusive: bool) -> GrammarRule {
+        let text_lf = content.replace("\r\n", "\n");
+        let byte_start = text_lf.find(text).unwrap();
+        let start = text_lf[..byte_start].chars().count() as i32;
+        let length = text.chars().count() as i32;
+        GrammarRule {
+            name: name.to_string(),
+            content: text.to_string(),
+            start_line: 0,
+            end_line: 0,
+            start_pos: start,
+            end_pos: if inclusive { start + length - 1 } else { start + length },
+        }
+    }
+
+    const GRAMMAR: &str = "grammar Regest;\n\n// Fälschung?\nvita: 'x' datum;\ndatum: 'y';";
+
+    #[test]
+    fn splices_a_rule_with_exclusive_or_inclusive_end() {
+        for inclusive in [false, true] {
+            let vita = rule(GRAMMAR, "vita", "vita: 'x' datum;", inclusive);
+            assert_eq!(
+                splice_rule("Regest.g4", GRAMMAR, &vita, "vita: 'z';").unwrap(),
+                "grammar Regest;\n\n// Fälschung?\nvita: 'z';\ndatum: 'y';"
+            );
+        }
+    }
+
+    #[test]
+    fn splices_the_rule_at_the_end_of_the_file() {
+        for inclusive in [false, true] {
+            let datum = rule(GRAMMAR, "datum", "datum: 'y';", inclusive);
+            assert_eq!(
+                splice_rule("Regest.g4", GRAMMAR, &datum, "datum\n    : 'y'\n    ;").unwrap(),
+                "grammar Regest;\n\n// Fälschung?\nvita: 'x' datum;\ndatum\n    : 'y'\n    ;"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let content = GRAMMAR.replace('\n', "\r\n");
+        let vita = rule(&content, "vita", "vita: 'x' datum;", false);
+        assert_eq!(
+            splice_rule("Regest.g4", &content, &vita, "vita\r\n    : 'z'\n    ;").unwrap(),
+            "grammar Regest;\r\n\r\n// Fälschung?\r\nvita\r\n    : 'z'\r\n    ;\r\ndatum: 'y';"
+        );
+    }
+
+    #[test]
+    fn refuses_offsets_that_no_longer_point_at_the_rule() {
+        let vita = rule(GRAMMAR, "vita", "vita: 'x' datum;", false);
+        let edited = GRAMMAR.replace("// Fälschung?\n", "");
+        let error = splice_rule("Regest.g4", &edited, &vita, "vita: 'z';").unwrap_err();
+        assert!(matches!(error, CommandError::StaleGrammarFile { rule, .. } if rule == "vita"));
+
+        let past_the_end = GrammarRule {
+            start_pos: 1000,
+            end_pos: 1010,
+            ..vita
+        };
+        assert!(splice_rule("Regest.g4", GRAMMAR, &past_the_end, "vita: 'z';").is_err());
+    }
+}
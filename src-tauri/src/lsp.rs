@@ -0,0 +1,626 @@
+// ==============================
+// src-tauri/src/lsp.rs
+// ==============================
+
+//! Language server for `.g4` files: `paredros-app lsp` speaks LSP on
+//! stdin/stdout.
+//!
+//! Which files belong together comes from the same `UserGrammar` the app
+//! loads, so definitions and references follow the imports. Rule names are
+//! found by scanning the text (the open buffer if there is one), which keeps
+//! positions right while a file is edited. Diagnostics are ANTLR's own errors,
+//! collected by generating the parser whenever a file is opened or saved.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::error::{CommandError, CommandResult};
+use crate::files;
+use crate::framing;
+use crate::grammar::{self, GrammarRule, UserGrammar};
+use crate::session::ParseSession;
+
+/// Requests answered here; anything else gets `METHOD_NOT_FOUND`
+const REQUESTS: &[&str] = &[
+    "initialize",
+    "shutdown",
+    "textDocument/definition",
+    "textDocument/references",
+    "textDocument/hover",
+    "textDocument/documentSymbol",
+    "textDocument/rename",
+];
+
+/// JSON-RPC error codes used here
+const METHOD_NOT_FOUND: i64 = -32601;
+const REQUEST_FAILED: i64 = -32803;
+
+/// LSP `DiagnosticSeverity`
+const SEVERITY_ERROR: i64 = 1;
+const SEVERITY_WARNING: i64 = 2;
+
+/// LSP `SymbolKind`
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_CONSTANT: i64 = 14;
+
+/// Serve one client on stdin and `output`, the original stdout.
+/// `main_grammar` is loaded right away; otherwise every opened file that no
+/// loaded grammar imports is loaded as a main grammar of its own.
+pub fn serve_stdio(output: impl Write, main_grammar: Option<PathBuf>) -> CommandResult<()> {
+    let stdin = io::stdin();
+    Server::new(stdin.lock(), output, main_grammar).serve()
+}
+
+/// A main grammar together with the files it imports
+struct LoadedGrammar {
+    session: ParseSession,
+    grammar: UserGrammar,
+}
+
+impl LoadedGrammar {
+    fn load(py: Python<'_>, path: &Path) -> CommandResult<Self> {
+        let session = ParseSession::open(py, path.display().to_string())?;
+        let grammar = grammar::user_grammar(py, &session.parse_info)?;
+        Ok(Self { session, grammar })
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.grammar.file_paths().iter().any(|p| files::same_file(p, path))
+    }
+
+    /// The rule and the path of the file that defines it
+    fn rule(&self, name: &str) -> Option<(&str, &GrammarRule)> {
+        self.grammar
+            .grammar_files
+            .values()
+            .find_map(|file| Some((file.path.as_str(), file.rules.get(name)?)))
+    }
+}
+
+/// One mention of a rule name in a grammar file
+struct Occurrence<'a> {
+    /// Byte offset
+    offset: usize,
+    name: &'a str,
+    /// The name is followed by `:`, i.e. this is where the rule is defined
+    is_definition: bool,
+}
+
+/// An `Occurrence` of a known name, detached from the text
+struct Mention {
+    offset: usize,
+    is_definition: bool,
+}
+
+struct Server<R, W> {
+    reader: BufReader<R>,
+    writer: W,
+    main_grammar: Option<PathBuf>,
+    grammars: Vec<LoadedGrammar>,
+    /// Text of the open documents, which may be ahead of the files on disk
+    documents: HashMap<PathBuf, String>,
+    /// Files that got diagnostics, so they can be cleared once fixed
+    diagnosed: HashSet<PathBuf>,
+}
+
+impl<R: Read, W: Write> Server<R, W> {
+    fn new(reader: R, writer: W, main_grammar: Option<PathBuf>) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+            main_grammar,
+            grammars: Vec::new(),
+            documents: HashMap::new(),
+            diagnosed: HashSet::new(),
+        }
+    }
+
+    fn serve(mut self) -> CommandResult<()> {
+        while let Some(message) = framing::read_message(&mut self.reader).map_err(transport_error)? {
+            let Some(method) = message["method"].as_str().map(str::to_string) else {
+                // A response to something we never ask for
+                continue;
+            };
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+            let Some(id) = message.get("id").cloned() else {
+                if method == "exit" {
+                    break;
+                }
+                let result = Python::with_gil(|py| self.notification(py, &method, &params));
+                if let Err(e) = result {
+                    self.log(&format!("{method}: {e}"))?;
+                }
+                continue;
+            };
+
+            let response = if !REQUESTS.contains(&method.as_str()) {
+                let message = format!("Unsupported request {method:?}");
+                json!({ "jsonrpc": "2.0", "id": id, "error": { "code": METHOD_NOT_FOUND, "message": message } })
+            } else {
+                match Python::with_gil(|py| self.request(py, &method, &params)) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(e) => {
+                        json!({ "jsonrpc": "2.0", "id": id, "error": { "code": REQUEST_FAILED, "message": e.to_string() } })
+                    }
+                }
+            };
+            framing::write_message(&mut self.writer, &response).map_err(transport_error)?;
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, py: Python<'_>, method: &str, params: &Value) -> CommandResult<Value> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "renameProvider": true,
+                },
+                "serverInfo": { "name": "paredros", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => {
+                let (path, name) = self.name_at(py, params)?;
+                let Some(loaded) = self.grammar_for(&path) else {
+                    return Ok(Value::Null);
+                };
+                let Some((file, _)) = loaded.rule(&name) else {
+                    return Ok(Value::Null);
+                };
+                let file = PathBuf::from(file);
+                let text = self.text(&file)?;
+                let definition = occurrences(&text).into_iter().find(|o| o.is_definition && o.name == name);
+                Ok(match definition {
+                    Some(o) => location(&file, &text, o.offset, name.len())?,
+                    None => Value::Null,
+                })
+            }
+            "textDocument/references" => {
+                let (path, name) = self.name_at(py, params)?;
+                let include_declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+                let mut locations = Vec::new();
+                for (file, text, found) in self.mentions(&path, &name)? {
+                    for m in found.into_iter().filter(|m| include_declaration || !m.is_definition) {
+                        locations.push(location(&file, &text, m.offset, name.len())?);
+                    }
+                }
+                Ok(locations.into())
+            }
+            "textDocument/hover" => {
+                let (path, name) = self.name_at(py, params)?;
+                let Some((file, rule)) = self.grammar_for(&path).and_then(|g| g.rule(&name)) else {
+                    return Ok(Value::Null);
+                };
+                let file_name = Path::new(file)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                Ok(json!({
+                    "contents": {
+                        "kind": "markdown",
+                        "value": format!("```antlr\n{}\n```\n{file_name}, line {}", rule.content.trim(), rule.start_line),
+                    }
+                }))
+            }
+            "textDocument/documentSymbol" => {
+                let path = document_path(params)?;
+                self.ensure_loaded(py, &path)?;
+                let text = self.text(&path)?;
+                let rules: HashSet<&str> = self
+                    .grammar_for(&path)
+                    .map(|g| g.grammar.rules().into_keys().collect())
+                    .unwrap_or_default();
+                let symbols = occurrences(&text)
+                    .into_iter()
+                    .filter(|o| o.is_definition && rules.contains(o.name))
+                    .map(|o| {
+                        let name_range = range(&text, o.offset, o.name.len());
+                        let end = rule_end(&text, o.offset);
+                        Ok(json!({
+                            "name": o.name,
+                            "kind": if is_lexer_rule(o.name) { SYMBOL_CONSTANT } else { SYMBOL_FUNCTION },
+                            "range": range(&text, o.offset, end - o.offset),
+                            "selectionRange": name_range,
+                        }))
+                    })
+                    .collect::<CommandResult<Vec<_>>>()?;
+                Ok(symbols.into())
+            }
+            "textDocument/rename" => {
+                let (path, name) = self.name_at(py, params)?;
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                self.check_new_name(&path, &name, new_name)?;
+
+                let mut changes = serde_json::Map::new();
+                for (file, text, found) in self.mentions(&path, &name)? {
+                    if found.is_empty() {
+                        continue;
+                    }
+                    let edits: Vec<Value> = found
+                        .iter()
+                        .map(|m| json!({ "range": range(&text, m.offset, name.len()), "newText": new_name }))
+                        .collect();
+                    changes.insert(uri(&file)?, edits.into());
+                }
+                Ok(json!({ "changes": changes }))
+            }
+            _ => Err(CommandError::invalid_argument(format!("Unsupported request {method:?}"))),
+        }
+    }
+
+    fn notification(&mut self, py: Python<'_>, method: &str, params: &Value) -> CommandResult<()> {
+        match method {
+            "initialized" => {
+                if let Some(main) = self.main_grammar.clone() {
+                    self.ensure_loaded(py, &main)?;
+                    self.diagnose(py, &main)?;
+                }
+            }
+            "textDocument/didOpen" => {
+                let path = document_path(params)?;
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(path.clone(), text.to_string());
+                self.ensure_loaded(py, &path)?;
+                self.diagnose(py, &path)?;
+            }
+            "textDocument/didChange" => {
+                let path = document_path(params)?;
+                // Full sync: the last change holds the whole text
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(path, text.to_string());
+                }
+            }
+            "textDocument/didSave" => {
+                let path = document_path(params)?;
+                // Imports and rules may have changed
+                for loaded in self.grammars.iter_mut().filter(|g| g.contains(&path)) {
+                    loaded.grammar = grammar::refresh(py, &loaded.session)?;
+                }
+                self.ensure_loaded(py, &path)?;
+                self.diagnose(py, &path)?;
+            }
+            "textDocument/didClose" => {
+                let path = document_path(params)?;
+                self.documents.remove(&path);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Load `path` as a main grammar unless a loaded grammar includes it.
+    fn ensure_loaded(&mut self, py: Python<'_>, path: &Path) -> CommandResult<()> {
+        if self.grammar_for(path).is_none() {
+            let loaded = LoadedGrammar::load(py, path)?;
+            // A grammar opened before may turn out to be imported by this one
+            self.grammars
+                .retain(|g| !loaded.contains(Path::new(&g.session.grammar_path)));
+            self.grammars.push(loaded);
+        }
+        Ok(())
+    }
+
+    fn grammar_for(&self, path: &Path) -> Option<&LoadedGrammar> {
+        self.grammars.iter().find(|g| g.contains(path))
+    }
+
+    /// Generate the parser of the grammar that includes `path` and publish
+    /// ANTLR's errors for each of its files.
+    fn diagnose(&mut self, py: Python<'_>, path: &Path) -> CommandResult<()> {
+        let Some(loaded) = self.grammar_for(path) else {
+            return Ok(());
+        };
+        let grammar_files = loaded.grammar.file_paths();
+
+        let mut found: HashMap<PathBuf, Vec<Value>> = HashMap::new();
+        match loaded.session.generate_parser(py) {
+            Ok(()) => {}
+            Err(CommandError::GrammarGenerationFailed { tool_output }) => {
+                for line in tool_output.lines() {
+                    let Some((file, diagnostic)) = parse_antlr_message(line) else {
+                        continue;
+                    };
+                    let file = grammar_files
+                        .iter()
+                        .find(|p| files::same_file(p, Path::new(&file)))
+                        .cloned()
+                        .unwrap_or_else(|| path.to_path_buf());
+                    found.entry(file).or_default().push(diagnostic);
+                }
+            }
+            Err(e) => {
+                // Not ANTLR's fault; show it on the file at hand
+                found.entry(path.to_path_buf()).or_default().push(json!({
+                    "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+                    "severity": SEVERITY_ERROR,
+                    "source": "paredros",
+                    "message": e.to_string(),
+                }));
+            }
+        }
+
+        // Clear what was reported before for these files
+        for file in grammar_files.iter().chain(std::iter::once(&path.to_path_buf())) {
+            if self.diagnosed.remove(file) && !found.contains_key(file) {
+                found.insert(file.clone(), Vec::new());
+            }
+        }
+        for (file, diagnostics) in found {
+            if !diagnostics.is_empty() {
+                self.diagnosed.insert(file.clone());
+            }
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri(&file)?, "diagnostics": diagnostics },
+            });
+            framing::write_message(&mut self.writer, &notification).map_err(transport_error)?;
+        }
+        Ok(())
+    }
+
+    /// The rule name under the cursor of a `TextDocumentPositionParams`
+    fn name_at(&mut self, py: Python<'_>, params: &Value) -> CommandResult<(PathBuf, String)> {
+        let path = document_path(params)?;
+        self.ensure_loaded(py, &path)?;
+        let text = self.text(&path)?;
+        let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+        let offset = byte_offset(&text, line, character);
+        let name = occurrences(&text)
+            .into_iter()
+            .find(|o| (o.offset..=o.offset + o.name.len()).contains(&offset))
+            .map(|o| o.name.to_string())
+            .ok_or_else(|| CommandError::invalid_argument("No rule name at this position"))?;
+        Ok((path, name))
+    }
+
+    /// Every file of the grammar that includes `path`, its text and where
+    /// `name` occurs in it
+    fn mentions(&self, path: &Path, name: &str) -> CommandResult<Vec<(PathBuf, String, Vec<Mention>)>> {
+        let files = match self.grammar_for(path) {
+            Some(loaded) => loaded.grammar.file_paths(),
+            None => vec![path.to_path_buf()],
+        };
+        files
+            .into_iter()
+            .map(|file| {
+                let text = self.text(&file)?;
+                let found = occurrences(&text)
+                    .into_iter()
+                    .filter(|o| o.name == name)
+                    .map(|o| Mention {
+                        offset: o.offset,
+                        is_definition: o.is_definition,
+                    })
+                    .collect();
+                Ok((file, text, found))
+            })
+            .collect()
+    }
+
+    fn check_new_name(&self, path: &Path, old: &str, new: &str) -> CommandResult<()> {
+        let mut chars = new.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(CommandError::invalid_argument(format!("{new:?} is not a valid rule name")));
+        }
+        if is_lexer_rule(old) != is_lexer_rule(new) {
+            return Err(CommandError::invalid_argument(
+                "Lexer rules start with an upper case letter, parser rules with a lower case one",
+            ));
+        }
+        if self.grammar_for(path).and_then(|g| g.rule(new)).is_some() {
+            return Err(CommandError::invalid_argument(format!("There already is a rule {new}")));
+        }
+        Ok(())
+    }
+
+    /// The open buffer if there is one, the file on disk otherwise
+    fn text(&self, path: &Path) -> CommandResult<String> {
+        if let Some(text) = self
+            .documents
+            .iter()
+            .find(|(open, _)| files::same_file(open, path))
+            .map(|(_, text)| text)
+        {
+            return Ok(text.clone());
+        }
+        std::fs::read_to_string(path).map_err(|e| CommandError::io(path, e))
+    }
+
+    fn log(&mut self, message: &str) -> CommandResult<()> {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "window/logMessage",
+            "params": { "type": 1, "message": message },
+        });
+        framing::write_message(&mut self.writer, &notification).map_err(transport_error)
+    }
+}
+
+/// Identifiers outside of comments, literals, character sets and actions
+fn occurrences(text: &str) -> Vec<Occurrence<'_>> {
+    let bytes = text.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(end) = skip_non_code(text, i) {
+            i = end;
+            continue;
+        }
+        if bytes[i].is_ascii_alphabetic() || bytes[i] == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            let next = text[i..].trim_start().as_bytes().first();
+            found.push(Occurrence {
+                offset: start,
+                name: &text[start..i],
+                is_definition: next == Some(&b':'),
+            });
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+/// If a comment, literal, character set or action starts at byte `i`, the
+/// offset just past its end
+fn skip_non_code(text: &str, i: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let end = match bytes[i] {
+        b'/' if bytes.get(i + 1) == Some(&b'/') => text[i..].find('\n').map_or(bytes.len(), |n| i + n),
+        b'/' if bytes.get(i + 1) == Some(&b'*') => text[i + 2..].find("*/").map_or(bytes.len(), |n| i + 2 + n + 2),
+        quote @ (b'\'' | b'[') => {
+            let close = if quote == b'[' { b']' } else { b'\'' };
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] != close {
+                j += if bytes[j] == b'\\' { 2 } else { 1 };
+            }
+            j + 1
+        }
+        b'{' => {
+            let mut depth = 0;
+            let mut j = i;
+            while j < bytes.len() {
+                match bytes[j] {
+                    b'{' => depth += 1,
+                    b'}' => depth -= 1,
+                    _ => {}
+                }
+                j += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            j
+        }
+        _ => return None,
+    };
+    Some(end.min(bytes.len()))
+}
+
+/// Byte offset just past the `;` that ends the rule starting at `offset`
+fn rule_end(text: &str, offset: usize) -> usize {
+    let mut i = offset;
+    while i < text.len() {
+        if let Some(end) = skip_non_code(text, i) {
+            i = end;
+        } else if text.as_bytes()[i] == b';' {
+            return i + 1;
+        } else {
+            i += 1;
+        }
+    }
+    text.len()
+}
+
+fn is_lexer_rule(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+/// `error(56): Regest.g4:12:4: reference to undefined rule: foo` into the
+/// file name and a diagnostic
+fn parse_antlr_message(line: &str) -> Option<(String, Value)> {
+    let line = line.trim();
+    let severity = if line.starts_with("error") {
+        SEVERITY_ERROR
+    } else if line.starts_with("warning") {
+        SEVERITY_WARNING
+    } else {
+        return None;
+    };
+    let (code, rest) = line.split_once("): ")?;
+    let code = code.split_once('(')?.1;
+
+    // The path may contain `:` itself, e.g. on Windows
+    rest.match_indices(": ").find_map(|(i, _)| {
+        let mut parts = rest[..i].rsplitn(3, ':');
+        let column: u64 = parts.next()?.parse().ok()?;
+        let line: u64 = parts.next()?.parse().ok()?;
+        let file = parts.next()?;
+        let start = json!({ "line": line.saturating_sub(1), "character": column });
+        Some((
+            file.to_string(),
+            json!({
+                "range": { "start": start, "end": start },
+                "severity": severity,
+                "code": code,
+                "source": "antlr4",
+                "message": &rest[i + 2..],
+            }),
+        ))
+    })
+}
+
+fn document_path(params: &Value) -> CommandResult<PathBuf> {
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or_else(|| CommandError::invalid_argument(format!("{uri:?} is not a file URI")))
+}
+
+fn uri(path: &Path) -> CommandResult<String> {
+    let absolute = std::path::absolute(path).map_err(|e| CommandError::io(path, e))?;
+    Url::from_file_path(&absolute)
+        .map(String::from)
+        .map_err(|_| CommandError::invalid_argument(format!("{} cannot be made a URI", path.display())))
+}
+
+fn location(path: &Path, text: &str, offset: usize, len: usize) -> CommandResult<Value> {
+    Ok(json!({ "uri": uri(path)?, "range": range(text, offset, len) }))
+}
+
+/// LSP range of `len` bytes at `offset`
+fn range(text: &str, offset: usize, len: usize) -> Value {
+    json!({ "start": position(text, offset), "end": position(text, offset + len) })
+}
+
+/// LSP position (line, UTF-16 column) of a byte offset
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+/// Byte offset of an LSP position; clamped to the end of the line
+fn byte_offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start = text
+        .match_indices('\n')
+        .nth(line.wrapping_sub(1))
+        .filter(|_| line > 0)
+        .map_or(0, |(n, _)| n + 1);
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn transport_error(err: io::Error) -> CommandError {
+    CommandError::io(Path::new("<lsp>"), err)
+}
@@ -1,418 +1,901 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod api;
+mod breakpoints;
+mod cli;
+mod commands;
+mod corpus;
+mod dap;
+mod debugger;
+mod diagnostics;
+mod error;
+mod expr;
+mod files;
+mod framing;
+mod grammar;
+mod lsp;
+mod project;
 mod python_env;
-
-use crate::python_env::{delete_venv, ensure_python_async};
+mod session;
+mod snapshot;
+mod tokens;
+mod traceback;
+mod tree_export;
+mod watcher;
+mod worker;
+
+use crate::api::{ApiServer, ApiServerInfo};
+use crate::breakpoints::{Breakpoint, BreakpointSpec, ContinueResult};
+use crate::corpus::{CorpusCase, CorpusReport};
+use crate::debugger::{ParseStepInfo, TokenInfo, TraceChunk, DEFAULT_TRACE_CHUNK};
+use crate::diagnostics::DiagnosticsReport;
+use crate::error::{CommandError, CommandResult};
+use crate::expr::WatchValue;
+use crate::grammar::UserGrammar;
+use crate::project::LoadedProject;
+use crate::python_env::{delete_venv, ensure_python_async, PythonSettings, WheelhouseReport};
+use crate::snapshot::{TreeChange, TreeNode};
+use crate::session::{EvictionPolicy, ParseInfoStore, SessionSummary};
+use crate::tokens::TokenFormat;
+use crate::tree_export::{TreeExportOptions, TreeFormat};
+use crate::watcher::GrammarWatcher;
+use crate::worker::PythonWorker;
 use pyo3::prelude::*;
-use pythonize::depythonize; // keep if you still use it in other commands
-use std::collections::{HashMap, HashSet};
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Mutex,
-};
+use std::path::PathBuf;
+use std::process::ExitCode;
 use serde::Serialize;
-use tauri::{AppHandle, State, Builder};
-// ---------------- Store ----------------
+use tauri::{AppHandle, Builder, Emitter, Manager, State};
+
+// ---------------- Commands ----------------
 
-struct ParseInfoStore {
-    counter: AtomicUsize,
-    nodes: Mutex<HashMap<usize, Py<PyAny>>>,
+#[tauri::command]
+async fn get_parse_info(
+    grammar: String,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<usize> {
+    worker
+        .run("get_parse_info", op_id, move |store| {
+            commands::open(store, grammar)
+        })
+        .await
 }
 
-impl Default for ParseInfoStore {
-    fn default() -> Self {
-        Self {
-            counter: AtomicUsize::new(1),
-            nodes: Mutex::new(HashMap::new()),
-        }
-    }
+/// Drops a ParseInformation instance together with its generated parser.
+/// Returns false if the instance was already closed or evicted.
+#[tauri::command]
+async fn close_parse_info(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+    watcher: State<'_, GrammarWatcher>,
+) -> CommandResult<bool> {
+    watcher.unwatch(id)?;
+    worker
+        .run("close_parse_info", None, move |store| {
+            store.remove(id)
+        })
+        .await
 }
 
-// ---------------- Commands ----------------
+/// Lists all open ParseInformation instances
+#[tauri::command]
+async fn list_parse_infos(worker: State<'_, PythonWorker>) -> CommandResult<Vec<SessionSummary>> {
+    worker
+        .run("list_parse_infos", None, move |store| {
+            store.list()
+        })
+        .await
+}
 
 #[tauri::command]
-fn get_parse_info(grammar: String, store: State<ParseInfoStore>) -> Result<usize, String> {
-    Python::with_gil(|py| {
-        let module = py
-            .import("paredros_debugger.ParseInformation")
-            .map_err(|e| e.to_string())?;
-        let cls = module.getattr("ParseInformation").map_err(|e| e.to_string())?;
-        let obj = cls.call1((grammar,)).map_err(|e| e.to_string())?;
-        let id = store.counter.fetch_add(1, Ordering::SeqCst);
-        store.nodes.lock().unwrap().insert(id, obj.into());
-        Ok(id)
-    })
+fn get_session_policy(store: State<ParseInfoStore>) -> CommandResult<EvictionPolicy> {
+    store.policy()
 }
 
-/// Call the generate_parser method on a stored ParseInformation instance
+/// Changes the session cap / idle timeout. Returns the ids of the sessions closed by it.
+#[tauri::command]
+async fn set_session_policy(
+    policy: EvictionPolicy,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<usize>> {
+    worker
+        .run("set_session_policy", None, move |store| {
+            if policy.max_sessions == 0 {
+                return Err(CommandError::invalid_argument("max_sessions must be at least 1"));
+            }
+            store.set_policy(policy)
+        })
+        .await
+}
+
+/// Writes grammar path, parsed input, current step and the given UI state to a `.paredros` file
 #[tauri::command]
-fn generate_parser(
+async fn save_session(
     id: usize,
-    store: State<ParseInfoStore>,
-) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+    path: PathBuf,
+    ui_state: Option<serde_json::Value>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<()> {
+    worker
+        .run("save_session", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+
+            let project = Python::with_gil(|py| {
+                project::capture(py, &session, &path, ui_state.unwrap_or_default())
+            })?;
+            project::write(&path, &project)
+        })
+        .await
+}
+
+/// Opens a `.paredros` file in a new ParseInformation instance, replaying
+/// parser generation, the saved input and the step
+#[tauri::command]
+async fn load_session(
+    path: PathBuf,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<LoadedProject> {
+    worker
+        .run("load_session", op_id, move |store| {
+            let project = project::read(&path)?;
+            let session = Python::with_gil(|py| project::restore(py, &project))?;
+            let id = store.insert(session)?;
+            Ok(LoadedProject { id, project })
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "generate_parser").map_err(|e| e.to_string())?;
-        
-        func.call0(py).map_err(|e| e.to_string())?;
+/// Watches all grammar files of the instance, imports included. On every change the
+/// parser is regenerated, the last input parsed again and `grammar/reloaded` emitted.
+/// Returns the watched files.
+#[tauri::command]
+async fn watch_grammar(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+    watcher: State<'_, GrammarWatcher>,
+) -> CommandResult<Vec<PathBuf>> {
+    let files = worker
+        .run("watch_grammar", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+
+            Python::with_gil(|py| grammar::user_grammar(py, &session.parse_info))
+        })
+        .await?
+        .file_paths();
+    watcher.watch(id, &files)?;
+    Ok(files)
+}
 
-        Ok("Generated parser successfully".to_string())
-    })
+/// Returns false if the instance wasn't watched
+#[tauri::command]
+fn unwatch_grammar(id: usize, watcher: State<GrammarWatcher>) -> CommandResult<bool> {
+    watcher.unwatch(id)
 }
 
-/// Call the parse_input method on a stored ParseInformation instance
+/// Replaces the text of a rule in its grammar file, keeping the previous version as
+/// `<file>.bak`, and returns the refreshed grammar. `file` is a key of `grammar_files` or a path.
 #[tauri::command]
-fn parse_input(
+async fn update_grammar_rule(
     id: usize,
-    input: String,
-    store: State<ParseInfoStore>,
-) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+    file: String,
+    rule_name: String,
+    new_content: String,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<UserGrammar> {
+    worker
+        .run("update_grammar_rule", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+
+            Python::with_gil(|py| {
+                let user_grammar = grammar::user_grammar(py, &session.parse_info)?;
+                let grammar_file = user_grammar.file(&file).ok_or_else(|| {
+                    CommandError::invalid_argument(format!("{file} is not part of the grammar"))
+                })?;
+                let rule = grammar_file.rules.get(&rule_name).ok_or_else(|| {
+                    CommandError::invalid_argument(format!("{file} has no rule {rule_name}"))
+                })?;
+
+                let path = std::path::Path::new(&grammar_file.path);
+                let content = std::fs::read_to_string(path).map_err(|e| CommandError::io(path, e))?;
+                let updated = grammar::splice_rule(&grammar_file.path, &content, rule, &new_content)?;
+                grammar::save_file(path, &updated)?;
+                grammar::refresh(py, &session)
+            })
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        // parse the input
-        let func = parse_info.getattr(py, "parse").map_err(|e| e.to_string())?;
-        func.call1(py, (input,)).map_err(|e| e.to_string())?;
+/// Overwrites one of the grammar files, keeping the previous version as `<file>.bak`,
+/// and returns the refreshed grammar
+#[tauri::command]
+async fn write_grammar_file(
+    id: usize,
+    path: String,
+    content: String,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<UserGrammar> {
+    worker
+        .run("write_grammar_file", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+
+            Python::with_gil(|py| {
+                let user_grammar = grammar::user_grammar(py, &session.parse_info)?;
+                let grammar_file = user_grammar.file(&path).ok_or_else(|| {
+                    CommandError::invalid_argument(format!("{path} is not part of the grammar"))
+                })?;
+
+                grammar::save_file(std::path::Path::new(&grammar_file.path), &content)?;
+                grammar::refresh(py, &session)
+            })
+        })
+        .await
+}
 
-        // go to step 0 in the parse process
-        let func2 = parse_info.getattr(py, "go_to_step").map_err(|e| e.to_string())?;
-        func2.call1(py, (0,)).map_err(|e| e.to_string())?;
+/// Call the generate_parser method on a stored ParseInformation instance
+#[tauri::command]
+async fn generate_parser(
+    id: usize,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("generate_parser", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::generate_parser(py, session))
+        })
+        .await
+}
 
-        Ok("Parsed successfully".to_string())
-    })
+/// Call the parse_input method on a stored ParseInformation instance
+#[tauri::command]
+async fn parse_input(
+    id: usize,
+    input: String,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("parse_input", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::parse_input(py, session, input))
+        })
+        .await
 }
 
 /// Jump to a specific step
 #[tauri::command]
-fn go_to_step(
+async fn go_to_step(
     id: usize,
     step_id: usize,
-    store: State<ParseInfoStore>,
-) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
-
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "go_to_step").map_err(|e| e.to_string())?;
-        
-        func.call1(py, (step_id,)).map_err(|e| e.to_string())?;
-
-        Ok("Went to indicated step successfully".to_string())
-    })
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("go_to_step", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::go_to_step(py, session, step_id))
+        })
+        .await
 }
 
 /// Step to next decision
 #[tauri::command]
-fn step_until_next_decision(
+async fn step_until_next_decision(
     id: usize,
-    store: State<ParseInfoStore>,
-) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
-
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "step_until_next_decision").map_err(|e| e.to_string())?;
-        
-        func.call0(py).map_err(|e| e.to_string())?;
-
-        Ok("Went to indicated step successfully".to_string())
-    })
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("step_until_next_decision", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::step_until_next_decision(py, session))
+        })
+        .await
 }
 
 #[tauri::command]
-fn step_back_until_previous_decision(
+async fn step_back_until_previous_decision(
     id: usize,
-    store: State<ParseInfoStore>,
-) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
-
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "step_back_until_previous_decision").map_err(|e| e.to_string())?;
-        
-        func.call0(py).map_err(|e| e.to_string())?;
-
-        Ok("Went to indicated step successfully".to_string())
-    })
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("step_back_until_previous_decision", op_id, move |store| {
+            commands::with_session(store, id, |py, session| {
+                commands::step_back_until_previous_decision(py, session)
+            })
+        })
+        .await
 }
 
+/// Adds a breakpoint, or replaces the one with the given id
+#[tauri::command]
+async fn set_breakpoint(
+    id: usize,
+    breakpoint: BreakpointSpec,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Breakpoint> {
+    worker
+        .run("set_breakpoint", None, move |store| {
+            commands::with_session(store, id, |_py, session| commands::set_breakpoint(session, breakpoint))
+        })
+        .await
+}
 
-/// This class mirrors the Python class
-#[derive(Debug, FromPyObject, Serialize)]
-struct GrammarRule {
-    #[pyo3(attribute)]
-    name: String,
-    #[pyo3(attribute)]
-    content: String,
-    #[pyo3(attribute("start_line"))]
-    start_line: i32,
-    #[pyo3(attribute("end_line"))]
-    end_line: i32,
-    #[pyo3(attribute("start_pos"))]
-    start_pos: i32,
-    #[pyo3(attribute("end_pos"))]
-    end_pos: i32,
-}
-
-/// This class mirrors the Python class
-#[derive(Debug, FromPyObject, Serialize)]
-struct GrammarFile {
-    #[pyo3(attribute)]
-    path: String,
-    #[pyo3(attribute)]
-    directory: String,
-    #[pyo3(attribute)]
-    rules: HashMap<String, GrammarRule>,
-    #[pyo3(attribute)]
-    imports: Vec<String>,
-    #[pyo3(attribute)]
-    content: String,
+/// Removes a breakpoint; returns false if there was none with that id
+#[tauri::command]
+async fn remove_breakpoint(
+    id: usize,
+    breakpoint_id: u32,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<bool> {
+    worker
+        .run("remove_breakpoint", None, move |store| {
+            commands::with_session(store, id, |_py, session| {
+                Ok(commands::remove_breakpoint(session, breakpoint_id))
+            })
+        })
+        .await
 }
 
-/// This class mirrors the Python class
-#[derive(Debug, FromPyObject, Serialize)]
-struct UserGrammar {
-    #[pyo3(attribute("grammar_files"))]
-    grammar_files: HashMap<String, GrammarFile>,
-    #[pyo3(attribute("processed_files"))]
-    processed_files: HashSet<String>,
+#[tauri::command]
+async fn list_breakpoints(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<Breakpoint>> {
+    worker
+        .run("list_breakpoints", None, move |store| {
+            commands::with_session(store, id, |_py, session| Ok(commands::list_breakpoints(session)))
+        })
+        .await
 }
 
-/// Mirrors the `possible_transitions` entries
-#[derive(Debug, FromPyObject, Serialize)]
-#[pyo3(from_item_all)]
-struct Transition {
-    target_state: usize,
-    matches: Vec<String>,
+/// Evaluates watch expressions at the given step, or at the current one.
+/// An expression that doesn't parse gets an error of its own
+#[tauri::command]
+async fn evaluate_watches(
+    id: usize,
+    expressions: Vec<String>,
+    step_id: Option<usize>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<WatchValue>> {
+    worker
+        .run("evaluate_watches", None, move |store| {
+            commands::with_session(store, id, |py, session| {
+                commands::evaluate_watches(py, session, &expressions, step_id)
+            })
+        })
+        .await
 }
 
-/// Mirrors the `grammar_rule_location` sub-dict
-#[derive(Debug, FromPyObject, Serialize)]
-#[pyo3(from_item_all)]
-struct GrammarRuleLocation {
-    name: String,
-    content: String,
-    file_path: String,
-    start_line: usize,
-    end_line: usize,
-    start_pos: usize,
-    end_pos: usize,
-}
-
-/// Mirrors `ParseStepInfo`, return type of `get_current_parse_step_info`
-#[derive(Debug, FromPyObject, Serialize)]
-#[pyo3(from_item_all)]
-#[serde(rename_all = "snake_case")]
-struct ParseStepInfo {
-    step_id: String,
-    step_type: String,
-    rule_name: Option<String>,
-    rule_stack: Vec<String>,
-    state: String,
-    current_token_repr: String,
-    token_index: usize,
-    chosen_transition_index: Option<i32>,
-    input_text_context: String,
-    next_token_stream_index: usize,
-    lookahead_repr: Vec<String>,
-    matching_error: bool,
-    is_error_node: bool,
-    possible_transitions: Option<Vec<Transition>>,
-    grammar_rule_location: Option<GrammarRuleLocation>,
-    input_context_snippet: Option<String>,
-}
-
-#[derive(Debug, FromPyObject, Serialize)]
-#[pyo3(from_item_all)]
-#[serde(rename_all = "camelCase")]
-struct TokenInfo {
-    text: String,
-    type_name: String,
-    type_id: i32,
-    line: i32,
-    column: i32,
-    start_index: i32,
-    stop_index: i32,
-    token_index: i32,
+/// Steps forward until a step hits an enabled breakpoint, or to the last step
+#[tauri::command]
+async fn continue_to_breakpoint(
+    id: usize,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<ContinueResult> {
+    worker
+        .run("continue_to_breakpoint", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::continue_to_breakpoint(py, session))
+        })
+        .await
 }
 
-/// Gets the property "grammar" from a ParseInformation instance
+/// Steps backward until a step hits an enabled breakpoint, or to the first step
 #[tauri::command]
-fn get_user_grammar(id: usize, store: State<ParseInfoStore>) -> Result<UserGrammar, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+async fn reverse_continue(
+    id: usize,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<ContinueResult> {
+    worker
+        .run("reverse_continue", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::reverse_continue(py, session))
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        let py_grammar = parse_info
-            .getattr(py, "grammar")
-            .map_err(|e| e.to_string())?;
 
-        py_grammar.extract(py).map_err(|e| e.to_string())
-    })
+/// Gets the property "grammar" from a ParseInformation instance
+#[tauri::command]
+async fn get_user_grammar(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<UserGrammar> {
+    worker
+        .run("get_user_grammar", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::user_grammar(py, session))
+        })
+        .await
 }
 
 /// Gets the meta information dictionary
 #[tauri::command]
-fn get_current_parse_step_info(id: usize, store: State<ParseInfoStore>) -> Result<ParseStepInfo, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+async fn get_current_parse_step_info(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<ParseStepInfo> {
+    worker
+        .run("get_current_parse_step_info", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::current_step(py, session))
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        let py_step = parse_info
-            .getattr(py, "get_current_parse_step_info")
-            .map_err(|e| e.to_string())?
-            .call0(py)
-            .map_err(|e| e.to_string())?;
-        py_step.extract(py).map_err(|e| e.to_string())
-    })
+/// Gets the meta information dictionary of the step after the current one,
+/// or nothing if the current step is the last one. The cursor stays where it is.
+#[tauri::command]
+async fn get_next_parse_step_info(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Option<ParseStepInfo>> {
+    worker
+        .run("get_next_parse_step_info", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::next_step(py, session))
+        })
+        .await
 }
 
-/// Gets the meta information dictionary
+/// Gets the meta information dictionary of any step without moving the cursor
 #[tauri::command]
-fn get_next_parse_step_info(id: usize, store: State<ParseInfoStore>) -> Result<ParseStepInfo, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+async fn get_parse_step_info(
+    id: usize,
+    step_id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<ParseStepInfo> {
+    worker
+        .run("get_parse_step_info", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::step_info(py, session, step_id))
+        })
+        .await
+}
 
-    let current_step: ParseStepInfo = Python::with_gil(|py| {
-        let py_step = parse_info
-            .getattr(py, "get_current_parse_step_info")
-            .map_err(|e| e.to_string())?
-            .call0(py)
-            .map_err(|e| e.to_string())?;
-        py_step.extract(py).map_err(|e| e.to_string())
-    })?;
+/// Gets the steps `from..to` (end exclusive, cut at the last step) without moving the cursor
+#[tauri::command]
+async fn get_parse_step_range(
+    id: usize,
+    from: usize,
+    to: usize,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<ParseStepInfo>> {
+    worker
+        .run("get_parse_step_range", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::step_range(py, session, from, to))
+        })
+        .await
+}
 
-    let old_step_id = current_step.step_id.parse::<i32>().unwrap();
+/// Gets every step of the trace in one go, without moving the cursor
+#[tauri::command]
+async fn get_full_trace(
+    id: usize,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<ParseStepInfo>> {
+    worker
+        .run("get_full_trace", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::full_trace(py, session))
+        })
+        .await
+}
+
+/// Like `get_full_trace`, but sends the steps as `trace/chunk` events of at most
+/// `chunk_size` steps, tagged with `op_id`. Returns the number of steps.
+#[tauri::command]
+async fn stream_full_trace(
+    id: usize,
+    op_id: String,
+    chunk_size: Option<usize>,
+    app: AppHandle,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<usize> {
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_TRACE_CHUNK).max(1);
+    let chunk_op_id = op_id.clone();
+    worker
+        .run("stream_full_trace", Some(op_id), move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+            let parse_info = session.parsed()?;
+
+            let mut offset = 0;
+            let mut steps = Vec::with_capacity(chunk_size);
+            let emit = |offset: usize, steps: Vec<ParseStepInfo>, done: bool| {
+                let _ = app.emit(
+                    "trace/chunk",
+                    TraceChunk {
+                        id,
+                        op_id: chunk_op_id.clone(),
+                        offset,
+                        steps,
+                        done,
+                    },
+                );
+            };
+            Python::with_gil(|py| {
+                debugger::visit_trace(py, parse_info, |step| {
+                    steps.push(step);
+                    if steps.len() == chunk_size {
+                        emit(offset, std::mem::take(&mut steps), false);
+                        offset += chunk_size;
+                    }
+                    Ok(())
+                })
+            })?;
+
+            let total = offset + steps.len();
+            emit(offset, steps, true);
+            Ok(total)
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "go_to_step").map_err(|e| e.to_string())?;
-        func.call1(py, (old_step_id+1,)).map_err(|e| e.to_string())
-    })?;
+/// Payload of the `corpus/case` event, sent after each input of `run_corpus`
+#[derive(Debug, Clone, Serialize)]
+struct CorpusProgress<'a> {
+    op_id: Option<&'a str>,
+    index: usize,
+    total: usize,
+    case: &'a CorpusCase,
+}
 
-    let next_step = Python::with_gil(|py| {
-        let py_step = parse_info
-            .getattr(py, "get_current_parse_step_info")
-            .map_err(|e| e.to_string())?
-            .call0(py)
-            .map_err(|e| e.to_string())?;
-        py_step.extract(py).map_err(|e| e.to_string())
-    });
+/// Parses every file matching `inputs` with a freshly generated parser and reports which
+/// ones hit syntax errors. A relative pattern is resolved against the grammar's directory.
+#[tauri::command]
+async fn run_corpus(
+    grammar: String,
+    inputs: String,
+    junit_path: Option<PathBuf>,
+    op_id: Option<String>,
+    app: AppHandle,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<CorpusReport> {
+    let event_op_id = op_id.clone();
+    worker
+        .run("run_corpus", op_id, move |_store| {
+            let files = corpus::expand_inputs(&inputs, std::path::Path::new(&grammar).parent())?;
+            let total = files.len();
+            let report = Python::with_gil(|py| {
+                corpus::run(py, &grammar, &inputs, &files, |index, case| {
+                    let _ = app.emit(
+                        "corpus/case",
+                        CorpusProgress {
+                            op_id: event_op_id.as_deref(),
+                            index,
+                            total,
+                            case,
+                        },
+                    );
+                })
+            })?;
+
+            if let Some(path) = junit_path {
+                std::fs::write(&path, corpus::junit_xml(&report))
+                    .map_err(|e| CommandError::io(&path, e))?;
+            }
+            Ok(report)
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "go_to_step").map_err(|e| e.to_string())?;
-        func.call1(py, (old_step_id,)).map_err(|e| e.to_string())
-    })?;
+/// Stores the complete parse tree of the session as the expected tree for its input
+#[tauri::command]
+async fn save_tree_snapshot(
+    id: usize,
+    snapshot_path: PathBuf,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<()> {
+    worker
+        .run("save_tree_snapshot", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+            let parse_info = session.parsed()?;
+
+            let tree = Python::with_gil(|py| snapshot::current_tree(py, parse_info))?;
+            let input = session.last_input.as_deref().unwrap_or_default();
+            snapshot::write(&snapshot_path, &session.grammar_path, input, tree)
+        })
+        .await
+}
 
-    next_step
+/// Structural diff between a stored snapshot and the complete parse tree of the session
+#[tauri::command]
+async fn compare_tree_snapshot(
+    id: usize,
+    snapshot_path: PathBuf,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<TreeChange>> {
+    worker
+        .run("compare_tree_snapshot", None, move |store| {
+            let expected = snapshot::read(&snapshot_path)?;
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+            let parse_info = session.parsed()?;
+
+            let tree = Python::with_gil(|py| snapshot::current_tree(py, parse_info))?;
+            Ok(snapshot::diff(&expected.tree, &tree))
+        })
+        .await
 }
 
 /// Gets the list of lexemes used in the expression. It also gets where the lexemes are located in the input string.
 #[tauri::command]
-fn get_token_list(id: usize, store: State<ParseInfoStore>) -> Result<Vec<TokenInfo>, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+async fn get_token_list(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<Vec<TokenInfo>> {
+    worker
+        .run("get_token_list", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::token_list(py, session))
+        })
+        .await
+}
 
-    Python::with_gil(|py| {
-        // Call the Python method that returns the list of token dicts
-        let py_tokens: Py<PyAny> = parse_info
-            .getattr(py, "get_token_list")
-            .map_err(|e| e.to_string())?
-            .call0(py)
-            .map_err(|e| e.to_string())?;
+/// Writes the lexemes of the input to `path` as CSV, TSV, JSON, JSON Lines or an ANTLR `.tokens` vocabulary
+#[tauri::command]
+async fn export_tokens(
+    id: usize,
+    format: TokenFormat,
+    path: PathBuf,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<()> {
+    worker
+        .run("export_tokens", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+            let parse_info = session.parsed()?;
+
+            let token_list = Python::with_gil(|py| debugger::token_list(py, parse_info))?;
+            files::write_atomically(&path, tokens::export(&token_list, format)?.as_bytes())
+        })
+        .await
+}
 
-        // Extract the Python list of dicts into a Vec<TokenInfo>
-        py_tokens.extract::<Vec<TokenInfo>>(py).map_err(|e| e.to_string())
-    })
+/// Parses a token stream saved by `export_tokens` (or written by hand) without running the lexer
+#[tauri::command]
+async fn parse_tokens(
+    id: usize,
+    format: TokenFormat,
+    path: PathBuf,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("parse_tokens", op_id, move |store| {
+            let content = std::fs::read_to_string(&path).map_err(|e| CommandError::io(&path, e))?;
+            let records = tokens::import(&content, format)?;
+
+            let handle = store.session(id)?;
+            let mut session = handle.lock()?;
+            Python::with_gil(|py| session.parse_tokens(py, &records))?;
+            Ok("Parsed successfully".to_string())
+        })
+        .await
 }
 
 /// Gets a JSON representation of the current (meaning partial) ParseTree from a ParseInformation instance
 #[tauri::command]
-fn get_json_parse_tree(id: usize, store: State<ParseInfoStore>) -> Result<serde_json::Value, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
-
-    Python::with_gil(|py| {
-        let dict_obj = parse_info
-            .getattr(py, "get_current_tree_dict")
-            .map_err(|e| e.to_string())?
-            .call0(py)
-            .map_err(|e| e.to_string())?;
+async fn get_json_parse_tree(
+    id: usize,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<serde_json::Value> {
+    worker
+        .run("get_json_parse_tree", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::parse_tree(py, session))
+        })
+        .await
+}
 
-        let dict_bound = dict_obj.bind(py);
-        depythonize::<serde_json::Value>(&dict_bound)
-            .map_err(|e| e.to_string())
-    })
+/// Writes the current parse tree to `path` as DOT, GraphML, S-expression or LaTeX
+#[tauri::command]
+async fn export_parse_tree(
+    id: usize,
+    format: TreeFormat,
+    path: PathBuf,
+    options: Option<TreeExportOptions>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<()> {
+    worker
+        .run("export_parse_tree", None, move |store| {
+            let handle = store.session(id)?;
+            let session = handle.lock()?;
+            let parse_info = session.parsed()?;
+
+            let tree = Python::with_gil(|py| debugger::parse_tree(py, parse_info))?;
+            let tree = tree_export::prepare(&TreeNode::from_json(&tree), options.unwrap_or_default())
+                .ok_or_else(|| CommandError::invalid_argument("The whole parse tree is an error node"))?;
+            files::write_atomically(&path, tree_export::render(&tree, format).as_bytes())
+        })
+        .await
 }
 
 /// Manipulates the current step in the ParseInformation instance's ParseTreeExplorer instance
 #[tauri::command]
-fn step_forwards(id: usize, step: usize, store: State<ParseInfoStore>) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
-
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "step_forward").map_err(|e| e.to_string())?;
-        
-        func.call1(py, (step,)).map_err(|e| e.to_string())?;
+async fn step_forwards(
+    id: usize,
+    step: usize,
+    op_id: Option<String>,
+    worker: State<'_, PythonWorker>,
+) -> CommandResult<String> {
+    worker
+        .run("step_forwards", op_id, move |store| {
+            commands::with_session(store, id, |py, session| commands::step_forwards(py, session, step))
+        })
+        .await
+}
 
-        Ok("Stepped successfully".to_string())
-    })
+#[tauri::command]
+async fn step_backwards(id: usize, worker: State<'_, PythonWorker>) -> CommandResult<String> {
+    worker
+        .run("step_backwards", None, move |store| {
+            commands::with_session(store, id, |py, session| commands::step_backwards(py, session))
+        })
+        .await
 }
 
+/// Interrupts a queued or running operation started with the given `op_id`.
+/// Returns false if no such operation is in progress.
 #[tauri::command]
-fn step_backwards(id: usize, store: State<ParseInfoStore>) -> Result<String, String> {
-    let nodes = store.nodes.lock().unwrap();
-    let parse_info = nodes.get(&id).ok_or("Invalid parse info id")?;
+async fn cancel_operation(op_id: String, app: AppHandle) -> CommandResult<bool> {
+    // Interrupting takes the GIL, which the operation may hold for a while
+    tauri::async_runtime::spawn_blocking(move || app.state::<PythonWorker>().cancel(&op_id))
+        .await
+        .map_err(anyhow::Error::from)?
+}
 
-    Python::with_gil(|py| {
-        let func = parse_info.getattr(py, "step_backwards").map_err(|e| e.to_string())?;
-        
-        func.call0(py).map_err(|e| e.to_string())?;
+/// Path of the log file Python tracebacks are written to
+#[tauri::command]
+fn get_error_log_path() -> CommandResult<Option<String>> {
+    Ok(traceback::log_file().map(|p| p.display().to_string()))
+}
 
-        Ok("Stepped successfully".to_string())
-    })
+/// Whether Python tracebacks should include the local variables of each frame
+#[tauri::command]
+fn set_traceback_locals(enabled: bool) -> CommandResult<()> {
+    traceback::set_capture_locals(enabled);
+    Ok(())
 }
 
 #[tauri::command]
-fn initialise_python_frontend_wrapper(app: tauri::AppHandle) -> Result<(), String> {
+fn initialise_python_frontend_wrapper(app: tauri::AppHandle) -> CommandResult<()> {
     ensure_python_async(app);
     Ok(())
 }
 
 #[tauri::command]
-fn repair_python(app: AppHandle) -> Result<(), String> {
-    delete_venv(&app).map_err(|e| e.to_string())
+fn repair_python(app: AppHandle) -> CommandResult<()> {
+    Ok(delete_venv(&app)?)
+}
+
+#[tauri::command]
+fn get_python_settings(app: AppHandle) -> CommandResult<PythonSettings> {
+    Ok(python_env::read_settings(&app)?)
+}
+
+/// Saves which provider makes the Python environment. Like `repair_python`,
+/// takes effect on the next start.
+#[tauri::command]
+fn set_python_settings(settings: PythonSettings, app: AppHandle) -> CommandResult<()> {
+    Ok(python_env::write_settings(&app, &settings)?)
+}
+
+/// Starts the local JSON-RPC API, or returns the running one.
+/// Without a port the OS picks a free one.
+#[tauri::command]
+fn start_api_server(port: Option<u16>, api: State<ApiServer>) -> CommandResult<ApiServerInfo> {
+    api.start(port)
+}
+
+/// Returns false if the API wasn't running
+#[tauri::command]
+fn stop_api_server(api: State<ApiServer>) -> CommandResult<bool> {
+    api.stop()
+}
+
+#[tauri::command]
+fn get_api_server(api: State<ApiServer>) -> CommandResult<Option<ApiServerInfo>> {
+    api.info()
+}
+
+/// Downloads and builds wheels of all Python dependencies into `dir`, for
+/// setting up machines without network
+#[tauri::command]
+async fn build_wheelhouse(dir: PathBuf, app: AppHandle) -> CommandResult<WheelhouseReport> {
+    let report = tauri::async_runtime::spawn_blocking(move || python_env::build_wheelhouse(&app, &dir))
+        .await
+        .map_err(anyhow::Error::from)??;
+    Ok(report)
+}
+
+/// Checks the Python environment, Java and git without changing anything;
+/// `report` is the text to paste into a bug ticket
+#[tauri::command]
+async fn run_diagnostics(app: AppHandle) -> CommandResult<DiagnosticsReport> {
+    let report = tauri::async_runtime::spawn_blocking(move || diagnostics::run(&app))
+        .await
+        .map_err(anyhow::Error::from)?;
+    Ok(report)
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
 //  main
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn main() {
+fn main() -> ExitCode {
+    // `paredros-app parse ...` and friends run without a window
+    if let Some(code) = cli::run_from_args() {
+        return code;
+    }
+
     Builder::default()
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(ParseInfoStore::default())
+        .setup(|app| {
+            if let Ok(dir) = app.path().app_data_dir() {
+                traceback::init_log_dir(&dir.join("logs"));
+            }
+            app.manage(PythonWorker::spawn(app.handle().clone()));
+            app.manage(GrammarWatcher::new(app.handle().clone()));
+            app.manage(ApiServer::new(app.handle().clone()));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             initialise_python_frontend_wrapper,
             get_parse_info,
+            close_parse_info,
+            list_parse_infos,
+            get_session_policy,
+            set_session_policy,
+            save_session,
+            load_session,
+            watch_grammar,
+            unwatch_grammar,
+            update_grammar_rule,
+            write_grammar_file,
             repair_python,
+            build_wheelhouse,
+            get_python_settings,
+            set_python_settings,
+            run_diagnostics,
+            cancel_operation,
+            get_error_log_path,
+            set_traceback_locals,
             generate_parser,
             parse_input,
             go_to_step,
             get_user_grammar,
             get_current_parse_step_info,
             get_next_parse_step_info,
+            get_parse_step_info,
+            get_parse_step_range,
+            get_full_trace,
+            stream_full_trace,
+            run_corpus,
+            save_tree_snapshot,
+            compare_tree_snapshot,
             get_token_list,
+            export_tokens,
+            parse_tokens,
             step_forwards,
             step_backwards,
             step_until_next_decision,
             step_back_until_previous_decision,
-            get_json_parse_tree
+            set_breakpoint,
+            remove_breakpoint,
+            list_breakpoints,
+            evaluate_watches,
+            continue_to_breakpoint,
+            reverse_continue,
+            start_api_server,
+            stop_api_server,
+            get_api_server,
+            get_json_parse_tree,
+            export_parse_tree
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
+    ExitCode::SUCCESS
 }
\ No newline at end of file
@@ -0,0 +1,133 @@
+// ==============================
+// src-tauri/src/project.rs
+// ==============================
+
+//! `.paredros` project files: everything needed to bring a debugging session
+//! back on another machine, stored as pretty-printed JSON.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::breakpoints::Breakpoints;
+use crate::debugger;
+use crate::error::{CommandError, CommandResult};
+use crate::files;
+use crate::session::ParseSession;
+
+/// Bumped whenever a field changes meaning; newer files are refused.
+const PROJECT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectFile {
+    pub version: u32,
+    /// Relative to the project file if the grammar lives next to or below it,
+    /// so a project folder can be handed on as a whole
+    pub grammar_path: String,
+    /// Text of the last `parse_input`; `None` if nothing was parsed
+    pub input: Option<String>,
+    pub step_id: Option<usize>,
+    #[serde(default)]
+    pub breakpoints: Breakpoints,
+    /// Opaque to the backend, stored as the frontend hands it over
+    #[serde(default)]
+    pub ui_state: serde_json::Value,
+    /// RFC 3339 timestamp
+    pub saved_at: String,
+}
+
+/// What `load_session` hands back to the frontend
+#[derive(Debug, Serialize)]
+pub struct LoadedProject {
+    pub id: usize,
+    /// `grammar_path` resolved against the project file's directory
+    pub project: ProjectFile,
+}
+
+/// Snapshot a session for saving it to `project_path`.
+pub fn capture(
+    py: Python<'_>,
+    session: &ParseSession,
+    project_path: &Path,
+    ui_state: serde_json::Value,
+) -> CommandResult<ProjectFile> {
+    let step_id = match session.parsed() {
+        Ok(parse_info) => Some(debugger::current_step(py, parse_info)?.index()?),
+        Err(_) => None,
+    };
+    Ok(ProjectFile {
+        version: PROJECT_VERSION,
+        grammar_path: relative_to_project(project_path, Path::new(&session.grammar_path)),
+        input: session.last_input.clone(),
+        step_id,
+        breakpoints: session.breakpoints.clone(),
+        ui_state,
+        saved_at: chrono::Local::now().to_rfc3339(),
+    })
+}
+
+/// Replay a project against a fresh `ParseInformation`: generate the parser,
+/// parse the saved input and go back to the saved step.
+/// Expects `project.grammar_path` to be resolved already.
+pub fn restore(py: Python<'_>, project: &ProjectFile) -> CommandResult<ParseSession> {
+    let mut session = ParseSession::open(py, project.grammar_path.clone())?;
+    session.breakpoints = project.breakpoints.clone();
+    if let Some(input) = &project.input {
+        session.generate_parser(py)?;
+        session.parse(py, input.clone())?;
+        if let Some(step_id) = project.step_id {
+            debugger::go_to_step(py, &session.parse_info, step_id)?;
+        }
+    }
+    Ok(session)
+}
+
+pub fn write(path: &Path, project: &ProjectFile) -> CommandResult<()> {
+    let json = serde_json::to_string_pretty(project).map_err(|e| invalid(path, e))?;
+    files::write_atomically(path, json.as_bytes())
+}
+
+/// Read a project file and resolve its grammar path.
+pub fn read(path: &Path) -> CommandResult<ProjectFile> {
+    let json = fs::read_to_string(path).map_err(|e| CommandError::io(path, e))?;
+    let mut project: ProjectFile = serde_json::from_str(&json).map_err(|e| invalid(path, e))?;
+    if project.version > PROJECT_VERSION {
+        return Err(invalid(
+            path,
+            format!(
+                "format version {} is newer than the supported version {PROJECT_VERSION}",
+                project.version
+            ),
+        ));
+    }
+    project.grammar_path = resolve_from_project(path, &project.grammar_path)
+        .display()
+        .to_string();
+    Ok(project)
+}
+
+fn relative_to_project(project_path: &Path, grammar_path: &Path) -> String {
+    project_path
+        .parent()
+        .and_then(|dir| grammar_path.strip_prefix(dir).ok())
+        .unwrap_or(grammar_path)
+        .display()
+        .to_string()
+}
+
+fn resolve_from_project(project_path: &Path, grammar_path: &str) -> PathBuf {
+    let grammar_path = Path::new(grammar_path);
+    match project_path.parent() {
+        Some(dir) if grammar_path.is_relative() => dir.join(grammar_path),
+        _ => grammar_path.to_path_buf(),
+    }
+}
+
+fn invalid(path: &Path, err: impl ToString) -> CommandError {
+    CommandError::InvalidProjectFile {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    }
+}
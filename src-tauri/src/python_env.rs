@@ -5,8 +5,10 @@
 use std::{
     fs,
     path::{Path, PathBuf},
-    process::Command,
-    io::Write
+    process::{Command, Output, Stdio},
+    io::{BufRead, BufReader, Read, Write},
+    sync::mpsc,
+    thread,
 };
 
 use pyo3::{Python, PyResult};
@@ -21,19 +23,254 @@ use tauri_plugin_dialog::{
     MessageDialogKind,
 };
 
-#[derive(Debug, Serialize, Clone)]
+/// Everything the bootstrap needs from its surroundings, so the same setup
+/// runs inside the Tauri app and from the headless CLI.
+pub trait SetupHost {
+    /// Directory of the managed venv
+    fn venv_dir(&self) -> Result<PathBuf>;
+    /// A path inside the bundled resources, if there are any
+    fn resource_path(&self, rel: &str) -> Option<PathBuf>;
+    fn progress(&self, progress: PySetupProgress);
+    /// A line printed by venv, pip or antlr4 while it runs
+    fn log(&self, log: PySetupLog);
+    /// Tell the user about a failure that needs their attention
+    fn show_dialog(&self, title: &str, message: &str);
+    /// A wheelhouse the user asked for explicitly; see `find_wheelhouse`
+    fn wheelhouse(&self) -> Option<PathBuf> {
+        None
+    }
+    /// A provider that overrides the one in the settings
+    fn provider(&self) -> Option<EnvProviderConfig> {
+        None
+    }
+}
+
+impl SetupHost for AppHandle {
+    fn venv_dir(&self) -> Result<PathBuf> {
+        self.path()
+            .resolve("pyenv", BaseDirectory::AppConfig)
+            .context("cannot resolve app config dir")
+    }
+
+    fn resource_path(&self, rel: &str) -> Option<PathBuf> {
+        self.path().resolve(rel, BaseDirectory::Resource).ok()
+    }
+
+    fn progress(&self, progress: PySetupProgress) {
+        let _ = self.emit("py/setup-progress", progress);
+    }
+
+    fn log(&self, log: PySetupLog) {
+        let _ = self.emit("py/setup-log", log);
+    }
+
+    fn show_dialog(&self, title: &str, message: &str) {
+        let handle = self.clone();
+        let msg = message.to_string();
+        let title = title.to_string();
+
+        // `blocking_show` must not run on the main thread, so spawn one:
+        std::thread::spawn(move || {
+            handle
+                .dialog()
+                .message(msg)
+                .title(title)
+                .kind(MessageDialogKind::Error)
+                .buttons(MessageDialogButtons::Ok)
+                .blocking_show();
+        });
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub enum PySetupProgress {
     Checking,
     CreatingVenv,
     InstallingPackages,
+    /// What pip is busy with; `percent` of the current download, if pip says
+    InstallingPackage {
+        package: String,
+        percent: Option<u8>,
+    },
     Done,
     Error(String),
 }
 
+/// Payload of the `py/setup-log` event
+#[derive(Debug, Serialize, Clone)]
+pub struct PySetupLog {
+    /// `venv`, `pip`, `uv` or `antlr4`
+    pub source: &'static str,
+    /// `stdout` or `stderr`
+    pub stream: &'static str,
+    pub line: String,
+    pub timestamp: String,
+}
+
+/// What `build_wheelhouse` put into the directory
+#[derive(Debug, Clone, Serialize)]
+pub struct WheelhouseReport {
+    pub dir: String,
+    /// File names of the wheels
+    pub wheels: Vec<String>,
+    /// File name of the ANTLR tool jar, if one was found to copy
+    pub antlr_jar: Option<String>,
+}
+
+/// Set to a directory of wheels to install from it instead of PyPI and GitHub
+pub const WHEELHOUSE_ENV: &str = "PAREDROS_WHEELHOUSE";
+
+/// Read by antlr4-tools; with it set, it doesn't ask Maven Central for the latest version
+const ANTLR_VERSION_ENV: &str = "ANTLR4_TOOLS_ANTLR_VERSION";
+
+const REQUIREMENTS: &[u8] = include_bytes!("../../requirements.txt");
+
+/// Which `EnvProvider` makes the environment
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum EnvProviderConfig {
+    /// `python -m venv` in the app's config dir
+    #[default]
+    Venv,
+    /// `uv venv` and `uv pip` in the app's config dir
+    Uv,
+    /// An existing conda environment
+    Conda { prefix: PathBuf },
+    /// The environment of this interpreter, e.g. one of a project venv.
+    /// An interpreter outside of a venv is only installed into with `allow_system`.
+    Interpreter {
+        python: PathBuf,
+        #[serde(default)]
+        allow_system: bool,
+    },
+}
+
+/// `python-env.json` next to the venv
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PythonSettings {
+    #[serde(default)]
+    pub provider: EnvProviderConfig,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
 struct VenvState {
     python_version: String,
     req_hash: String,
+    /// `EnvProvider::id`; switching providers rebuilds the environment
+    #[serde(default = "default_provider_id")]
+    provider: String,
+    /// The ANTLR version `install_antlr_jar` pinned; kept across rebuilds
+    /// that don't change it, and never a reason to rebuild by itself
+    #[serde(default)]
+    antlr_version: Option<String>,
+}
+
+/// Written before there were providers
+fn default_provider_id() -> String {
+    VenvProvider.id()
+}
+
+/// Oldest and newest minor versions of Python 3 the app works with
+const MIN_PYTHON_MINOR: u32 = 10;
+const MAX_PYTHON_MINOR: u32 = 13;
+
+/// The Python *major.minor* pyo3 was built against, e.g. `3.12`. libpython of
+/// exactly this version is loaded into the app, so the environment must match.
+pub(crate) const LINKED_PYTHON: &str = env!("PAREDROS_LINKED_PYTHON");
+
+/// An interpreter found on PATH; `version` is `None` if it didn't run
+#[derive(Debug, Clone, Serialize)]
+pub struct PythonCandidate {
+    pub path: PathBuf,
+    pub version: Option<String>,
+}
+
+/// The app was built against another Python than `.python-version` names or
+/// than it supports, or none of the interpreters found is that Python
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("{}", incompatible_message(wanted, linked, min, max, found))]
+pub struct IncompatiblePython {
+    /// From `.python-version`, e.g. `3.12`
+    pub wanted: String,
+    /// `LINKED_PYTHON`
+    pub linked: String,
+    /// E.g. `3.10` to `3.13`
+    pub min: String,
+    pub max: String,
+    pub found: Vec<PythonCandidate>,
+}
+
+impl IncompatiblePython {
+    fn new(found: Vec<PythonCandidate>) -> Self {
+        Self {
+            wanted: desired_py_version(),
+            linked: LINKED_PYTHON.to_string(),
+            min: format!("3.{MIN_PYTHON_MINOR}"),
+            max: format!("3.{MAX_PYTHON_MINOR}"),
+            found,
+        }
+    }
+}
+
+pub(crate) fn incompatible_message(
+    wanted: &str,
+    linked: &str,
+    min: &str,
+    max: &str,
+    found: &[PythonCandidate],
+) -> String {
+    if parse_py_version(wanted) != parse_py_version(linked) {
+        return format!(
+            "This build of Paredros is linked against Python {linked}, but .python-version asks for {wanted}. \
+             Rebuild it against Python {wanted}."
+        );
+    }
+    if !is_supported(linked) {
+        return format!(
+            "This build of Paredros is linked against Python {linked}, but only Python {min} to {max} is supported."
+        );
+    }
+    let found = if found.is_empty() {
+        "none on PATH".to_string()
+    } else {
+        found
+            .iter()
+            .map(|c| format!("{} ({})", c.path.display(), c.version.as_deref().unwrap_or("did not run")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "No compatible Python found. This build of Paredros needs Python {linked}, which it was built against. \
+         Found: {found}"
+    )
+}
+
+/// The build itself is consistent: pyo3 was linked against the Python of
+/// `.python-version`, and that one is in the supported range.
+pub(crate) fn check_linked_python() -> Result<(), IncompatiblePython> {
+    if is_compatible(&desired_py_version()) {
+        Ok(())
+    } else {
+        Err(IncompatiblePython::new(Vec::new()))
+    }
+}
+
+/// `3.12` → `(3, 12)`
+fn parse_py_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Whether `version`, e.g. `3.12.4`, is in the supported range
+pub(crate) fn is_supported(version: &str) -> bool {
+    matches!(parse_py_version(version), Some((3, minor)) if (MIN_PYTHON_MINOR..=MAX_PYTHON_MINOR).contains(&minor))
+}
+
+/// Whether an environment of `version` can be loaded: supported, and `LINKED_PYTHON`
+pub(crate) fn is_compatible(version: &str) -> bool {
+    is_supported(version) && parse_py_version(version) == parse_py_version(LINKED_PYTHON)
 }
 
 /// Read the desired Python *major.minor* from the checked‑in `.python-version`
@@ -48,25 +285,9 @@ fn desired_py_version() -> String {
     }
 }
 
-fn show_error_dialog(app: &AppHandle, title: &str, message: &str) {
-    let handle = app.clone();
-    let msg = message.to_string();
-    let title = title.to_string();
-
-    std::thread::spawn(move || {
-        handle
-            .dialog()
-            .message(msg)
-            .title(title)
-            .kind(MessageDialogKind::Error)
-            .buttons(MessageDialogButtons::Ok)
-            .blocking_show();
-    });
-
-    let _ = app.emit(
-        "py/setup-progress",
-        PySetupProgress::Error(message.to_string()),
-    );
+fn show_error_dialog(host: &impl SetupHost, title: &str, message: &str) {
+    host.show_dialog(title, message);
+    host.progress(PySetupProgress::Error(message.to_string()));
 }
 
 pub fn ensure_python_async(app: AppHandle) {
@@ -77,12 +298,9 @@ pub fn ensure_python_async(app: AppHandle) {
     });
 }
 
-fn add_embedded_python_paths(app: &tauri::AppHandle) {
+fn add_embedded_python_paths(host: &impl SetupHost) {
     // Resolve your embedded Python root (from bundled resources)
-    let py_root = app
-        .path()
-        .resolve("py/windows", tauri::path::BaseDirectory::Resource)
-        .ok();
+    let py_root = host.resource_path("py/windows");
 
     if let Some(py_root) = py_root {
         let dlls = py_root.join("DLLs");
@@ -122,13 +340,16 @@ fn add_embedded_python_paths(app: &tauri::AppHandle) {
 }
 
 /// Synchronous bootstrap. Safe to call multiple times.
-pub fn ensure_python_sync(app: &AppHandle) -> Result<()> {
-    let _ = app.emit("py/setup-progress", PySetupProgress::Checking);
+pub fn ensure_python_sync(host: &impl SetupHost) -> Result<()> {
+    host.progress(PySetupProgress::Checking);
 
-    match bootstrap_python_env(app) {
+    match bootstrap_python_env(host) {
         Ok(venv_dir) => {
             configure_env_for_venv(&venv_dir)?;
-            add_embedded_python_paths(app);
+            if let Some(state) = read_state(&host.venv_dir()?) {
+                pin_antlr_version(&state);
+            }
+            add_embedded_python_paths(host);
             pyo3::prepare_freethreaded_python();
 
             // ================== START: ADD THIS FIX ==================
@@ -149,125 +370,255 @@ pub fn ensure_python_sync(app: &AppHandle) -> Result<()> {
             })?;
             // =================== END: ADD THIS FIX ===================
 
-            let _ = app.emit("py/setup-progress", PySetupProgress::Done);
+            host.progress(PySetupProgress::Done);
             Ok(())
         }
 
         // ---------- v2 dialog on error ----------
+        Err(e) if e.is::<IncompatiblePython>() => {
+            // bootstrap has said what was found already
+            host.progress(PySetupProgress::Error(e.to_string()));
+            Err(e)
+        }
         Err(e) => {
-            let handle = app.clone();
-            let desired_python_version = desired_py_version();
-
-            // `blocking_show` must not run on the main thread, so spawn one:
-            std::thread::spawn(move || {
-                handle
-                    .dialog()
-                    .message(
-                        format!("Something went wrong during the setup process.\n\
-                         Please try installing Python {desired_python_version} and re-install the application."),
-                    )
-                    .title("Python not detected")
-                    .kind(MessageDialogKind::Error)
-                    .buttons(MessageDialogButtons::Ok)
-                    .blocking_show();
-            });
+            host.show_dialog(
+                "Python not detected",
+                &format!("Something went wrong during the setup process.\n\
+                 Please try installing Python {LINKED_PYTHON} and re-install the application."),
+            );
 
-            let _ = app.emit("py/setup-progress", PySetupProgress::Error(e.to_string()));
+            host.progress(PySetupProgress::Error(e.to_string()));
             Err(e)
         }
     }
 }
 
+fn settings_path(host: &impl SetupHost) -> Result<PathBuf> {
+    let venv_dir = host.venv_dir()?;
+    let dir = venv_dir.parent().context("the venv dir has no parent")?;
+    Ok(dir.join("python-env.json"))
+}
+
+pub fn read_settings(host: &impl SetupHost) -> Result<PythonSettings> {
+    let path = settings_path(host)?;
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("{} is not valid", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PythonSettings::default()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Takes effect the next time the environment is set up, e.g. after `delete_venv`.
+pub fn write_settings(host: &impl SetupHost, settings: &PythonSettings) -> Result<()> {
+    let path = settings_path(host)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_vec_pretty(settings)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
 /// Delete the venv so it gets rebuilt next run.
-pub fn delete_venv(app: &AppHandle) -> Result<()> {
-    let venv_dir = app
-        .path()
-        .resolve("pyenv", BaseDirectory::AppConfig)
-        .context("cannot resolve app config dir")?;
+pub fn delete_venv(host: &impl SetupHost) -> Result<()> {
+    let venv_dir = host.venv_dir()?;
     if venv_dir.exists() {
         fs::remove_dir_all(&venv_dir)?;
     }
     Ok(())
 }
 
+/// What the setup would use, found without changing anything
+pub(crate) struct EnvInspection {
+    /// `EnvProvider::id`
+    pub provider: String,
+    /// The interpreter environments are made from, and its version
+    pub base_python: Result<(PathBuf, String)>,
+    pub env_dir: Result<PathBuf>,
+    /// The environment's interpreter, if it exists
+    pub env_python: Option<PathBuf>,
+    /// `None` if there is no `VenvState` yet, otherwise what doesn't match it
+    pub state_differences: Option<Vec<String>>,
+    pub antlr4: Option<PathBuf>,
+}
+
+pub(crate) fn inspect_env(host: &impl SetupHost) -> Result<EnvInspection> {
+    match provider_config(host)? {
+        EnvProviderConfig::Venv => inspect_with(host, &VenvProvider),
+        EnvProviderConfig::Uv => inspect_with(host, &UvProvider),
+        EnvProviderConfig::Conda { prefix } => inspect_with(host, &CondaProvider { prefix }),
+        EnvProviderConfig::Interpreter { python, allow_system } => {
+            inspect_with(host, &InterpreterProvider { python, allow_system })
+        }
+    }
+}
+
+fn inspect_with(host: &impl SetupHost, provider: &impl EnvProvider) -> Result<EnvInspection> {
+    let venv_dir = host.venv_dir()?;
+    let base_python = provider.base_python(host).and_then(|python| {
+        let version = get_python_version(&python)?;
+        Ok((python, version))
+    });
+    let env_dir = provider.env_dir(&venv_dir);
+    let env_python = env_dir.as_ref().ok().map(|dir| provider.python(dir)).filter(|p| p.exists());
+    let antlr4 = env_dir.as_ref().ok().and_then(|dir| antlr4_path(dir));
+
+    let state_differences = read_state(&venv_dir).map(|current| {
+        let mut differences = Vec::new();
+        if let Ok((_, version)) = &base_python {
+            if &current.python_version != version {
+                differences.push(format!("made with Python {}, now {version}", current.python_version));
+            }
+        }
+        if current.req_hash != requirements_hash() {
+            differences.push("the requirements changed".to_string());
+        }
+        if current.provider != provider.id() {
+            differences.push(format!("made by {}, now {}", current.provider, provider.id()));
+        }
+        differences
+    });
+
+    Ok(EnvInspection {
+        provider: provider.id(),
+        base_python,
+        env_dir,
+        env_python,
+        state_differences,
+        antlr4,
+    })
+}
+
 // ---------------- internal helpers ----------------
 
-fn bootstrap_python_env(app: &AppHandle) -> Result<PathBuf> {
-    let emit_progress = |progress| {
-        let _ = app.emit("py/setup-progress", progress);
-    };
+/// The host's provider, otherwise the one in the settings
+fn provider_config(host: &impl SetupHost) -> Result<EnvProviderConfig> {
+    match host.provider() {
+        Some(config) => Ok(config),
+        None => Ok(read_settings(host)?.provider),
+    }
+}
+
+fn requirements_hash() -> String {
+    let mut h = Sha256::new();
+    h.update(REQUIREMENTS);
+    format!("{:x}", h.finalize())
+}
+
+fn read_state(venv_dir: &Path) -> Option<VenvState> {
+    fs::read(venv_dir.join(".venv_state.json"))
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+}
+
+fn bootstrap_python_env(host: &impl SetupHost) -> Result<PathBuf> {
+    match provider_config(host)? {
+        EnvProviderConfig::Venv => bootstrap_with(host, &VenvProvider),
+        EnvProviderConfig::Uv => bootstrap_with(host, &UvProvider),
+        EnvProviderConfig::Conda { prefix } => bootstrap_with(host, &CondaProvider { prefix }),
+        EnvProviderConfig::Interpreter { python, allow_system } => {
+            bootstrap_with(host, &InterpreterProvider { python, allow_system })
+        }
+    }
+}
+
+/// The venv dir always holds the requirements and the `VenvState`; with
+/// the default providers it is the environment as well.
+fn bootstrap_with(host: &impl SetupHost, provider: &impl EnvProvider) -> Result<PathBuf> {
+    let emit_progress = |progress| host.progress(progress);
 
     emit_progress(PySetupProgress::Checking);
 
-    let venv_dir = app
-        .path()
-        .resolve("pyenv", BaseDirectory::AppConfig)
-        .context("cannot resolve app config dir")?;
+    let venv_dir = host.venv_dir()?;
 
-    println!("Using venv dir: {}", venv_dir.display());
+    eprintln!("Using venv dir: {}", venv_dir.display());
 
     fs::create_dir_all(&venv_dir)
         .with_context(|| format!("failed to create venv directory at {}", venv_dir.display()))?;
 
-    let req_bytes: &[u8] = include_bytes!("../../requirements.txt");
     let req_path = venv_dir.join("requirements.txt");
-    fs::write(&req_path, req_bytes).context("failed to write requirements.txt")?;
+    fs::write(&req_path, REQUIREMENTS).context("failed to write requirements.txt")?;
 
-    let req_hash = {
-        let mut h = Sha256::new();
-        h.update(req_bytes);
-        format!("{:x}", h.finalize())
-    };
+    let req_hash = requirements_hash();
 
     let state_path = venv_dir.join(".venv_state.json");
-    let current: VenvState = fs::read(&state_path)
-        .ok()
-        .and_then(|b| serde_json::from_slice(&b).ok())
-        .unwrap_or_default();
+    let current = read_state(&venv_dir).unwrap_or_default();
 
-    let base_python = find_base_python(app).map_err(|e| {
-        show_error_dialog(app, "Python Interpreter Not Found", &e.to_string());
+    if let Err(e) = check_linked_python() {
+        show_error_dialog(host, "Incompatible Python Version", &e.to_string());
+        return Err(e.into());
+    }
+
+    let base_python = provider.base_python(host).map_err(|e| {
+        show_error_dialog(host, "Python Interpreter Not Found", &e.to_string());
         e
     })?;
 
     let py_version = get_python_version(&base_python).map_err(|e| {
-        show_error_dialog(app, "Failed to Get Python Version", &e.to_string());
+        show_error_dialog(host, "Failed to Get Python Version", &e.to_string());
         e
     })?;
+    // Catches the interpreters the user picked, and the bundled one
+    if !is_compatible(&py_version) {
+        let e = IncompatiblePython::new(vec![PythonCandidate {
+            path: base_python.clone(),
+            version: Some(py_version.clone()),
+        }]);
+        show_error_dialog(host, "Incompatible Python Version", &e.to_string());
+        return Err(e.into());
+    }
 
-    let desired = VenvState {
+    let mut desired = VenvState {
         python_version: py_version.clone(),
         req_hash: req_hash.clone(),
+        provider: provider.id(),
+        antlr_version: current.antlr_version.clone(),
     };
 
-    let venv_python = venv_python_path(&venv_dir);
+    let env_dir = provider.env_dir(&venv_dir)?;
+    let venv_python = provider.python(&env_dir);
 
     if !venv_python.exists() || current != desired {
+        // Preferred whenever there is one, so air-gapped machines never touch the network
+        let wheelhouse = find_wheelhouse(host);
+
         emit_progress(PySetupProgress::CreatingVenv);
 
-        create_venv(&base_python, &venv_dir).map_err(|e| {
-            show_error_dialog(app, "Virtual Environment Creation Failed", &e.to_string());
+        provider.create(host, &base_python, &env_dir, wheelhouse.is_none()).map_err(|e| {
+            show_error_dialog(host, "Virtual Environment Creation Failed", &e.to_string());
             e
         })?;
 
         emit_progress(PySetupProgress::InstallingPackages);
 
-        let git = ensure_git_on_path(app);
-        if git.is_err() {
-            show_error_dialog(app, "Git Not Found", "Please install Git to continue.");
-            return Err(git.unwrap_err());
-        }
-
-        // DEBUG
-        println!("Path variable looks like this: {}", std::env::var("PATH").unwrap_or_default());
+        let install_req_path = match &wheelhouse {
+            Some(wheelhouse) => {
+                eprintln!("Installing offline from {}", wheelhouse.display());
+                let offline_req_path = venv_dir.join("requirements-offline.txt");
+                let requirements = offline_requirements(&String::from_utf8_lossy(REQUIREMENTS));
+                fs::write(&offline_req_path, requirements)
+                    .context("failed to write requirements-offline.txt")?;
+                desired.antlr_version = install_antlr_jar(wheelhouse)?;
+                offline_req_path
+            }
+            None => {
+                let git = ensure_git_on_path(host);
+                if git.is_err() {
+                    show_error_dialog(host, "Git Not Found", "Please install Git to continue.");
+                    return Err(git.unwrap_err());
+                }
+                desired.antlr_version = None;
+                req_path
+            }
+        };
 
-        pip_install_requirements(&venv_python, &req_path).map_err(|e| {
-            show_error_dialog(app, "Dependency Installation Failed", &e.to_string());
+        provider.install(host, &venv_python, &install_req_path, wheelhouse.as_deref()).map_err(|e| {
+            show_error_dialog(host, "Dependency Installation Failed", &e.to_string());
             e
         })?;
 
-        run_antlr4(app, &venv_dir).map_err(|e| {
-            show_error_dialog(app, "ANTLR Setup failed", &e.to_string());
+        pin_antlr_version(&desired);
+        run_antlr4(host, &env_dir).map_err(|e| {
+            show_error_dialog(host, "ANTLR Setup failed", &e.to_string());
             e
         })?;
 
@@ -276,20 +627,281 @@ fn bootstrap_python_env(app: &AppHandle) -> Result<PathBuf> {
             .context("failed to persist venv state")?;
     }
 
-    Ok(venv_dir)
+    Ok(env_dir)
+}
+
+/// One way of providing the environment the Python side runs in
+trait EnvProvider {
+    /// Recorded in the `VenvState`
+    fn id(&self) -> String;
+    /// The interpreter whose version is recorded, and which new environments are made from
+    fn base_python(&self, host: &impl SetupHost) -> Result<PathBuf>;
+    /// Where the environment lives, given the app's venv dir
+    fn env_dir(&self, venv_dir: &Path) -> Result<PathBuf>;
+    fn python(&self, env_dir: &Path) -> PathBuf {
+        venv_python_path(env_dir)
+    }
+    /// Set up the environment; an existing one is only checked
+    fn create(&self, host: &impl SetupHost, base_python: &Path, env_dir: &Path, online: bool) -> Result<()>;
+    fn install(&self, host: &impl SetupHost, python: &Path, req_path: &Path, wheelhouse: Option<&Path>) -> Result<()> {
+        pip_install_requirements(host, python, req_path, wheelhouse)
+    }
 }
 
-fn find_base_python(app: &AppHandle) -> Result<PathBuf> {
-    if let Some(p) = find_embedded_python(app) {
+struct VenvProvider;
+
+impl EnvProvider for VenvProvider {
+    fn id(&self) -> String {
+        "venv".to_string()
+    }
+
+    fn base_python(&self, host: &impl SetupHost) -> Result<PathBuf> {
+        find_base_python(host)
+    }
+
+    fn env_dir(&self, venv_dir: &Path) -> Result<PathBuf> {
+        Ok(venv_dir.to_path_buf())
+    }
+
+    fn create(&self, host: &impl SetupHost, base_python: &Path, env_dir: &Path, online: bool) -> Result<()> {
+        create_venv(host, base_python, env_dir, online)
+    }
+}
+
+struct UvProvider;
+
+impl UvProvider {
+    fn uv() -> Result<PathBuf> {
+        which::which("uv").map_err(|_| anyhow!("uv was selected, but is not on PATH"))
+    }
+}
+
+impl EnvProvider for UvProvider {
+    fn id(&self) -> String {
+        "uv".to_string()
+    }
+
+    fn base_python(&self, host: &impl SetupHost) -> Result<PathBuf> {
+        find_base_python(host)
+    }
+
+    fn env_dir(&self, venv_dir: &Path) -> Result<PathBuf> {
+        Ok(venv_dir.to_path_buf())
+    }
+
+    fn create(&self, host: &impl SetupHost, base_python: &Path, env_dir: &Path, online: bool) -> Result<()> {
+        let mut cmd = Command::new(Self::uv()?);
+        // The venv dir also holds the requirements and the state, keep them
+        cmd.args(["venv", "--allow-existing", "--python"]).arg(base_python).arg(env_dir);
+        if !online {
+            cmd.arg("--offline");
+        }
+        let output = run_logged(host, "uv", &mut cmd, None, |_| {}).context("failed to spawn uv venv")?;
+        if !output.status.success() {
+            return Err(anyhow!("uv venv failed:\n{}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+
+    fn install(&self, host: &impl SetupHost, python: &Path, req_path: &Path, wheelhouse: Option<&Path>) -> Result<()> {
+        let mut cmd = Command::new(Self::uv()?);
+        cmd.args(["pip", "install", "--python"]).arg(python);
+        if let Some(wheelhouse) = wheelhouse {
+            cmd.args(["--offline", "--no-index", "--find-links"]).arg(wheelhouse);
+        }
+        cmd.arg("-r").arg(req_path);
+
+        let mut pip = PipProgress::default();
+        let output = run_logged(host, "uv", &mut cmd, None, |line| {
+            if let Some(progress) = pip.update(line) {
+                host.progress(progress);
+            }
+        })
+        .context("failed to spawn uv pip install")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "uv pip install -r requirements.txt failed:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// An environment made by someone else; the requirements are installed
+/// into it, but it is never created or deleted
+fn check_existing_env(python: &Path) -> Result<()> {
+    if python.exists() {
+        Ok(())
+    } else {
+        Err(anyhow!("There is no Python interpreter at {}", python.display()))
+    }
+}
+
+struct CondaProvider {
+    prefix: PathBuf,
+}
+
+impl EnvProvider for CondaProvider {
+    fn id(&self) -> String {
+        format!("conda:{}", self.prefix.display())
+    }
+
+    fn base_python(&self, _host: &impl SetupHost) -> Result<PathBuf> {
+        Ok(self.python(&self.prefix))
+    }
+
+    fn env_dir(&self, _venv_dir: &Path) -> Result<PathBuf> {
+        Ok(self.prefix.clone())
+    }
+
+    fn python(&self, env_dir: &Path) -> PathBuf {
+        // Unlike in a venv, not in Scripts on Windows
+        if cfg!(windows) {
+            env_dir.join("python.exe")
+        } else {
+            env_dir.join("bin").join("python")
+        }
+    }
+
+    fn create(&self, _host: &impl SetupHost, base_python: &Path, _env_dir: &Path, _online: bool) -> Result<()> {
+        check_existing_env(base_python)
+    }
+}
+
+struct InterpreterProvider {
+    python: PathBuf,
+    allow_system: bool,
+}
+
+impl InterpreterProvider {
+    /// Runs `code` with the interpreter; its stdout
+    fn eval(&self, code: &str) -> Result<String> {
+        let out = Command::new(&self.python)
+            .args(["-c", code])
+            .output()
+            .with_context(|| format!("could not run {}", self.python.display()))?;
+        if !out.status.success() {
+            return Err(anyhow!(
+                "{} failed: {}",
+                self.python.display(),
+                String::from_utf8_lossy(&out.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    }
+}
+
+impl EnvProvider for InterpreterProvider {
+    fn id(&self) -> String {
+        format!("interpreter:{}", self.python.display())
+    }
+
+    fn base_python(&self, _host: &impl SetupHost) -> Result<PathBuf> {
+        check_existing_env(&self.python)?;
+        Ok(self.python.clone())
+    }
+
+    /// `sys.prefix` of the interpreter
+    fn env_dir(&self, _venv_dir: &Path) -> Result<PathBuf> {
+        self.eval("import sys; print(sys.prefix)").map(PathBuf::from)
+    }
+
+    fn python(&self, _env_dir: &Path) -> PathBuf {
+        self.python.clone()
+    }
+
+    fn create(&self, _host: &impl SetupHost, base_python: &Path, _env_dir: &Path, _online: bool) -> Result<()> {
+        check_existing_env(base_python)
+    }
+
+    /// Refuses a Python outside of a venv unless `allow_system`, since
+    /// everything else using that Python would get the packages too
+    fn install(&self, host: &impl SetupHost, python: &Path, req_path: &Path, wheelhouse: Option<&Path>) -> Result<()> {
+        if !self.allow_system && self.eval("import sys; print(sys.prefix != sys.base_prefix)")? != "True" {
+            return Err(anyhow!(
+                "{} is not in a virtual environment. Choose a venv's interpreter, or allow installing into \
+                 this Python explicitly (--allow-system-python on the command line)",
+                self.python.display()
+            ));
+        }
+        pip_install_requirements(host, python, req_path, wheelhouse)
+    }
+}
+
+fn find_base_python(host: &impl SetupHost) -> Result<PathBuf> {
+    if let Some(p) = find_embedded_python(host) {
         return Ok(p);
     }
-    which::which("python3")
-        .or_else(|_| which::which("python"))
-        .map_err(|_| anyhow!("No Python interpreter found (embedded/system)."))
+    let candidates = find_pythons_on_path();
+    choose_python(LINKED_PYTHON, &candidates).ok_or_else(|| IncompatiblePython::new(candidates).into())
+}
+
+/// `python`, `python3` and `python3.X` in every PATH dir, in PATH order,
+/// each with its version
+fn find_pythons_on_path() -> Vec<PythonCandidate> {
+    let mut seen = Vec::new();
+    let mut candidates = Vec::new();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| is_python_name(name))
+            .collect();
+        names.sort();
+        for name in names {
+            let path = dir.join(name);
+            // python3 is usually a link to one of the python3.X
+            let real = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if seen.contains(&real) || !real.is_file() {
+                continue;
+            }
+            seen.push(real);
+            let version = get_python_version(&path).ok().filter(|v| parse_py_version(v).is_some());
+            candidates.push(PythonCandidate { path, version });
+        }
+    }
+    candidates
+}
+
+fn is_python_name(name: &str) -> bool {
+    let name = if cfg!(windows) {
+        match name.strip_suffix(".exe") {
+            Some(name) => name,
+            None => return false,
+        }
+    } else {
+        name
+    };
+    match name.strip_prefix("python3") {
+        Some("") => true,
+        Some(minor) => minor.strip_prefix('.').is_some_and(|m| !m.is_empty() && m.bytes().all(|b| b.is_ascii_digit())),
+        None => name == "python",
+    }
+}
+
+/// The best interpreter of the `wanted` version, which is the only one that
+/// can be loaded: one named for its version (`python3.12`) over a `python3`
+/// or `python` that may point elsewhere after the next update, then the
+/// first on PATH.
+fn choose_python(wanted: &str, candidates: &[PythonCandidate]) -> Option<PathBuf> {
+    // Not `file_stem`, which takes `.12` for an extension
+    let versioned = |c: &PythonCandidate| {
+        let name = c.path.file_name().unwrap_or_default().to_string_lossy();
+        name.starts_with("python3.")
+    };
+    let mut matching = candidates.iter().filter(|c| {
+        c.version
+            .as_deref()
+            .is_some_and(|v| is_supported(v) && parse_py_version(v) == parse_py_version(wanted))
+    });
+    let first = matching.clone().next()?;
+    Some(matching.find(|c| versioned(c)).unwrap_or(first).path.clone())
 }
 
-fn find_embedded_python(app: &AppHandle) -> Option<PathBuf> {
-    let root = app.path().resolve("py", BaseDirectory::Resource).ok()?;
+fn find_embedded_python(host: &impl SetupHost) -> Option<PathBuf> {
+    let root = host.resource_path("py")?;
     let plat = if cfg!(all(target_os = "windows", target_arch = "x86_64")) {
         "windows"
     } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
@@ -308,14 +920,17 @@ fn find_embedded_python(app: &AppHandle) -> Option<PathBuf> {
     path.exists().then_some(path)
 }
 
-fn create_venv(base_python: &Path, venv_dir: &Path) -> Result<()> {
+fn create_venv(host: &impl SetupHost, base_python: &Path, venv_dir: &Path, upgrade_deps: bool) -> Result<()> {
     // Spawn the command and CAPTURE its output instead of just waiting for the status.
 
-    let output = Command::new(base_python)
-        .args(["-m", "venv", "--upgrade-deps"])
-        .arg(venv_dir)
-        .output()
-        .context("failed to spawn python -m venv")?;
+    let mut cmd = Command::new(base_python);
+    cmd.args(["-m", "venv"]);
+    // Upgrading pip needs PyPI
+    if upgrade_deps {
+        cmd.arg("--upgrade-deps");
+    }
+    cmd.arg(venv_dir);
+    let output = run_logged(host, "venv", &mut cmd, None, |_| {}).context("failed to spawn python -m venv")?;
 
     if !output.status.success() {
         // Turn the raw bytes into UTF‑8 strings (lossy is fine for diagnostics)
@@ -338,19 +953,34 @@ fn create_venv(base_python: &Path, venv_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-fn pip_install_requirements(venv_python: &Path, req_path: &Path) -> Result<()> {
-    // Online install
-    let output = Command::new(venv_python)
-        .args([
-            "-m",
-            "pip",
-            "install",
-            "--disable-pip-version-check",
-            "-r",
-            req_path.to_str().unwrap(),
-        ])
-        .output()
-        .context("pip install failed to spawn")?;
+fn pip_install_requirements(
+    host: &impl SetupHost,
+    venv_python: &Path,
+    req_path: &Path,
+    wheelhouse: Option<&Path>,
+) -> Result<()> {
+    let mut cmd = Command::new(venv_python);
+    cmd.args(["-m", "pip", "install", "--disable-pip-version-check"]);
+    // Offline install, otherwise online. Conda envs, other interpreters and
+    // offline venvs may have a pip too old for the raw progress.
+    match wheelhouse {
+        Some(wheelhouse) => {
+            cmd.arg("--no-index").arg("--find-links").arg(wheelhouse);
+        }
+        None if pip_version(venv_python).is_some_and(|v| v >= RAW_PROGRESS_PIP) => {
+            cmd.args(["--progress-bar", "raw"]);
+        }
+        None => {}
+    }
+    cmd.args(["-r", req_path.to_str().unwrap()]);
+
+    let mut pip = PipProgress::default();
+    let output = run_logged(host, "pip", &mut cmd, None, |line| {
+        if let Some(progress) = pip.update(line) {
+            host.progress(progress);
+        }
+    })
+    .context("pip install failed to spawn")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -365,7 +995,305 @@ fn pip_install_requirements(venv_python: &Path, req_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn get_python_version(py: &Path) -> Result<String> {
+/// The first pip with `--progress-bar raw`
+const RAW_PROGRESS_PIP: (u32, u32) = (24, 1);
+
+/// *major.minor* of the interpreter's pip, if it has one that runs
+fn pip_version(python: &Path) -> Option<(u32, u32)> {
+    let out = Command::new(python).args(["-m", "pip", "--version"]).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    parse_pip_version(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// `pip 24.1.2 from /usr/lib/... (python 3.12)` → `(24, 1)`
+fn parse_pip_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.trim().strip_prefix("pip ")?.split_whitespace().next()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    // `pip 24` has no minor version
+    let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+    Some((major, minor))
+}
+
+/// Run `cmd` to the end, sending every line it prints as a `py/setup-log`
+/// event right away, and to `on_line`. The output is captured as well, for
+/// error messages.
+fn run_logged(
+    host: &impl SetupHost,
+    source: &'static str,
+    cmd: &mut Command,
+    stdin: Option<&str>,
+    mut on_line: impl FnMut(&str),
+) -> Result<Output> {
+    cmd.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+    if let (Some(text), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // Dropping the pipe closes it; a process that doesn't read is fine
+        let _ = pipe.write_all(text.as_bytes());
+    }
+
+    let pipes: [(&'static str, Box<dyn Read + Send>); 2] = [
+        ("stdout", Box::new(child.stdout.take().context("stdout not captured")?)),
+        ("stderr", Box::new(child.stderr.take().context("stderr not captured")?)),
+    ];
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for (stream, pipe) in pipes {
+            let tx = tx.clone();
+            scope.spawn(move || {
+                let mut reader = BufReader::new(pipe);
+                let mut buf = Vec::new();
+                // Lossy, so a stray byte never stops the reading and blocks the process
+                while reader.read_until(b'\n', &mut buf).unwrap_or(0) > 0 {
+                    let line = String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_string();
+                    buf.clear();
+                    if tx.send((stream, line)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        for (stream, line) in rx {
+            on_line(&line);
+            let captured = if stream == "stdout" { &mut stdout } else { &mut stderr };
+            captured.extend_from_slice(line.as_bytes());
+            captured.push(b'\n');
+            host.log(PySetupLog {
+                source,
+                stream,
+                line,
+                timestamp: chrono::Local::now().to_rfc3339(),
+            });
+        }
+    });
+
+    let status = child.wait()?;
+    Ok(Output { status, stdout, stderr })
+}
+
+/// Turns pip's output into `InstallingPackage` progress
+#[derive(Default)]
+struct PipProgress {
+    package: Option<String>,
+    /// Last reported for the current package; pip prints a line per chunk
+    percent: Option<u8>,
+}
+
+impl PipProgress {
+    fn update(&mut self, line: &str) -> Option<PySetupProgress> {
+        let line = line.trim();
+        let percent = if let Some(rest) = line
+            .strip_prefix("Collecting ")
+            .or_else(|| line.strip_prefix("Processing "))
+        {
+            self.package = Some(package_name(rest));
+            None
+        } else if let Some(rest) = line.strip_prefix("Building wheel for ") {
+            self.package = rest.split_whitespace().next().map(str::to_string);
+            None
+        } else if let Some(rest) = line.strip_prefix("+ ") {
+            // uv lists what it installed as ` + name==version`
+            self.package = Some(package_name(rest));
+            None
+        } else if let Some(rest) = line.strip_prefix("Installing collected packages: ") {
+            self.package = Some(rest.to_string());
+            None
+        } else if let Some(rest) = line.strip_prefix("Progress ") {
+            // `--progress-bar raw`: "Progress <bytes> of <total>"
+            let (done, total) = rest.split_once(" of ")?;
+            let (done, total): (u64, u64) = (done.trim().parse().ok()?, total.trim().parse().ok()?);
+            let percent = (total > 0).then(|| (done.min(total) * 100 / total) as u8);
+            if percent == self.percent {
+                return None;
+            }
+            percent
+        } else {
+            return None;
+        };
+        self.percent = percent;
+        Some(PySetupProgress::InstallingPackage {
+            package: self.package.clone()?,
+            percent,
+        })
+    }
+}
+
+/// `antlr4-tools>=0.2`, `paredros-debugger @ git+...` or the path of a wheel
+fn package_name(requirement: &str) -> String {
+    let name = requirement
+        .trim()
+        .split(|c: char| c.is_whitespace() || "<>=!~;[(@".contains(c))
+        .next()
+        .unwrap_or_default();
+    if name.ends_with(".whl") || name.contains(['/', '\\']) {
+        let file = Path::new(name).file_name().unwrap_or_default().to_string_lossy();
+        return file.split('-').next().unwrap_or_default().to_string();
+    }
+    name.to_string()
+}
+
+/// The first directory with wheels in it of: the one the host names, the
+/// one in `PAREDROS_WHEELHOUSE`, `wheelhouse` next to the venv and the
+/// bundled one.
+fn find_wheelhouse(host: &impl SetupHost) -> Option<PathBuf> {
+    let next_to_venv = host
+        .venv_dir()
+        .ok()
+        .and_then(|dir| Some(dir.parent()?.join("wheelhouse")));
+    host.wheelhouse()
+        .into_iter()
+        .chain(std::env::var_os(WHEELHOUSE_ENV).map(PathBuf::from))
+        .chain(next_to_venv)
+        .chain(host.resource_path("wheelhouse"))
+        .find(|dir| wheels_in(dir).is_some_and(|wheels| !wheels.is_empty()))
+}
+
+fn wheels_in(dir: &Path) -> Option<Vec<String>> {
+    let mut wheels: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".whl"))
+        .collect();
+    wheels.sort();
+    Some(wheels)
+}
+
+/// `name @ git+https://...` makes pip fetch the source even with
+/// `--no-index`; offline the wheel of that name is used instead.
+fn offline_requirements(requirements: &str) -> String {
+    requirements
+        .lines()
+        .map(|line| match line.split_once('@') {
+            Some((name, url)) if url.contains("://") => name.trim(),
+            _ => line.trim(),
+        })
+        .filter(|line| !line.is_empty())
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/// Where antlr4-tools keeps the ANTLR jars, the local Maven repository
+fn antlr_jar_dir() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".m2/repository/org/antlr/antlr4"))
+}
+
+/// The newest `antlr4-<version>-complete.jar` in `dir` or its subdirectories,
+/// with its version
+fn find_antlr_jar(dir: &Path) -> Option<(String, PathBuf)> {
+    let version_of = |path: &Path| {
+        let name = path.file_name()?.to_str()?;
+        Some(name.strip_prefix("antlr4-")?.strip_suffix("-complete.jar")?.to_string())
+    };
+    let numeric = |version: &str| -> Vec<u32> { version.split('.').filter_map(|p| p.parse().ok()).collect() };
+
+    let entries = fs::read_dir(dir).ok()?.flatten().map(|e| e.path());
+    entries
+        .flat_map(|path| {
+            if path.is_dir() {
+                fs::read_dir(&path)
+                    .map(|inner| inner.flatten().map(|e| e.path()).collect())
+                    .unwrap_or_default()
+            } else {
+                vec![path]
+            }
+        })
+        .filter_map(|path| Some((version_of(&path)?, path)))
+        .max_by_key(|(version, _)| numeric(version))
+}
+
+/// Put the ANTLR jar of the wheelhouse where antlr4-tools looks for it and
+/// return its version to pin, so neither the setup nor later parser
+/// generation goes to Maven Central. Without a jar in the wheelhouse
+/// antlr4-tools is left alone.
+fn install_antlr_jar(wheelhouse: &Path) -> Result<Option<String>> {
+    let Some((version, jar)) = find_antlr_jar(wheelhouse) else {
+        return Ok(None);
+    };
+    let target_dir = antlr_jar_dir()
+        .ok_or_else(|| anyhow!("cannot resolve the home directory"))?
+        .join(&version);
+    fs::create_dir_all(&target_dir)
+        .with_context(|| format!("failed to create {}", target_dir.display()))?;
+    let target = target_dir.join(format!("antlr4-{version}-complete.jar"));
+    if !target.exists() {
+        fs::copy(&jar, &target).with_context(|| format!("failed to copy {}", jar.display()))?;
+    }
+    Ok(Some(version))
+}
+
+/// Point antlr4-tools at the jar `install_antlr_jar` installed. Needed in
+/// every process, since antlr4 runs again whenever a parser is generated.
+fn pin_antlr_version(state: &VenvState) {
+    if let Some(version) = &state.antlr_version {
+        std::env::set_var(ANTLR_VERSION_ENV, version);
+    }
+}
+
+/// Fill `dir` with wheels of everything in requirements.txt, plus the ANTLR
+/// jar if this machine has one, for `find_wheelhouse` on machines without
+/// network. The wheels fit this platform and Python version only.
+pub fn build_wheelhouse(host: &impl SetupHost, dir: &Path) -> Result<WheelhouseReport> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let venv_python = venv_python_path(&host.venv_dir()?);
+    let python = if venv_python.exists() {
+        venv_python
+    } else {
+        find_base_python(host)?
+    };
+    if !ensure_git_on_path(host)? {
+        return Err(anyhow!("Git is needed to fetch paredros-debugger"));
+    }
+
+    let req_path = dir.join("requirements.txt");
+    fs::write(&req_path, REQUIREMENTS).context("failed to write requirements.txt")?;
+    let mut cmd = Command::new(&python);
+    cmd.args(["-m", "pip", "wheel", "--disable-pip-version-check", "-r"])
+        .arg(&req_path)
+        .arg("--wheel-dir")
+        .arg(dir);
+    let mut pip = PipProgress::default();
+    let output = run_logged(host, "pip", &mut cmd, None, |line| {
+        if let Some(progress) = pip.update(line) {
+            host.progress(progress);
+        }
+    })
+    .context("pip wheel failed to spawn")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        return Err(anyhow!(
+            "pip wheel -r requirements.txt failed:\n{}\n{}",
+            stderr.trim(),
+            stdout.trim()
+        ));
+    }
+
+    let antlr_jar = match antlr_jar_dir().and_then(|jars| find_antlr_jar(&jars)) {
+        Some((version, jar)) => {
+            let name = format!("antlr4-{version}-complete.jar");
+            fs::copy(&jar, dir.join(&name)).with_context(|| format!("failed to copy {}", jar.display()))?;
+            Some(name)
+        }
+        None => None,
+    };
+
+    Ok(WheelhouseReport {
+        dir: dir.display().to_string(),
+        wheels: wheels_in(dir).unwrap_or_default(),
+        antlr_jar,
+    })
+}
+
+pub(crate) fn get_python_version(py: &Path) -> Result<String> {
     let out = Command::new(py)
         .args([
             "-c",
@@ -373,6 +1301,14 @@ fn get_python_version(py: &Path) -> Result<String> {
         ])
         .output()
         .context("could not run python to get version")?;
+    // A broken interpreter prints nothing, which must not pass for a version
+    if !out.status.success() {
+        return Err(anyhow!(
+            "{} failed to report its version: {}",
+            py.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
     Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
 }
 
@@ -448,7 +1384,7 @@ fn prepend_env_path(var: &str, path: &Path) {
     std::env::set_var(var, &new_val);
 }
 
-fn ensure_git_on_path(app: &AppHandle) -> anyhow::Result<bool> {
+fn ensure_git_on_path(host: &impl SetupHost) -> anyhow::Result<bool> {
     match which::which("git") {
         Ok(git) => {
             let dir = git.parent()
@@ -456,15 +1392,12 @@ fn ensure_git_on_path(app: &AppHandle) -> anyhow::Result<bool> {
             prepend_env_path("PATH", dir);
 
             // DEBUG
-            println!("Found git at: {}", git.display());
+            eprintln!("Found git at: {}", git.display());
 
             Ok(true)
         }
         Err(e) => {
-            let _ = app.emit(
-                "py/setup-progress",
-                PySetupProgress::Error(e.to_string()),
-            );
+            host.progress(PySetupProgress::Error(e.to_string()));
             Ok(false)
         }
     }
@@ -499,27 +1432,19 @@ fn antlr4_path(venv_dir: &Path) -> Option<PathBuf> {
     None
 }
 
-fn run_antlr4(app: &AppHandle, venv_dir: &Path) -> Result<()> {
+fn run_antlr4(host: &impl SetupHost, venv_dir: &Path) -> Result<()> {
     let antlr = antlr4_path(venv_dir)
         .ok_or_else(|| {
-            let _ = app.emit(
-                "py/setup-progress",
-                PySetupProgress::Error("antlr4 launcher not found in the virtual-env".to_string()),
-            );
+            host.progress(PySetupProgress::Error(
+                "antlr4 launcher not found in the virtual-env".to_string(),
+            ));
             anyhow!("antlr4 launcher not found in the virtual-env")
         })?;
 
-    // If Java isn't installed, this will trigger the antlr4-tools prompt
+    // If Java isn't installed, this will trigger the antlr4-tools prompt.
+    // Captured, so it ends up in the error and never on the CLI's stdout
     let mut cmd = Command::new(&antlr);
-    cmd.stdin(std::process::Stdio::piped());
-    cmd.stdout(std::process::Stdio::inherit());
-    cmd.stderr(std::process::Stdio::inherit());
-
-    let mut child = cmd.spawn().context("failed to spawn antlr4")?;
-    if let Some(mut stdin) = child.stdin.take() {
-        writeln!(stdin, "yes")?;
-    }
-    let output = child.wait_with_output().context("failed to wait on antlr4")?;
+    let output = run_logged(host, "antlr4", &mut cmd, Some("yes\n"), |_| {}).context("failed to spawn antlr4")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -532,12 +1457,164 @@ fn run_antlr4(app: &AppHandle, venv_dir: &Path) -> Result<()> {
             stdout.trim()
         );
 
-        let _ = app.emit(
-            "py/setup-progress",
-            PySetupProgress::Error(msg.clone()),
-        );
+        host.progress(PySetupProgress::Error(msg.clone()));
 
         return Err(anyhow!(msg));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installing(package: &str, percent: Option<u8>) -> Option<PySetupProgress> {
+        Some(PySetupProgress::InstallingPackage {
+            package: package.to_string(),
+            percent,
+        })
+    }
+
+    #[test]
+    fn pip_progress_follows_the_current_package() {
+        let mut pip = PipProgress::default();
+        assert_eq!(pip.update("Progress 10 of 100"), None);
+        assert_eq!(
+            pip.update("Collecting antlr4-tools>=0.2"),
+            installing("antlr4-tools", None)
+        );
+        assert_eq!(
+            pip.update("  Building wheel for paredros-debugger (pyproject.toml)"),
+            installing("paredros-debugger", None)
+        );
+        assert_eq!(
+            pip.update(" + antlr4-python3-runtime==4.13.2"),
+            installing("antlr4-python3-runtime", None)
+        );
+        assert_eq!(
+            pip.update("Installing collected packages: a, b"),
+            installing("a, b", None)
+        );
+        assert_eq!(pip.update("Successfully installed a b"), None);
+    }
+
+    #[test]
+    fn pip_progress_is_reported_when_the_percentage_changes() {
+        let mut pip = PipProgress::default();
+        pip.update("Collecting antlr4-tools");
+        assert_eq!(pip.update("Progress 0 of 2000"), installing("antlr4-tools", Some(0)));
+        assert_eq!(pip.update("Progress 10 of 2000"), None);
+        assert_eq!(pip.update("Progress 20 of 2000"), installing("antlr4-tools", Some(1)));
+        assert_eq!(pip.update("Progress 39 of 2000"), None);
+        assert_eq!(
+            pip.update("Progress 5000 of 2000"),
+            installing("antlr4-tools", Some(100))
+        );
+        assert_eq!(pip.update("Progress 5 of 0"), installing("antlr4-tools", None));
+        assert_eq!(pip.update("Progress of"), None);
+
+        // Starts over with the next package
+        pip.update("Collecting paredros-debugger");
+        assert_eq!(pip.update("Progress 0 of 10"), installing("paredros-debugger", Some(0)));
+    }
+
+    fn candidate(path: &str, version: Option<&str>) -> PythonCandidate {
+        PythonCandidate {
+            path: PathBuf::from(path),
+            version: version.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_python_versions() {
+        assert_eq!(parse_py_version("3.12"), Some((3, 12)));
+        assert_eq!(parse_py_version(" 3.12.4\n"), Some((3, 12)));
+        assert_eq!(parse_py_version("3"), None);
+        assert_eq!(parse_py_version("3.x"), None);
+        assert_eq!(parse_py_version(""), None);
+    }
+
+    #[test]
+    fn supports_python_3_10_to_3_13() {
+        assert!(is_supported("3.10"));
+        assert!(is_supported("3.13.1"));
+        assert!(!is_supported("3.9"));
+        assert!(!is_supported("3.14"));
+        assert!(!is_supported("2.7"));
+        assert!(!is_supported(""));
+    }
+
+    #[test]
+    fn recognizes_python_executables() {
+        let exe = |name: &str| if cfg!(windows) { format!("{name}.exe") } else { name.to_string() };
+        for name in ["python", "python3", "python3.12"] {
+            assert!(is_python_name(&exe(name)), "{name}");
+        }
+        for name in ["python3.", "python3.x", "python2", "pythonw", "python3-config", "python3.12-config"] {
+            assert!(!is_python_name(&exe(name)), "{name}");
+        }
+    }
+
+    #[test]
+    fn chooses_a_versioned_interpreter_of_the_wanted_version() {
+        let candidates = [
+            candidate("/usr/bin/python", Some("3.12")),
+            candidate("/usr/bin/python3", None),
+            candidate("/usr/bin/python3.11", Some("3.11")),
+            candidate("/opt/bin/python3.12", Some("3.12")),
+        ];
+        assert_eq!(choose_python("3.12", &candidates), Some(PathBuf::from("/opt/bin/python3.12")));
+        assert_eq!(choose_python("3.11", &candidates), Some(PathBuf::from("/usr/bin/python3.11")));
+        assert_eq!(choose_python("3.12", &candidates[..2]), Some(PathBuf::from("/usr/bin/python")));
+    }
+
+    #[test]
+    fn chooses_nothing_of_another_or_unsupported_version() {
+        let candidates = [candidate("/usr/bin/python3.13", Some("3.13")), candidate("/usr/bin/python3.9", Some("3.9"))];
+        assert_eq!(choose_python("3.12", &candidates), None);
+        assert_eq!(choose_python("3.9", &candidates), None);
+        assert_eq!(choose_python("3.12", &[]), None);
+    }
+
+    #[test]
+    fn parses_pip_versions() {
+        assert_eq!(
+            parse_pip_version("pip 24.1.2 from /usr/lib/python3/dist-packages/pip (python 3.12)\n"),
+            Some((24, 1))
+        );
+        assert_eq!(parse_pip_version("pip 9.0.1 from /opt/conda/lib (python 3.10)"), Some((9, 0)));
+        assert_eq!(parse_pip_version("pip 24 from /x (python 3.12)"), Some((24, 0)));
+        assert_eq!(parse_pip_version("No module named pip"), None);
+        assert!(parse_pip_version("pip 23.3.1 from /x").is_some_and(|v| v < RAW_PROGRESS_PIP));
+    }
+
+    #[test]
+    fn package_name_strips_versions_markers_and_paths() {
+        assert_eq!(package_name("antlr4-tools>=0.2"), "antlr4-tools");
+        assert_eq!(package_name("antlr4-tools ; python_version >= '3.10'"), "antlr4-tools");
+        assert_eq!(
+            package_name("paredros-debugger @ git+https://github.com/HisQu/paredros-debugger.git"),
+            "paredros-debugger"
+        );
+        assert_eq!(package_name("requests[socks]~=2.0"), "requests");
+        assert_eq!(
+            package_name("./wheelhouse/antlr4_tools-0.2.1-py3-none-any.whl"),
+            "antlr4_tools"
+        );
+        assert_eq!(
+            package_name("/tmp/wheels/paredros_debugger-0.1.0-py3-none-any.whl (from -r req.txt)"),
+            "paredros_debugger"
+        );
+    }
+
+    #[test]
+    fn offline_requirements_drop_urls_and_blank_lines() {
+        let requirements = "antlr4-tools\n\n  antlr4-python3-runtime==4.13.2  \n\
+            paredros-debugger @ git+https://github.com/HisQu/paredros-debugger.git@main\n\
+            local @ file:///tmp/local\nmarker; python_version >= '3.10'\n";
+        assert_eq!(
+            offline_requirements(requirements),
+            "antlr4-tools\nantlr4-python3-runtime==4.13.2\nparedros-debugger\nlocal\nmarker; python_version >= '3.10'\n"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+// ==============================
+// src-tauri/src/session.rs
+// ==============================
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::breakpoints::Breakpoints;
+use crate::debugger;
+use crate::error::{CommandError, CommandResult};
+use crate::tokens::{self, TokenRecord};
+
+/// Default number of `ParseInformation` instances kept alive at once.
+const DEFAULT_MAX_SESSIONS: usize = 8;
+/// Default time after which an untouched session is dropped.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// One live `ParseInformation` instance.
+///
+/// Lock order: always lock the session *before* taking the GIL. A thread
+/// that blocks on a session lock while holding the GIL never releases it.
+pub struct ParseSession {
+    pub parse_info: Py<PyAny>,
+    pub grammar_path: String,
+    /// Text handed to the last successful `parse_input`
+    pub last_input: Option<String>,
+    pub breakpoints: Breakpoints,
+}
+
+impl ParseSession {
+    pub fn new(parse_info: Py<PyAny>, grammar_path: String) -> Self {
+        Self {
+            parse_info,
+            grammar_path,
+            last_input: None,
+            breakpoints: Breakpoints::default(),
+        }
+    }
+
+    /// Create a fresh `ParseInformation` for the grammar at `grammar_path`.
+    pub fn open(py: Python<'_>, grammar_path: String) -> CommandResult<Self> {
+        let module = py.import("paredros_debugger.ParseInformation")?;
+        let cls = module.getattr("ParseInformation")?;
+        let obj = cls.call1((grammar_path.as_str(),))?;
+        Ok(Self::new(obj.into(), grammar_path))
+    }
+
+    /// Run ANTLR on the grammar and load the generated parser.
+    pub fn generate_parser(&self, py: Python<'_>) -> CommandResult<()> {
+        self.parse_info
+            .getattr(py, "generate_parser")?
+            .call0(py)
+            .map_err(|e| CommandError::from_generation_error(py, e))?;
+        Ok(())
+    }
+
+    /// Parse `input` and put the cursor on the first step.
+    pub fn parse(&mut self, py: Python<'_>, input: String) -> CommandResult<()> {
+        self.parse_info.getattr(py, "parse")?.call1(py, (input.as_str(),))?;
+        self.parse_info.getattr(py, "go_to_step")?.call1(py, (0,))?;
+        self.last_input = Some(input);
+        Ok(())
+    }
+
+    /// Parse a saved token stream instead of lexing an input; see `tokens`.
+    pub fn parse_tokens(&mut self, py: Python<'_>, records: &[TokenRecord]) -> CommandResult<()> {
+        let text = tokens::parse_replayed(py, &self.parse_info, records)?;
+        self.parse_info.getattr(py, "go_to_step")?.call1(py, (0,))?;
+        self.last_input = Some(text);
+        Ok(())
+    }
+
+    /// The `ParseInformation` instance, provided an input has been parsed.
+    pub fn parsed(&self) -> CommandResult<&Py<PyAny>> {
+        match self.last_input {
+            Some(_) => Ok(&self.parse_info),
+            None => Err(CommandError::NotParsedYet),
+        }
+    }
+}
+
+pub type SessionHandle = Arc<Mutex<ParseSession>>;
+
+/// Store entry; the bookkeeping lives outside the session lock so listing
+/// and eviction never wait for a running parse.
+struct Entry {
+    session: SessionHandle,
+    grammar_path: String,
+    created_at: SystemTime,
+    last_used: Instant,
+}
+
+/// What `list_parse_infos` hands to the frontend
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    pub id: usize,
+    pub grammar_path: String,
+    /// Milliseconds since the unix epoch
+    pub created_at: u64,
+    pub idle_secs: u64,
+    /// `None` until the input has been parsed, or while the session is busy
+    pub step_count: Option<usize>,
+    /// Another operation currently holds the session
+    pub busy: bool,
+}
+
+/// Limits applied whenever a new session is opened; the idle timeout also
+/// whenever one is looked up.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EvictionPolicy {
+    /// Upper bound of sessions; the least recently used ones are closed first.
+    pub max_sessions: usize,
+    /// Sessions untouched for longer than this are closed. `None` disables it.
+    pub idle_timeout_secs: Option<u64>,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        Self {
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            idle_timeout_secs: Some(DEFAULT_IDLE_TIMEOUT.as_secs()),
+        }
+    }
+}
+
+/// All open sessions. The map lock is only held for lookups; Python work
+/// happens under the lock of the individual session.
+pub struct ParseInfoStore {
+    counter: AtomicUsize,
+    nodes: Mutex<HashMap<usize, Entry>>,
+    policy: Mutex<EvictionPolicy>,
+}
+
+impl Default for ParseInfoStore {
+    fn default() -> Self {
+        Self {
+            counter: AtomicUsize::new(1),
+            nodes: Mutex::new(HashMap::new()),
+            policy: Mutex::new(EvictionPolicy::default()),
+        }
+    }
+}
+
+impl ParseInfoStore {
+    /// Store a new session, evicting old ones first so the new one always fits.
+    pub fn insert(&self, session: ParseSession) -> CommandResult<usize> {
+        let id = self.counter.fetch_add(1, Ordering::SeqCst);
+        let policy = *self.policy.lock()?;
+        let entry = Entry {
+            grammar_path: session.grammar_path.clone(),
+            session: Arc::new(Mutex::new(session)),
+            created_at: SystemTime::now(),
+            last_used: Instant::now(),
+        };
+
+        let mut nodes = self.nodes.lock()?;
+        let evicted = evict(&mut nodes, &policy, policy.max_sessions.saturating_sub(1));
+        nodes.insert(id, entry);
+        drop(nodes);
+
+        release(evicted);
+        Ok(id)
+    }
+
+    /// Look up a session and mark it as recently used. Idle sessions are
+    /// closed first, so one that timed out is gone even if nothing was opened since.
+    pub fn session(&self, id: usize) -> CommandResult<SessionHandle> {
+        let policy = *self.policy.lock()?;
+        let mut nodes = self.nodes.lock()?;
+        let idle = evict(&mut nodes, &policy, usize::MAX);
+        let session = nodes.get_mut(&id).map(|entry| {
+            entry.last_used = Instant::now();
+            entry.session.clone()
+        });
+        drop(nodes);
+
+        // Only wait for the GIL if there is something to free
+        if !idle.is_empty() {
+            release(idle);
+        }
+        session.ok_or(CommandError::UnknownSession { id })
+    }
+
+    /// Remove a session. Returns `false` if the id was unknown.
+    /// An operation still running on it keeps the instance alive until it ends.
+    pub fn remove(&self, id: usize) -> CommandResult<bool> {
+        let removed = self.nodes.lock()?.remove(&id);
+        let found = removed.is_some();
+        release(removed);
+        Ok(found)
+    }
+
+    pub fn list(&self) -> CommandResult<Vec<SessionSummary>> {
+        let entries: Vec<(usize, String, SystemTime, Instant, SessionHandle)> = self
+            .nodes
+            .lock()?
+            .iter()
+            .map(|(id, e)| (*id, e.grammar_path.clone(), e.created_at, e.last_used, e.session.clone()))
+            .collect();
+
+        let mut summaries: Vec<SessionSummary> = entries
+            .into_iter()
+            .map(|(id, grammar_path, created_at, last_used, session)| {
+                // Don't wait for sessions that are in the middle of a parse
+                let session = session.try_lock().ok();
+                let step_count = session.as_ref().and_then(|s| {
+                    s.last_input.as_ref()?;
+                    Python::with_gil(|py| debugger::step_count(py, &s.parse_info))
+                });
+                SessionSummary {
+                    id,
+                    grammar_path,
+                    created_at: created_at
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_millis() as u64)
+                        .unwrap_or_default(),
+                    idle_secs: last_used.elapsed().as_secs(),
+                    step_count,
+                    busy: session.is_none(),
+                }
+            })
+            .collect();
+        summaries.sort_by_key(|s| s.id);
+        Ok(summaries)
+    }
+
+    pub fn policy(&self) -> CommandResult<EvictionPolicy> {
+        Ok(*self.policy.lock()?)
+    }
+
+    /// Replace the eviction policy and apply it right away.
+    /// Returns the ids of the sessions that were closed.
+    pub fn set_policy(&self, policy: EvictionPolicy) -> CommandResult<Vec<usize>> {
+        *self.policy.lock()? = policy;
+        let mut nodes = self.nodes.lock()?;
+        let evicted = evict(&mut nodes, &policy, policy.max_sessions);
+        drop(nodes);
+
+        let ids = evicted.iter().map(|(id, _)| *id).collect();
+        release(evicted);
+        Ok(ids)
+    }
+}
+
+/// Drop idle sessions, then the least recently used ones until at most
+/// `keep` remain.
+fn evict(
+    nodes: &mut HashMap<usize, Entry>,
+    policy: &EvictionPolicy,
+    keep: usize,
+) -> Vec<(usize, Entry)> {
+    let mut doomed: Vec<usize> = match policy.idle_timeout_secs {
+        Some(secs) => nodes
+            .iter()
+            .filter(|(_, e)| e.last_used.elapsed() > Duration::from_secs(secs))
+            .map(|(id, _)| *id)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let remaining = nodes.len() - doomed.len();
+    if remaining > keep {
+        let mut by_age: Vec<(usize, Instant)> = nodes
+            .iter()
+            .filter(|(id, _)| !doomed.contains(id))
+            .map(|(id, e)| (*id, e.last_used))
+            .collect();
+        by_age.sort_by_key(|(_, last_used)| *last_used);
+        doomed.extend(by_age.into_iter().take(remaining - keep).map(|(id, _)| id));
+    }
+
+    doomed
+        .into_iter()
+        .filter_map(|id| nodes.remove(&id).map(|e| (id, e)))
+        .collect()
+}
+
+/// Drop the Python objects while holding the GIL, so they are freed right
+/// away instead of being queued until some other thread takes the GIL.
+fn release<T>(sessions: T) {
+    Python::with_gil(|_py| drop(sessions));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_sessions: usize, idle_timeout_secs: Option<u64>) -> ParseInfoStore {
+        let store = ParseInfoStore::default();
+        store
+            .set_policy(EvictionPolicy {
+                max_sessions,
+                idle_timeout_secs,
+            })
+            .unwrap();
+        store
+    }
+
+    fn open(store: &ParseInfoStore) -> usize {
+        let parse_info = Python::with_gil(|py| py.None());
+        store.insert(ParseSession::new(parse_info, "Regest.g4".to_string())).unwrap()
+    }
+
+    /// Pretend session `id` was last used `secs` seconds ago
+    fn age(store: &ParseInfoStore, id: usize, secs: u64) {
+        let mut nodes = store.nodes.lock().unwrap();
+        let entry = nodes.get_mut(&id).unwrap();
+        entry.last_used = Instant::now().checked_sub(Duration::from_secs(secs)).unwrap();
+    }
+
+    fn ids(store: &ParseInfoStore) -> Vec<usize> {
+        let mut ids: Vec<usize> = store.nodes.lock().unwrap().keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn opening_beyond_the_limit_closes_the_least_recently_used() {
+        let store = store(2, None);
+        let (first, second) = (open(&store), open(&store));
+        age(&store, first, 20);
+        age(&store, second, 10);
+        store.session(first).unwrap();
+
+        let third = open(&store);
+        assert_eq!(ids(&store), [first, third]);
+    }
+
+    #[test]
+    fn lowering_the_limit_closes_sessions_right_away() {
+        let store = store(4, None);
+        let opened: Vec<usize> = (0..3).map(|_| open(&store)).collect();
+        for (i, id) in opened.iter().enumerate() {
+            age(&store, *id, 10 - i as u64);
+        }
+        let mut closed = store
+            .set_policy(EvictionPolicy {
+                max_sessions: 1,
+                idle_timeout_secs: None,
+            })
+            .unwrap();
+        closed.sort();
+        assert_eq!(closed, opened[..2]);
+        assert_eq!(ids(&store), opened[2..]);
+    }
+
+    #[test]
+    fn looking_up_a_session_closes_idle_ones() {
+        let store = store(8, Some(60));
+        let (idle, active, other) = (open(&store), open(&store), open(&store));
+        age(&store, idle, 61);
+        age(&store, other, 59);
+
+        store.session(active).unwrap();
+        assert_eq!(ids(&store), [active, other]);
+
+        age(&store, other, 61);
+        assert!(matches!(
+            store.session(other),
+            Err(CommandError::UnknownSession { id }) if id == other
+        ));
+        assert_eq!(ids(&store), [active]);
+    }
+
+    #[test]
+    fn no_idle_timeout_keeps_idle_sessions() {
+        let store = store(8, None);
+        let (idle, active) = (open(&store), open(&store));
+        age(&store, idle, 24 * 60 * 60);
+        store.session(active).unwrap();
+        open(&store);
+        assert_eq!(ids(&store).len(), 3);
+    }
+}
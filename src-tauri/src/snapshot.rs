@@ -0,0 +1,554 @@
+// ==============================
+// src-tauri/src/snapshot.rs
+// ==============================
+
+//! Golden parse-tree snapshots: the expected tree of an input is stored as
+//! JSON, and later trees are compared against it structurally.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::debugger;
+use crate::error::{CommandError, CommandResult};
+use crate::files;
+use crate::session::ParseSession;
+
+/// Appended to the input file name unless a snapshot directory is given
+pub const SNAPSHOT_SUFFIX: &str = ".tree.json";
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// A parse tree node without the ids and trace details of
+/// `get_current_tree_dict`, which change with every run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeNode {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub error: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Convert a node of the depythonized `get_current_tree_dict` output.
+    pub fn from_json(node: &serde_json::Value) -> Self {
+        let rule = node["rule_name"].as_str().map(str::to_string);
+        Self {
+            token: match rule {
+                Some(_) => None,
+                None => node["token"].as_str().map(str::to_string),
+            },
+            rule,
+            error: node["node_type"]
+                .as_str()
+                .is_some_and(|t| t.to_lowercase().contains("error")),
+            children: node["children"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(TreeNode::from_json)
+                .collect(),
+        }
+    }
+
+    /// Rule name, quoted token text or `?`
+    pub fn label(&self) -> String {
+        match (&self.rule, &self.token) {
+            (Some(rule), _) => rule.clone(),
+            (None, Some(token)) => format!("{token:?}"),
+            (None, None) => "?".to_string(),
+        }
+    }
+
+    fn is_rule(&self) -> bool {
+        self.rule.is_some()
+    }
+}
+
+/// The file stored per input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub grammar: String,
+    pub input: String,
+    pub tree: TreeNode,
+}
+
+/// One structural difference. Paths look like `regest/vita[2]/datum[0]`,
+/// with the index of each node among its siblings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum TreeChange {
+    Added { path: String, node: String },
+    Removed { path: String, node: String },
+    RuleRenamed { path: String, from: String, to: String },
+    TokenChanged { path: String, from: String, to: String },
+    /// The node is an error node now (`error`), or no longer is
+    ErrorChanged { path: String, node: String, error: bool },
+    /// The same subtree was removed in one place and added in another
+    Moved { from: String, to: String, node: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    Unchanged,
+    Changed,
+    /// No snapshot stored yet
+    Missing,
+    /// Written by this run
+    Updated,
+    Errored,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotResult {
+    pub input: String,
+    pub snapshot: String,
+    pub status: SnapshotStatus,
+    pub changes: Vec<TreeChange>,
+    pub message: Option<String>,
+}
+
+/// Where the snapshot of `input` lives
+pub fn snapshot_path(input: &Path, dir: Option<&Path>) -> PathBuf {
+    let name = format!(
+        "{}{SNAPSHOT_SUFFIX}",
+        input.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+    );
+    match dir {
+        Some(dir) => dir.join(name),
+        None => input.with_file_name(name),
+    }
+}
+
+pub fn read(path: &Path) -> CommandResult<Snapshot> {
+    let json = fs::read_to_string(path).map_err(|e| CommandError::io(path, e))?;
+    let snapshot: Snapshot = serde_json::from_str(&json).map_err(|e| invalid(path, e))?;
+    if snapshot.version > SNAPSHOT_VERSION {
+        return Err(invalid(
+            path,
+            format!("format version {} is newer than {SNAPSHOT_VERSION}", snapshot.version),
+        ));
+    }
+    Ok(snapshot)
+}
+
+pub fn write(path: &Path, grammar: &str, input: &str, tree: TreeNode) -> CommandResult<()> {
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION,
+        grammar: grammar.to_string(),
+        input: input.to_string(),
+        tree,
+    };
+    let json = serde_json::to_string_pretty(&snapshot).map_err(|e| invalid(path, e))?;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| CommandError::io(dir, e))?;
+    }
+    files::write_atomically(path, (json + "\n").as_bytes())
+}
+
+/// The complete tree of a parsed session, normalised for snapshots
+pub fn current_tree(py: Python<'_>, parse_info: &Py<PyAny>) -> CommandResult<TreeNode> {
+    Ok(TreeNode::from_json(&debugger::final_parse_tree(py, parse_info)?))
+}
+
+/// Parse each input and compare its tree with the stored snapshot.
+/// With `update`, changed and missing snapshots are (re)written.
+pub fn check_inputs(
+    py: Python<'_>,
+    grammar: &str,
+    inputs: &[PathBuf],
+    snapshot_dir: Option<&Path>,
+    update: bool,
+    mut on_result: impl FnMut(&SnapshotResult),
+) -> CommandResult<Vec<SnapshotResult>> {
+    let mut session = ParseSession::open(py, grammar.to_string())?;
+    session.generate_parser(py)?;
+
+    let mut results = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let path = snapshot_path(input, snapshot_dir);
+        let result = match check_input(py, &mut session, grammar, input, &path, update) {
+            Ok((status, changes)) => SnapshotResult {
+                input: input.display().to_string(),
+                snapshot: path.display().to_string(),
+                status,
+                changes,
+                message: None,
+            },
+            Err(e) if e.is_interrupt() => return Err(e),
+            Err(e) => SnapshotResult {
+                input: input.display().to_string(),
+                snapshot: path.display().to_string(),
+                status: SnapshotStatus::Errored,
+                changes: Vec::new(),
+                message: Some(e.to_string()),
+            },
+        };
+        on_result(&result);
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn check_input(
+    py: Python<'_>,
+    session: &mut ParseSession,
+    grammar: &str,
+    input: &Path,
+    path: &Path,
+    update: bool,
+) -> CommandResult<(SnapshotStatus, Vec<TreeChange>)> {
+    let text = fs::read_to_string(input).map_err(|e| CommandError::io(input, e))?;
+    session.parse(py, text)?;
+    let tree = current_tree(py, session.parsed()?)?;
+
+    let (status, changes) = if path.exists() {
+        let changes = diff(&read(path)?.tree, &tree);
+        let status = if changes.is_empty() {
+            SnapshotStatus::Unchanged
+        } else {
+            SnapshotStatus::Changed
+        };
+        (status, changes)
+    } else {
+        (SnapshotStatus::Missing, Vec::new())
+    };
+
+    if update && status != SnapshotStatus::Unchanged {
+        write(path, grammar, &input.display().to_string(), tree)?;
+        return Ok((SnapshotStatus::Updated, changes));
+    }
+    Ok((status, changes))
+}
+
+/// Structural differences from `expected` to `actual`
+pub fn diff(expected: &TreeNode, actual: &TreeNode) -> Vec<TreeChange> {
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    diff_node(expected, actual, &expected.label(), &actual.label(), &mut changes, &mut removed, &mut added);
+
+    // A removed rule subtree that shows up unchanged elsewhere was moved
+    for (from, node) in removed {
+        let moved_to = node
+            .is_rule()
+            .then(|| added.iter().position(|(_, a)| *a == node))
+            .flatten();
+        match moved_to {
+            Some(i) => {
+                let (to, _) = added.remove(i);
+                changes.push(TreeChange::Moved {
+                    from,
+                    to,
+                    node: node.label(),
+                });
+            }
+            None => changes.push(TreeChange::Removed {
+                path: from,
+                node: node.label(),
+            }),
+        }
+    }
+    changes.extend(added.into_iter().map(|(path, node)| TreeChange::Added {
+        path,
+        node: node.label(),
+    }));
+    changes
+}
+
+type Subtrees<'a> = Vec<(String, &'a TreeNode)>;
+
+fn diff_node<'a>(
+    old: &'a TreeNode,
+    new: &'a TreeNode,
+    old_path: &str,
+    new_path: &str,
+    changes: &mut Vec<TreeChange>,
+    removed: &mut Subtrees<'a>,
+    added: &mut Subtrees<'a>,
+) {
+    match (&old.rule, &new.rule) {
+        (Some(from), Some(to)) if from != to => changes.push(TreeChange::RuleRenamed {
+            path: new_path.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+        }),
+        (None, None) if old.token != new.token => changes.push(TreeChange::TokenChanged {
+            path: new_path.to_string(),
+            from: old.label(),
+            to: new.label(),
+        }),
+        _ => {}
+    }
+    if old.error != new.error {
+        changes.push(TreeChange::ErrorChanged {
+            path: new_path.to_string(),
+            node: new.label(),
+            error: new.error,
+        });
+    }
+
+    let child_path = |path: &str, node: &TreeNode, index: usize| format!("{path}/{}[{index}]", node.label());
+    let pairs = align(&old.children, &new.children);
+    for pair in pairs {
+        match pair {
+            (Some(i), Some(j)) => {
+                let (o, n) = (&old.children[i], &new.children[j]);
+                diff_node(o, n, &child_path(old_path, o, i), &child_path(new_path, n, j), changes, removed, added);
+            }
+            (Some(i), None) => {
+                let o = &old.children[i];
+                removed.push((child_path(old_path, o, i), o));
+            }
+            (None, Some(j)) => {
+                let n = &new.children[j];
+                added.push((child_path(new_path, n, j), n));
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Pair up children: equal labels and error flags via their longest common
+/// subsequence, then the leftovers between two matches position by position
+/// if they are of the same kind (a renamed rule, a changed token or error
+/// flag). Unpaired ones were removed or added.
+fn align(old: &[TreeNode], new: &[TreeNode]) -> Vec<(Option<usize>, Option<usize>)> {
+    let (n, m) = (old.len(), new.len());
+    let same = |i: usize, j: usize| {
+        old[i].rule == new[j].rule && old[i].token == new[j].token && old[i].error == new[j].error
+    };
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if same(i, j) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut gap_old, mut gap_new) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && same(i, j) {
+            flush_gap(old, new, &mut gap_old, &mut gap_new, &mut pairs);
+            pairs.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            gap_old.push(i);
+            i += 1;
+        } else {
+            gap_new.push(j);
+            j += 1;
+        }
+    }
+    flush_gap(old, new, &mut gap_old, &mut gap_new, &mut pairs);
+    pairs
+}
+
+fn flush_gap(
+    old: &[TreeNode],
+    new: &[TreeNode],
+    gap_old: &mut Vec<usize>,
+    gap_new: &mut Vec<usize>,
+    pairs: &mut Vec<(Option<usize>, Option<usize>)>,
+) {
+    let mut new_left: Vec<Option<usize>> = gap_new.drain(..).map(Some).collect();
+    for i in gap_old.drain(..) {
+        let partner = new_left
+            .iter_mut()
+            .find(|j| j.is_some_and(|j| new[j].is_rule() == old[i].is_rule()))
+            .and_then(Option::take);
+        pairs.push((Some(i), partner));
+    }
+    pairs.extend(new_left.into_iter().flatten().map(|j| (None, Some(j))));
+}
+
+/// One line per change, for terminals and review comments
+pub fn format_changes(changes: &[TreeChange]) -> String {
+    changes
+        .iter()
+        .map(|c| match c {
+            TreeChange::Added { path, node } => format!("+ {node} added at {path}"),
+            TreeChange::Removed { path, node } => format!("- {node} removed from {path}"),
+            TreeChange::RuleRenamed { path, from, to } => format!("~ rule {from} became {to} at {path}"),
+            TreeChange::TokenChanged { path, from, to } => format!("~ token {from} became {to} at {path}"),
+            TreeChange::ErrorChanged { path, node, error: true } => format!("! {node} became an error node at {path}"),
+            TreeChange::ErrorChanged { path, node, error: false } => {
+                format!("! {node} is no longer an error node at {path}")
+            }
+            TreeChange::Moved { from, to, node } => format!("> {node} moved from {from} to {to}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn invalid(path: &Path, err: impl ToString) -> CommandError {
+    CommandError::invalid_argument(format!(
+        "{} is not a valid tree snapshot: {}",
+        path.display(),
+        err.to_string()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode {
+            rule: Some(name.to_string()),
+            token: None,
+            error: false,
+            children,
+        }
+    }
+
+    fn token(text: &str) -> TreeNode {
+        TreeNode {
+            rule: None,
+            token: Some(text.to_string()),
+            error: false,
+            children: Vec::new(),
+        }
+    }
+
+    fn error(text: &str) -> TreeNode {
+        TreeNode {
+            error: true,
+            ..token(text)
+        }
+    }
+
+    #[test]
+    fn equal_trees_have_no_changes() {
+        let tree = rule("regest", vec![rule("vita", vec![token("Hans")]), token(",")]);
+        assert_eq!(diff(&tree, &tree.clone()), Vec::new());
+    }
+
+    #[test]
+    fn reports_renamed_rules_and_changed_tokens_in_place() {
+        let old = rule("regest", vec![rule("vita", vec![token("Hans")])]);
+        let new = rule("regest", vec![rule("datum", vec![token("Heinz")])]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                TreeChange::RuleRenamed {
+                    path: "regest/datum[0]".to_string(),
+                    from: "vita".to_string(),
+                    to: "datum".to_string(),
+                },
+                TreeChange::TokenChanged {
+                    path: "regest/datum[0]/\"Heinz\"[0]".to_string(),
+                    from: "\"Hans\"".to_string(),
+                    to: "\"Heinz\"".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_error_nodes_that_appear_or_disappear() {
+        let old = rule("regest", vec![token("Hans"), token(","), error("?")]);
+        let new = rule("regest", vec![token("Hans"), error(","), token("?")]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                TreeChange::ErrorChanged {
+                    path: "regest/\",\"[1]".to_string(),
+                    node: "\",\"".to_string(),
+                    error: true,
+                },
+                TreeChange::ErrorChanged {
+                    path: "regest/\"?\"[2]".to_string(),
+                    node: "\"?\"".to_string(),
+                    error: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_moved_subtrees() {
+        let vita = rule("vita", vec![token("Hans")]);
+        let old = rule("regest", vec![rule("kopf", vec![vita.clone()]), rule("rumpf", vec![]), token(";")]);
+        let new = rule("regest", vec![rule("kopf", vec![]), rule("rumpf", vec![vita]), token(".")]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                TreeChange::TokenChanged {
+                    path: "regest/\".\"[2]".to_string(),
+                    from: "\";\"".to_string(),
+                    to: "\".\"".to_string(),
+                },
+                TreeChange::Moved {
+                    from: "regest/kopf[0]/vita[0]".to_string(),
+                    to: "regest/rumpf[1]/vita[0]".to_string(),
+                    node: "vita".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_added_and_removed_subtrees() {
+        let old = rule("regest", vec![token("Hans"), rule("vita", vec![])]);
+        let new = rule("regest", vec![token("Hans"), token(","), token("Heinz")]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                TreeChange::Removed {
+                    path: "regest/vita[1]".to_string(),
+                    node: "vita".to_string(),
+                },
+                TreeChange::Added {
+                    path: "regest/\",\"[1]".to_string(),
+                    node: "\",\"".to_string(),
+                },
+                TreeChange::Added {
+                    path: "regest/\"Heinz\"[2]".to_string(),
+                    node: "\"Heinz\"".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn aligns_equal_children_around_insertions() {
+        let old = [token("a"), token("b"), token("c")];
+        let new = [token("a"), token("x"), token("b"), token("c")];
+        assert_eq!(
+            align(&old, &new),
+            vec![(Some(0), Some(0)), (None, Some(1)), (Some(1), Some(2)), (Some(2), Some(3))]
+        );
+    }
+
+    #[test]
+    fn pairs_leftovers_of_the_same_kind_by_position() {
+        let old = [token("a"), rule("vita", vec![]), token("b"), token("z")];
+        let new = [token("a"), token("x"), rule("datum", vec![]), token("z")];
+        assert_eq!(
+            align(&old, &new),
+            vec![(Some(0), Some(0)), (Some(1), Some(2)), (Some(2), Some(1)), (Some(3), Some(3))]
+        );
+    }
+
+    #[test]
+    fn pairs_a_token_with_its_error_node() {
+        let old = [token("a"), token("b")];
+        let new = [error("a"), token("b")];
+        assert_eq!(align(&old, &new), vec![(Some(0), Some(0)), (Some(1), Some(1))]);
+    }
+}
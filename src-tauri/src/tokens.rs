@@ -0,0 +1,506 @@
+// ==============================
+// src-tauri/src/tokens.rs
+// ==============================
+
+//! Saving the token stream of a parse, and parsing a saved one again without
+//! running the lexer, so parser rules can be tried out in isolation from
+//! lexer changes.
+//!
+//! During such a replay `antlr4.Lexer.nextToken` hands out the saved tokens,
+//! on their saved channels, instead of lexing; it is restored once no replay
+//! runs. Token types are looked up by name in the new lexer, so renumbered
+//! types don't matter; the numeric id is only the fallback.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fmt::Write;
+
+use pyo3::prelude::*;
+use pyo3::types::PyModule;
+use serde::{Deserialize, Serialize};
+
+use crate::debugger::TokenInfo;
+use crate::error::{CommandError, CommandResult};
+
+const COLUMNS: [&str; 9] = [
+    "text",
+    "typeName",
+    "typeId",
+    "line",
+    "column",
+    "startIndex",
+    "stopIndex",
+    "tokenIndex",
+    "channel",
+];
+
+const EOF_TYPE: i32 = -1;
+/// `Token.DEFAULT_CHANNEL`
+const DEFAULT_CHANNEL: i32 = 0;
+
+const REPLAY_MODULE: &str = "paredros_token_replay";
+const REPLAY_SOURCE: &CStr = cr#"
+import threading
+
+from antlr4.Lexer import Lexer
+from antlr4.Token import CommonToken, Token
+
+# Thread ident -> (tokens, end of the text, line and column of EOF)
+_replays = {}
+_lex = Lexer.nextToken
+# Guards _replays together with the patch of Lexer.nextToken
+_lock = threading.Lock()
+
+
+def _token_type(lexer, type_name, type_id):
+    names = list(lexer.symbolicNames)
+    if type_name in names:
+        return names.index(type_name)
+    if type_id is not None:
+        return type_id
+    raise ValueError(f"The lexer has no token type {type_name}")
+
+
+def _next_token(lexer):
+    replay = _replays.get(threading.get_ident())
+    if replay is None:
+        return _lex(lexer)
+    tokens, end, eof_line, eof_column = replay
+    position = getattr(lexer, "_paredros_position", 0)
+    lexer._paredros_position = position + 1
+    source = (lexer, lexer._input)
+
+    if position >= len(tokens):
+        token = CommonToken(source, Token.EOF, Token.DEFAULT_CHANNEL, end, end - 1)
+        token.text = "<EOF>"
+        token.line, token.column = eof_line, eof_column
+        return token
+
+    type_name, type_id, text, start, stop, line, column, channel = tokens[position]
+    token_type = _token_type(lexer, type_name, type_id)
+    token = CommonToken(source, token_type, channel, start, stop)
+    token.text = text
+    token.line, token.column = line, column
+    return token
+
+
+def parse(parse_info, text, tokens, eof_line, eof_column):
+    ident = threading.get_ident()
+    with _lock:
+        _replays[ident] = (tokens, len(text), eof_line, eof_column)
+        Lexer.nextToken = _next_token
+    try:
+        parse_info.parse(text)
+    finally:
+        with _lock:
+            del _replays[ident]
+            if not _replays:
+                Lexer.nextToken = _lex
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TokenFormat {
+    Csv,
+    Tsv,
+    /// One array of tokens
+    Json,
+    /// One token per line
+    JsonLines,
+    /// ANTLR's `NAME=type` vocabulary; can't be parsed again
+    Tokens,
+}
+
+/// A token read back from a file. Only the text and the type, by name or
+/// id, are required; hand-written streams may leave out the rest.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRecord {
+    pub text: String,
+    pub type_name: Option<String>,
+    pub type_id: Option<i32>,
+    pub line: Option<i32>,
+    pub column: Option<i32>,
+    /// The default channel if left out
+    pub channel: Option<i32>,
+}
+
+impl TokenRecord {
+    fn is_eof(&self) -> bool {
+        self.type_id == Some(EOF_TYPE) || self.type_name.as_deref() == Some("EOF")
+    }
+}
+
+pub fn export(tokens: &[TokenInfo], format: TokenFormat) -> CommandResult<String> {
+    Ok(match format {
+        TokenFormat::Csv => delimited(tokens, ",", quote_csv),
+        TokenFormat::Tsv => delimited(tokens, "\t", escape_tsv),
+        TokenFormat::Json => to_json(&tokens)? + "\n",
+        TokenFormat::JsonLines => {
+            let mut lines = String::new();
+            for token in tokens {
+                lines += &to_json(token)?;
+                lines.push('\n');
+            }
+            lines
+        }
+        TokenFormat::Tokens => {
+            let types: BTreeMap<i32, &str> = tokens
+                .iter()
+                .filter(|t| t.type_id != EOF_TYPE)
+                .map(|t| (t.type_id, t.type_name.as_str()))
+                .collect();
+            types.iter().map(|(id, name)| format!("{name}={id}\n")).collect()
+        }
+    })
+}
+
+fn to_json(value: &impl Serialize) -> CommandResult<String> {
+    serde_json::to_string(value).map_err(|e| CommandError::invalid_argument(e.to_string()))
+}
+
+fn delimited(tokens: &[TokenInfo], separator: &str, escape: fn(&str) -> String) -> String {
+    let mut out = COLUMNS.join(separator) + "\n";
+    for t in tokens {
+        let _ = writeln!(
+            out,
+            "{}{separator}{}{separator}{}{separator}{}{separator}{}{separator}{}{separator}{}{separator}{}{separator}{}",
+            escape(&t.text),
+            escape(&t.type_name),
+            t.type_id,
+            t.line,
+            t.column,
+            t.start_index,
+            t.stop_index,
+            t.token_index,
+            t.channel
+        );
+    }
+    out
+}
+
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks become backslash escapes
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape_tsv(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Read a saved token stream; an `EOF` token at the end is dropped.
+pub fn import(content: &str, format: TokenFormat) -> CommandResult<Vec<TokenRecord>> {
+    let records = match format {
+        TokenFormat::Csv => from_rows(parse_csv(content))?,
+        TokenFormat::Tsv => from_rows(
+            content
+                .lines()
+                .filter(|l| !l.is_empty())
+                .map(|l| l.split('\t').map(unescape_tsv).collect())
+                .collect(),
+        )?,
+        TokenFormat::Json => serde_json::from_str(content).map_err(invalid)?,
+        TokenFormat::JsonLines => content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).map_err(invalid))
+            .collect::<CommandResult<_>>()?,
+        TokenFormat::Tokens => {
+            return Err(CommandError::invalid_argument(
+                "A .tokens file only lists the token types, not a token stream",
+            ))
+        }
+    };
+
+    let records: Vec<TokenRecord> = records.into_iter().filter(|r| !r.is_eof()).collect();
+    if let Some(i) = records.iter().position(|r| r.type_name.is_none() && r.type_id.is_none()) {
+        return Err(invalid(format!("token {i} has neither a typeName nor a typeId")));
+    }
+    Ok(records)
+}
+
+fn invalid(err: impl ToString) -> CommandError {
+    CommandError::invalid_argument(format!("Not a valid token stream: {}", err.to_string()))
+}
+
+/// Records from rows whose first one names the columns
+fn from_rows(rows: Vec<Vec<String>>) -> CommandResult<Vec<TokenRecord>> {
+    let mut rows = rows.into_iter();
+    let header = rows.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|h| h.trim() == name);
+    let text = column("text").ok_or_else(|| invalid("there is no text column"))?;
+    let (type_name, type_id, line, col) = (column("typeName"), column("typeId"), column("line"), column("column"));
+    let channel = column("channel");
+
+    rows.enumerate()
+        .map(|(i, row)| {
+            let field = |index: Option<usize>| index.and_then(|index| row.get(index)).filter(|f| !f.is_empty());
+            let number = |index: Option<usize>| {
+                field(index)
+                    .map(|f| f.trim().parse::<i32>().map_err(|e| invalid(format!("row {}: {e}", i + 1))))
+                    .transpose()
+            };
+            Ok(TokenRecord {
+                text: row.get(text).cloned().unwrap_or_default(),
+                type_name: field(type_name).cloned(),
+                type_id: number(type_id)?,
+                line: number(line)?,
+                column: number(col)?,
+                channel: number(channel)?,
+            })
+        })
+        .collect()
+}
+
+/// RFC 4180: quoted fields may contain separators, quotes (doubled) and line breaks
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// A token placed in the text that stands in for the input
+type Placed = (Option<String>, Option<i32>, String, usize, isize, i32, i32, i32);
+
+/// Lay the tokens out as text: at their line and column if known, otherwise
+/// separated by single spaces. Indices count characters, like ANTLR's.
+fn layout(records: &[TokenRecord]) -> (String, Vec<Placed>, (i32, i32)) {
+    let mut text = String::new();
+    let (mut index, mut line, mut column) = (0usize, 1i32, 0i32);
+    let mut placed = Vec::with_capacity(records.len());
+
+    for (i, record) in records.iter().enumerate() {
+        let mut pad = |c: char, n: i32| {
+            for _ in 0..n {
+                text.push(c);
+            }
+            index += n.max(0) as usize;
+        };
+        match (record.line, record.column) {
+            (Some(l), _) if l > line => {
+                pad('\n', l - line);
+                line = l;
+                column = record.column.unwrap_or(0);
+                pad(' ', column);
+            }
+            (_, Some(c)) if record.line.is_none_or(|l| l == line) && c > column => {
+                pad(' ', c - column);
+                column = c;
+            }
+            (None, None) if i > 0 => {
+                pad(' ', 1);
+                column += 1;
+            }
+            _ => {}
+        }
+
+        let length = record.text.chars().count();
+        placed.push((
+            record.type_name.clone(),
+            record.type_id,
+            record.text.clone(),
+            index,
+            index as isize + length as isize - 1,
+            line,
+            column,
+            record.channel.unwrap_or(DEFAULT_CHANNEL),
+        ));
+        text.push_str(&record.text);
+        index += length;
+        match record.text.rsplit_once('\n') {
+            Some((before, after)) => {
+                line += before.matches('\n').count() as i32 + 1;
+                column = after.chars().count() as i32;
+            }
+            None => column += length as i32,
+        }
+    }
+    (text, placed, (line, column))
+}
+
+/// Parse the saved tokens with the session's parser, skipping its lexer.
+/// Returns the text the tokens were laid out as, which stands in for the input.
+pub fn parse_replayed(py: Python<'_>, parse_info: &Py<PyAny>, records: &[TokenRecord]) -> CommandResult<String> {
+    let module = match py.import(REPLAY_MODULE) {
+        Ok(module) => module,
+        Err(_) => PyModule::from_code(py, REPLAY_SOURCE, c"paredros_token_replay.py", c"paredros_token_replay")?,
+    };
+    let (text, placed, (eof_line, eof_column)) = layout(records);
+    module
+        .getattr("parse")?
+        .call1((parse_info.bind(py), text.as_str(), placed, eof_line, eof_column))?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &str, type_name: &str, type_id: i32, (line, column): (i32, i32), index: i32) -> TokenInfo {
+        TokenInfo {
+            text: text.to_string(),
+            type_name: type_name.to_string(),
+            type_id,
+            line,
+            column,
+            start_index: 0,
+            stop_index: 0,
+            token_index: index,
+            channel: if type_name == "WS" { 1 } else { DEFAULT_CHANNEL },
+        }
+    }
+
+    fn record(text: &str, line: Option<i32>, column: Option<i32>) -> TokenRecord {
+        TokenRecord {
+            text: text.to_string(),
+            type_name: Some("T".to_string()),
+            type_id: None,
+            line,
+            column,
+            channel: None,
+        }
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|f| f.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn parses_csv_with_quoted_line_breaks_and_a_last_row_without_one() {
+        let content = "text,typeName\r\n\"a,\nb\",X\r\n\"say \"\"hi\"\"\",Y";
+        assert_eq!(
+            parse_csv(content),
+            rows(&[&["text", "typeName"], &["a,\nb", "X"], &["say \"hi\"", "Y"]])
+        );
+        assert_eq!(parse_csv("a,\n"), rows(&[&["a", ""]]));
+    }
+
+    #[test]
+    fn escapes_and_unescapes_tsv_fields() {
+        let field = "a\tb\nc\rd\\e";
+        assert_eq!(escape_tsv(field), "a\\tb\\nc\\rd\\\\e");
+        assert_eq!(unescape_tsv(&escape_tsv(field)), field);
+        assert_eq!(unescape_tsv("\\x\\"), "x\\");
+    }
+
+    #[test]
+    fn reads_records_by_column_name() {
+        let records = from_rows(rows(&[
+            &[" typeId", "text ", "line", "channel"],
+            &["3", "abc", "", "1"],
+            &["", "d"],
+        ]))
+        .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].text.as_str(), records[0].type_id), ("abc", Some(3)));
+        assert_eq!((records[0].line, records[0].channel), (None, Some(1)));
+        assert_eq!((records[1].text.as_str(), records[1].type_id), ("d", None));
+        assert_eq!(records[1].type_name, None);
+
+        assert!(from_rows(rows(&[&["typeId"], &["3"]])).is_err());
+        assert!(from_rows(rows(&[&["text", "line"], &["a", "one"]])).is_err());
+    }
+
+    #[test]
+    fn lays_out_tokens_after_a_multi_line_token() {
+        let (text, placed, eof) = layout(&[
+            record("/*\n*/", Some(1), Some(0)),
+            record("x", Some(2), Some(4)),
+            record("y", None, None),
+        ]);
+        assert_eq!(text, "/*\n*/  x y");
+        let positions: Vec<_> = placed.iter().map(|p| (p.3, p.4, p.5, p.6)).collect();
+        assert_eq!(positions, [(0, 4, 1, 0), (7, 7, 2, 4), (9, 9, 2, 6)]);
+        assert_eq!(eof, (2, 7));
+    }
+
+    #[test]
+    fn lays_out_a_column_without_a_line_on_the_current_line() {
+        let (text, placed, _) = layout(&[
+            record("a", Some(3), Some(1)),
+            record("b", None, Some(4)),
+            record("c", Some(3), Some(2)),
+        ]);
+        assert_eq!(text, "\n\n a  bc");
+        let positions: Vec<_> = placed.iter().map(|p| (p.3, p.5, p.6)).collect();
+        assert_eq!(positions, [(3, 3, 1), (6, 3, 4), (7, 3, 5)]);
+    }
+
+    #[test]
+    fn imports_what_it_exported() {
+        let tokens = [
+            token("say \"a, b\"", "STRING", 4, (1, 0), 0),
+            token("\t\\\n", "WS", 7, (1, 10), 1),
+            token("x", "ID", 5, (2, 0), 2),
+            token("<EOF>", "EOF", EOF_TYPE, (2, 1), 3),
+        ];
+        for format in [TokenFormat::Csv, TokenFormat::Tsv, TokenFormat::JsonLines] {
+            let records = import(&export(&tokens, format).unwrap(), format).unwrap();
+            assert_eq!(records.len(), 3, "{format:?}");
+            for (record, token) in records.iter().zip(&tokens) {
+                assert_eq!(record.text, token.text, "{format:?}");
+                assert_eq!(record.type_name.as_deref(), Some(token.type_name.as_str()), "{format:?}");
+                assert_eq!(record.type_id, Some(token.type_id), "{format:?}");
+                assert_eq!((record.line, record.column), (Some(token.line), Some(token.column)), "{format:?}");
+                assert_eq!(record.channel, Some(token.channel), "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_tokens_without_a_type() {
+        assert!(import("text\nabc\n", TokenFormat::Csv).is_err());
+        assert!(import("", TokenFormat::Tokens).is_err());
+    }
+}
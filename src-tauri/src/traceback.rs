@@ -0,0 +1,263 @@
+// ==============================
+// src-tauri/src/traceback.rs
+// ==============================
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::OnceCell;
+use pyo3::prelude::*;
+use serde::Serialize;
+
+/// Log file size after which it is rotated to `python-errors.log.1`
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// Number of rotated files kept next to the active one
+const MAX_ROTATED_LOGS: usize = 3;
+/// `repr()` of a local variable is cut after this many characters
+const MAX_LOCAL_REPR: usize = 200;
+/// Guards against cyclic `__cause__`/`__context__` chains
+const MAX_CHAIN_DEPTH: usize = 16;
+
+static LOG_FILE: OnceCell<PathBuf> = OnceCell::new();
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+static CAPTURE_LOCALS: AtomicBool = AtomicBool::new(false);
+
+/// One entry of a Python traceback, innermost frame last
+#[derive(Debug, Clone, Serialize)]
+pub struct TracebackFrame {
+    pub file: String,
+    pub line: Option<u32>,
+    pub function: String,
+    /// The source line as printed by Python's `traceback` module
+    pub source: Option<String>,
+    /// `repr()` of the frame locals, only captured if enabled
+    pub locals: Option<BTreeMap<String, String>>,
+}
+
+/// An exception that caused (`raise ... from`) or was being handled when
+/// the reported exception was raised
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainedException {
+    /// Either "cause" or "context"
+    pub relation: &'static str,
+    pub type_name: String,
+    pub message: String,
+    pub frames: Vec<TracebackFrame>,
+}
+
+/// Everything we know about a Python exception
+#[derive(Debug, Clone, Serialize)]
+pub struct PythonErrorReport {
+    pub type_name: String,
+    pub message: String,
+    /// Python's own rendering, including the chained exceptions
+    pub formatted: String,
+    pub frames: Vec<TracebackFrame>,
+    /// Outermost first, i.e. in the order Python prints them
+    pub chain: Vec<ChainedException>,
+}
+
+/// Set the directory for `python-errors.log`. Only the first call has an effect.
+pub fn init_log_dir(dir: &Path) {
+    if fs::create_dir_all(dir).is_ok() {
+        let _ = LOG_FILE.set(dir.join("python-errors.log"));
+    }
+}
+
+pub fn log_file() -> Option<&'static Path> {
+    LOG_FILE.get().map(PathBuf::as_path)
+}
+
+/// Whether frame locals are included in future reports
+pub fn set_capture_locals(enabled: bool) {
+    CAPTURE_LOCALS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn capture(py: Python<'_>, err: &PyErr) -> PythonErrorReport {
+    let value = err.value(py).as_any();
+    let with_locals = CAPTURE_LOCALS.load(Ordering::Relaxed);
+
+    let mut chain = Vec::new();
+    let mut current = value.clone();
+    while chain.len() < MAX_CHAIN_DEPTH {
+        let Some((relation, next)) = chained(&current) else {
+            break;
+        };
+        chain.push(ChainedException {
+            relation,
+            type_name: type_name(&next),
+            message: next.str().map(|s| s.to_string()).unwrap_or_default(),
+            frames: frames(py, &next, with_locals),
+        });
+        current = next;
+    }
+    chain.reverse();
+
+    PythonErrorReport {
+        type_name: type_name(value),
+        message: value.str().map(|s| s.to_string()).unwrap_or_default(),
+        formatted: format_exception(py, value).unwrap_or_else(|_| err.to_string()),
+        frames: frames(py, value, with_locals),
+        chain,
+    }
+}
+
+/// Append the report to the error log, rotating it when it grows too large.
+/// Logging is best effort; failures are only printed.
+pub fn log_report(report: &PythonErrorReport) {
+    let Some(path) = LOG_FILE.get() else {
+        return;
+    };
+    let _guard = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Err(e) = rotate(path).and_then(|_| append(path, report)) {
+        eprintln!("failed to write {}: {e}", path.display());
+    }
+}
+
+fn rotate(path: &Path) -> std::io::Result<()> {
+    let too_big = fs::metadata(path)
+        .map(|m| m.len() >= MAX_LOG_SIZE)
+        .unwrap_or(false);
+    if !too_big {
+        return Ok(());
+    }
+    let rotated = |n: usize| PathBuf::from(format!("{}.{n}", path.display()));
+    for n in (1..MAX_ROTATED_LOGS).rev() {
+        if rotated(n).exists() {
+            fs::rename(rotated(n), rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+fn append(path: &Path, report: &PythonErrorReport) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        file,
+        "===== {} {}: {}",
+        chrono::Local::now().to_rfc3339(),
+        report.type_name,
+        report.message
+    )?;
+    writeln!(file, "{}", report.formatted.trim_end())?;
+    for frame in &report.frames {
+        let Some(locals) = frame.locals.as_ref().filter(|l| !l.is_empty()) else {
+            continue;
+        };
+        let line = frame.line.map(|l| l.to_string()).unwrap_or_else(|| "?".into());
+        writeln!(file, "--- locals of {} ({}:{line})", frame.function, frame.file)?;
+        for (name, value) in locals {
+            writeln!(file, "    {name} = {value}")?;
+        }
+    }
+    writeln!(file)
+}
+
+/// `__cause__` if set, otherwise `__context__` unless suppressed
+fn chained<'py>(exc: &Bound<'py, PyAny>) -> Option<(&'static str, Bound<'py, PyAny>)> {
+    let present = |o: &Bound<'py, PyAny>| !o.is_none();
+
+    if let Some(cause) = exc.getattr("__cause__").ok().filter(present) {
+        return Some(("cause", cause));
+    }
+    let suppressed = exc
+        .getattr("__suppress_context__")
+        .and_then(|s| s.extract::<bool>())
+        .unwrap_or(false);
+    if suppressed {
+        return None;
+    }
+    exc.getattr("__context__")
+        .ok()
+        .filter(present)
+        .map(|context| ("context", context))
+}
+
+fn frames(py: Python<'_>, exc: &Bound<'_, PyAny>, with_locals: bool) -> Vec<TracebackFrame> {
+    let linecache = py.import("linecache").ok();
+    let mut frames = Vec::new();
+    let mut tb = exc.getattr("__traceback__").ok();
+
+    while let Some(current) = tb.filter(|t| !t.is_none()) {
+        if let Ok(frame) = current.getattr("tb_frame") {
+            frames.push(frame_info(&frame, &current, linecache.as_ref(), with_locals));
+        }
+        tb = current.getattr("tb_next").ok();
+    }
+    frames
+}
+
+fn frame_info(
+    frame: &Bound<'_, PyAny>,
+    tb: &Bound<'_, PyAny>,
+    linecache: Option<&Bound<'_, PyModule>>,
+    with_locals: bool,
+) -> TracebackFrame {
+    let code = frame.getattr("f_code").ok();
+    let code_attr = |name: &str| {
+        code.as_ref()
+            .and_then(|c| c.getattr(name).ok())
+            .and_then(|v| v.extract::<String>().ok())
+            .unwrap_or_default()
+    };
+    let file = code_attr("co_filename");
+    let line = tb.getattr("tb_lineno").and_then(|l| l.extract::<u32>()).ok();
+
+    let source = match (linecache, line) {
+        (Some(linecache), Some(line)) => linecache
+            .call_method1("getline", (file.as_str(), line))
+            .and_then(|l| l.extract::<String>())
+            .ok()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty()),
+        _ => None,
+    };
+
+    TracebackFrame {
+        function: code_attr("co_name"),
+        file,
+        line,
+        source,
+        locals: with_locals.then(|| frame_locals(frame)).flatten(),
+    }
+}
+
+fn frame_locals(frame: &Bound<'_, PyAny>) -> Option<BTreeMap<String, String>> {
+    let locals = frame.getattr("f_locals").ok()?;
+    let items = locals.call_method0("items").ok()?;
+    let mut out = BTreeMap::new();
+    for item in items.try_iter().ok()?.flatten() {
+        let Ok((name, value)) = item.extract::<(String, Bound<'_, PyAny>)>() else {
+            continue;
+        };
+        let mut repr = value
+            .repr()
+            .map(|r| r.to_string())
+            .unwrap_or_else(|_| "<repr failed>".to_string());
+        if repr.chars().count() > MAX_LOCAL_REPR {
+            repr = repr.chars().take(MAX_LOCAL_REPR).collect::<String>() + "…";
+        }
+        out.insert(name, repr);
+    }
+    Some(out)
+}
+
+fn type_name(exc: &Bound<'_, PyAny>) -> String {
+    exc.get_type()
+        .qualname()
+        .map(|n| n.to_string())
+        .unwrap_or_else(|_| "Exception".to_string())
+}
+
+fn format_exception(py: Python<'_>, exc: &Bound<'_, PyAny>) -> PyResult<String> {
+    let lines: Vec<String> = py
+        .import("traceback")?
+        .call_method1("format_exception", (exc.get_type(), exc, exc.getattr("__traceback__")?))?
+        .extract()?;
+    Ok(lines.concat())
+}
@@ -0,0 +1,309 @@
+// ==============================
+// src-tauri/src/tree_export.rs
+// ==============================
+
+//! Writes a parse tree in formats other tools understand: Graphviz DOT,
+//! GraphML, ANTLR's LISP-style `toStringTree` and LaTeX `forest`/`qtree`.
+
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use crate::snapshot::TreeNode;
+
+/// Joins the rule names of a collapsed chain
+const CHAIN_SEPARATOR: &str = "/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TreeFormat {
+    Dot,
+    GraphMl,
+    /// `(regest (vita ...) ...)`, as ANTLR's `toStringTree` prints it
+    SExpr,
+    /// LaTeX `forest` environment
+    Forest,
+    /// LaTeX `qtree` `\Tree` command
+    Qtree,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct TreeExportOptions {
+    /// A rule whose only child is another rule becomes one node named after
+    /// both, e.g. `expr/term`
+    pub collapse_chains: bool,
+    /// Leave out error nodes together with their children
+    pub hide_errors: bool,
+}
+
+/// Apply the options; `None` if the root itself is hidden
+pub fn prepare(tree: &TreeNode, options: TreeExportOptions) -> Option<TreeNode> {
+    if options.hide_errors && tree.error {
+        return None;
+    }
+    let mut node = TreeNode {
+        rule: tree.rule.clone(),
+        token: tree.token.clone(),
+        error: tree.error,
+        children: tree.children.iter().filter_map(|c| prepare(c, options)).collect(),
+    };
+    if options.collapse_chains {
+        while let (Some(rule), [child]) = (&node.rule, node.children.as_slice()) {
+            let Some(child_rule) = &child.rule else { break };
+            let rule = format!("{rule}{CHAIN_SEPARATOR}{child_rule}");
+            let child = node.children.pop().expect("one child");
+            node = TreeNode {
+                rule: Some(rule),
+                error: node.error || child.error,
+                ..child
+            };
+        }
+    }
+    Some(node)
+}
+
+pub fn render(tree: &TreeNode, format: TreeFormat) -> String {
+    match format {
+        TreeFormat::Dot => to_dot(tree),
+        TreeFormat::GraphMl => to_graphml(tree),
+        TreeFormat::SExpr => to_sexpr(tree) + "\n",
+        TreeFormat::Forest => format!("\\begin{{forest}}\n{}\n\\end{{forest}}\n", to_forest(tree)),
+        TreeFormat::Qtree => format!("\\Tree {}\n", to_qtree(tree)),
+    }
+}
+
+/// Rule name or token text, unescaped
+fn text(node: &TreeNode) -> &str {
+    node.rule.as_deref().or(node.token.as_deref()).unwrap_or("?")
+}
+
+/// Nodes in pre-order with their parent's index
+fn flatten(tree: &TreeNode) -> Vec<(&TreeNode, Option<usize>)> {
+    fn visit<'a>(node: &'a TreeNode, parent: Option<usize>, out: &mut Vec<(&'a TreeNode, Option<usize>)>) {
+        let index = out.len();
+        out.push((node, parent));
+        for child in &node.children {
+            visit(child, Some(index), out);
+        }
+    }
+    let mut nodes = Vec::new();
+    visit(tree, None, &mut nodes);
+    nodes
+}
+
+fn to_dot(tree: &TreeNode) -> String {
+    let mut dot = String::from("digraph parse_tree {\n  node [shape=box, fontname=\"Helvetica\"];\n");
+    let nodes = flatten(tree);
+    for (i, (node, _)) in nodes.iter().enumerate() {
+        let mut attributes = format!("label=\"{}\"", escape_dot(text(node)));
+        if node.rule.is_none() {
+            attributes.push_str(", shape=plaintext");
+        }
+        if node.error {
+            attributes.push_str(", color=red, fontcolor=red");
+        }
+        let _ = writeln!(dot, "  n{i} [{attributes}];");
+    }
+    for (i, (_, parent)) in nodes.iter().enumerate() {
+        if let Some(parent) = parent {
+            let _ = writeln!(dot, "  n{parent} -> n{i};");
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn escape_dot(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn to_graphml(tree: &TreeNode) -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <key id=\"error\" for=\"node\" attr.name=\"error\" attr.type=\"boolean\"/>\n",
+        "  <graph id=\"parse_tree\" edgedefault=\"directed\">\n",
+    ));
+    let nodes = flatten(tree);
+    for (i, (node, _)) in nodes.iter().enumerate() {
+        let kind = if node.rule.is_some() { "rule" } else { "token" };
+        let _ = writeln!(
+            xml,
+            "    <node id=\"n{i}\"><data key=\"label\">{}</data><data key=\"kind\">{kind}</data><data key=\"error\">{}</data></node>",
+            escape_xml(text(node)),
+            node.error
+        );
+    }
+    for (i, (_, parent)) in nodes.iter().enumerate() {
+        if let Some(parent) = parent {
+            let _ = writeln!(xml, "    <edge source=\"n{parent}\" target=\"n{i}\"/>");
+        }
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Like `Trees.toStringTree`: leaves by their text, with whitespace escaped
+fn to_sexpr(node: &TreeNode) -> String {
+    let label = text(node)
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    if node.children.is_empty() {
+        return label;
+    }
+    let children: Vec<String> = node.children.iter().map(to_sexpr).collect();
+    format!("({label} {})", children.join(" "))
+}
+
+/// `[label [child] ...]`, with each label in braces so brackets and commas
+/// in tokens don't confuse `forest`
+fn to_forest(node: &TreeNode) -> String {
+    let label = latex_label(node);
+    let children: String = node.children.iter().map(|c| format!(" {}", to_forest(c))).collect();
+    format!("[{{{label}}}{children}]")
+}
+
+/// `[.rule child ... ]`; leaves are plain braced text
+fn to_qtree(node: &TreeNode) -> String {
+    let label = latex_label(node);
+    if node.children.is_empty() {
+        return format!("{{{label}}}");
+    }
+    let children: String = node.children.iter().map(|c| format!(" {}", to_qtree(c))).collect();
+    format!("[.{{{label}}}{children} ]")
+}
+
+/// Tokens in typewriter font, errors in red (needs `xcolor`)
+fn latex_label(node: &TreeNode) -> String {
+    let mut label = escape_latex(text(node));
+    if node.rule.is_none() {
+        label = format!("\\texttt{{{label}}}");
+    }
+    if node.error {
+        label = format!("\\textcolor{{red}}{{{label}}}");
+    }
+    label
+}
+
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            '\n' => escaped.push_str("\\textbackslash{}n"),
+            '\t' => escaped.push_str("\\textbackslash{}t"),
+            '\r' => escaped.push_str("\\textbackslash{}r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode {
+            rule: Some(name.to_string()),
+            token: None,
+            error: false,
+            children,
+        }
+    }
+
+    fn token(text: &str) -> TreeNode {
+        TreeNode {
+            rule: None,
+            token: Some(text.to_string()),
+            error: false,
+            children: Vec::new(),
+        }
+    }
+
+    /// `start` over the chain `expr` → `term` and an erroneous `junk` rule
+    fn chain_with_error() -> TreeNode {
+        let junk = TreeNode {
+            error: true,
+            ..rule("junk", vec![token("%")])
+        };
+        rule("start", vec![rule("expr", vec![rule("term", vec![token("{x_1}")])]), junk])
+    }
+
+    const BOTH: TreeExportOptions = TreeExportOptions {
+        collapse_chains: true,
+        hide_errors: true,
+    };
+
+    #[test]
+    fn collapses_chains_and_hides_errors() {
+        let collapsed = TreeExportOptions {
+            collapse_chains: true,
+            hide_errors: false,
+        };
+        let tree = prepare(&chain_with_error(), collapsed).unwrap();
+        assert_eq!(tree.rule.as_deref(), Some("start"));
+        assert_eq!(tree.children[0], rule("expr/term", vec![token("{x_1}")]));
+        assert!(tree.children[1].error);
+
+        // Without the error `start` heads the chain too
+        assert_eq!(prepare(&chain_with_error(), BOTH).unwrap(), rule("start/expr/term", vec![token("{x_1}")]));
+        let error = TreeNode { error: true, ..chain_with_error() };
+        assert_eq!(prepare(&error, BOTH), None);
+        assert_eq!(prepare(&chain_with_error(), TreeExportOptions::default()).unwrap(), chain_with_error());
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape_latex("{a_1} & 50% $#"), "\\{a\\_1\\} \\& 50\\% \\$\\#");
+        assert_eq!(
+            escape_latex("\\~^<>\n"),
+            "\\textbackslash{}\\textasciitilde{}\\textasciicircum{}\\textless{}\\textgreater{}\\textbackslash{}n"
+        );
+        assert_eq!(escape_dot("say \"a\\b\"\n"), "say \\\"a\\\\b\\\"\\n");
+        assert_eq!(escape_xml("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn renders_qtree_and_forest() {
+        let tree = prepare(&chain_with_error(), BOTH).unwrap();
+        assert_eq!(render(&tree, TreeFormat::Qtree), "\\Tree [.{start/expr/term} {\\texttt{\\{x\\_1\\}}} ]\n");
+        assert_eq!(
+            render(&tree, TreeFormat::Forest),
+            "\\begin{forest}\n[{start/expr/term} [{\\texttt{\\{x\\_1\\}}}]]\n\\end{forest}\n"
+        );
+
+        let junk = &prepare(&chain_with_error(), TreeExportOptions::default()).unwrap().children[1];
+        assert_eq!(to_qtree(junk), "[.{\\textcolor{red}{junk}} {\\texttt{\\%}} ]");
+        assert_eq!(to_forest(junk), "[{\\textcolor{red}{junk}} [{\\texttt{\\%}}]]");
+    }
+}
@@ -0,0 +1,236 @@
+// ==============================
+// src-tauri/src/watcher.rs
+// ==============================
+
+//! Watches the grammar files of a session and, when one of them changes on
+//! disk, regenerates the parser, re-parses the last input and puts the cursor
+//! back where it was.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use pyo3::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::debugger;
+use crate::error::{CommandError, CommandResult};
+use crate::grammar;
+use crate::session::ParseSession;
+use crate::worker::PythonWorker;
+
+/// Editors often write a file in several steps; wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Payload of the `grammar/reloaded` event
+#[derive(Debug, Clone, Serialize)]
+pub struct GrammarReloaded {
+    pub id: usize,
+    pub changed_files: Vec<String>,
+    pub added_rules: Vec<String>,
+    pub removed_rules: Vec<String>,
+    /// Rules whose text changed
+    pub changed_rules: Vec<String>,
+    /// Whether the last input was parsed again
+    pub reparsed: bool,
+    /// Where the cursor ended up; earlier than before if the new trace is shorter
+    pub step_id: Option<usize>,
+    /// Set if the reload failed; the session is then left as it was
+    pub error: Option<CommandError>,
+}
+
+/// What `reload_session` found out, before the file list is known
+struct Reload {
+    files: Vec<PathBuf>,
+    added_rules: Vec<String>,
+    removed_rules: Vec<String>,
+    changed_rules: Vec<String>,
+    reparsed: bool,
+    step_id: Option<usize>,
+}
+
+/// One debounced watcher per watched session
+pub struct GrammarWatcher {
+    app: AppHandle,
+    watches: Mutex<HashMap<usize, Debouncer<RecommendedWatcher>>>,
+}
+
+impl GrammarWatcher {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            watches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Watch `files` for session `id`, replacing whatever was watched for it before.
+    pub fn watch(&self, id: usize, files: &[PathBuf]) -> CommandResult<()> {
+        let debouncer = self.debouncer(id, files)?;
+        self.watches.lock()?.insert(id, debouncer);
+        Ok(())
+    }
+
+    /// Like `watch`, but only if session `id` is still watched; a reload that
+    /// finishes after `unwatch` must not bring the watch back.
+    fn rewatch(&self, id: usize, files: &[PathBuf]) -> CommandResult<()> {
+        let debouncer = self.debouncer(id, files)?;
+        if let Some(watch) = self.watches.lock()?.get_mut(&id) {
+            *watch = debouncer;
+        }
+        Ok(())
+    }
+
+    fn debouncer(&self, id: usize, files: &[PathBuf]) -> CommandResult<Debouncer<RecommendedWatcher>> {
+        let watched: HashSet<PathBuf> = files.iter().map(|f| normalize(f)).collect();
+
+        let app = self.app.clone();
+        let filter = watched.clone();
+        let mut debouncer = new_debouncer(DEBOUNCE, move |events: DebounceEventResult| {
+            let Ok(events) = events else {
+                return;
+            };
+            let mut changed: Vec<String> = events
+                .into_iter()
+                .map(|e| normalize(&e.path))
+                .filter(|p| filter.contains(p))
+                .map(|p| p.display().to_string())
+                .collect();
+            changed.sort();
+            changed.dedup();
+            if !changed.is_empty() {
+                let app = app.clone();
+                tauri::async_runtime::spawn(reload(app, id, changed));
+            }
+        })
+        .map_err(|e| watch_error(Path::new("."), e))?;
+
+        // Watch the directories rather than the files, so editors that save
+        // by replacing the file don't end the watch
+        let dirs: HashSet<&Path> = watched.iter().filter_map(|f| f.parent()).collect();
+        for dir in dirs {
+            debouncer
+                .watcher()
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|e| watch_error(dir, e))?;
+        }
+        Ok(debouncer)
+    }
+
+    /// Returns false if the session wasn't watched.
+    pub fn unwatch(&self, id: usize) -> CommandResult<bool> {
+        Ok(self.watches.lock()?.remove(&id).is_some())
+    }
+}
+
+async fn reload(app: AppHandle, id: usize, changed_files: Vec<String>) {
+    let worker = app.state::<PythonWorker>();
+    let result = worker
+        .run("reload_grammar", None, move |store| {
+            let handle = store.session(id)?;
+            let mut session = handle.lock()?;
+            Python::with_gil(|py| reload_session(py, &mut session))
+        })
+        .await;
+
+    let event = match result {
+        Ok(reload) => {
+            // The imports may have changed along with the files
+            let watcher = app.state::<GrammarWatcher>();
+            let error = watcher.rewatch(id, &reload.files).err();
+            GrammarReloaded {
+                id,
+                changed_files,
+                added_rules: reload.added_rules,
+                removed_rules: reload.removed_rules,
+                changed_rules: reload.changed_rules,
+                reparsed: reload.reparsed,
+                step_id: reload.step_id,
+                error,
+            }
+        }
+        Err(CommandError::UnknownSession { .. }) => {
+            // Closed or evicted in the meantime
+            let _ = app.state::<GrammarWatcher>().unwatch(id);
+            return;
+        }
+        Err(error) => GrammarReloaded {
+            id,
+            changed_files,
+            added_rules: Vec::new(),
+            removed_rules: Vec::new(),
+            changed_rules: Vec::new(),
+            reparsed: false,
+            step_id: None,
+            error: Some(error),
+        },
+    };
+    let _ = app.emit("grammar/reloaded", event);
+}
+
+/// Replace the session's `ParseInformation` with a fresh one for the same
+/// grammar. Only swapped in once everything succeeded.
+fn reload_session(py: Python<'_>, session: &mut ParseSession) -> CommandResult<Reload> {
+    let old_grammar = grammar::user_grammar(py, &session.parse_info)?;
+    let old_step = match session.parsed() {
+        Ok(parse_info) => Some(debugger::current_step(py, parse_info)?.index()?),
+        Err(_) => None,
+    };
+
+    let mut fresh = ParseSession::open(py, session.grammar_path.clone())?;
+    fresh.generate_parser(py)?;
+    let mut step_id = None;
+    if let Some(input) = session.last_input.clone() {
+        fresh.parse(py, input)?;
+        if let Some(step) = old_step {
+            let last = debugger::last_step_index(py, &fresh.parse_info)?;
+            debugger::go_to_step(py, &fresh.parse_info, step.min(last))?;
+            step_id = Some(debugger::current_step(py, &fresh.parse_info)?.index()?);
+        }
+    }
+    let new_grammar = grammar::user_grammar(py, &fresh.parse_info)?;
+
+    let reparsed = fresh.last_input.is_some();
+    fresh.breakpoints = std::mem::take(&mut session.breakpoints);
+    drop(std::mem::replace(session, fresh));
+
+    let (old_rules, new_rules) = (old_grammar.rules(), new_grammar.rules());
+    let names = |rules: &[&str]| rules.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+    let added: Vec<&str> = new_rules.keys().filter(|r| !old_rules.contains_key(*r)).copied().collect();
+    let removed: Vec<&str> = old_rules.keys().filter(|r| !new_rules.contains_key(*r)).copied().collect();
+    let changed: Vec<&str> = new_rules
+        .iter()
+        .filter(|(name, rule)| old_rules.get(*name).is_some_and(|old| old.content != rule.content))
+        .map(|(name, _)| *name)
+        .collect();
+
+    Ok(Reload {
+        files: new_grammar.file_paths(),
+        added_rules: names(&added),
+        removed_rules: names(&removed),
+        changed_rules: names(&changed),
+        reparsed,
+        step_id,
+    })
+}
+
+/// Canonical form for comparing paths; works for deleted files as well.
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent().and_then(|p| p.canonicalize().ok()), path.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn watch_error(path: &Path, err: notify_debouncer_mini::notify::Error) -> CommandError {
+    CommandError::Io {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    }
+}
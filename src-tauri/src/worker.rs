@@ -0,0 +1,257 @@
+// ==============================
+// src-tauri/src/worker.rs
+// ==============================
+
+use std::collections::HashMap;
+use std::os::raw::c_long;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::oneshot;
+
+use crate::error::{CommandError, CommandResult};
+use crate::session::ParseInfoStore;
+
+/// How often a `Running` event is emitted for each operation in progress
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+/// Number of worker threads, i.e. how many sessions can be busy at once
+const POOL_SIZE: usize = 4;
+
+/// Called with `None` if the operation was cancelled before it started.
+/// Returns whether the operation succeeded.
+type Job = Box<dyn FnOnce(Option<&ParseInfoStore>) -> bool + Send>;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum OperationStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// Payload of the `py/operation` event
+#[derive(Debug, Serialize, Clone)]
+pub struct OperationProgress {
+    pub op_id: String,
+    pub name: &'static str,
+    pub status: OperationStatus,
+    pub elapsed_ms: u64,
+}
+
+struct Operation {
+    name: &'static str,
+    started: Option<Instant>,
+    cancelled: bool,
+}
+
+/// Bookkeeping shared by the worker threads, the heartbeat thread and
+/// `cancel_operation`
+#[derive(Default)]
+struct Operations {
+    ops: HashMap<String, Operation>,
+    /// Running operations and the `threading.get_ident()` of their thread
+    running: HashMap<String, c_long>,
+}
+
+/// Runs all Python work on a small pool of dedicated threads, so the Tauri
+/// main thread never waits for the GIL. Jobs on different sessions run side
+/// by side; jobs on the same session serialize on the session lock.
+pub struct PythonWorker {
+    sender: mpsc::Sender<(String, Job)>,
+    operations: Arc<Mutex<Operations>>,
+    app: AppHandle,
+}
+
+impl PythonWorker {
+    pub fn spawn(app: AppHandle) -> Self {
+        let (sender, receiver) = mpsc::channel::<(String, Job)>();
+        let operations = Arc::new(Mutex::new(Operations::default()));
+
+        let receiver = Arc::new(Mutex::new(receiver));
+        for n in 0..POOL_SIZE {
+            let worker_app = app.clone();
+            let worker_ops = operations.clone();
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("python-worker-{n}"))
+                .spawn(move || {
+                    // Resolved on the first job, so Python is not initialised
+                    // before the venv has been set up
+                    let mut thread_ident = None;
+                    loop {
+                        let next = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                        let Ok((op_id, job)) = next else {
+                            break;
+                        };
+                        let ident = *thread_ident.get_or_insert_with(python_thread_ident);
+                        run_job(&worker_app, &worker_ops, ident, op_id, job);
+                    }
+                })
+                .expect("failed to spawn a python worker thread");
+        }
+
+        let heartbeat_app = app.clone();
+        let heartbeat_ops = operations.clone();
+        thread::Builder::new()
+            .name("python-worker-heartbeat".into())
+            .spawn(move || loop {
+                thread::sleep(HEARTBEAT_INTERVAL);
+                let running: Vec<OperationProgress> = {
+                    let ops = heartbeat_ops.lock().unwrap_or_else(|e| e.into_inner());
+                    ops.running
+                        .keys()
+                        .filter_map(|id| ops.ops.get(id).map(|op| progress(id, op, OperationStatus::Running)))
+                        .collect()
+                };
+                for progress in running {
+                    let _ = heartbeat_app.emit("py/operation", progress);
+                }
+            })
+            .expect("failed to spawn the python worker heartbeat thread");
+
+        Self {
+            sender,
+            operations,
+            app,
+        }
+    }
+
+    /// Queue `job` on the worker thread and wait for its result.
+    /// Without an `op_id` a random one is generated.
+    pub async fn run<T, F>(&self, name: &'static str, op_id: Option<String>, job: F) -> CommandResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ParseInfoStore) -> CommandResult<T> + Send + 'static,
+    {
+        let op_id = op_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let (tx, rx) = oneshot::channel();
+
+        {
+            let mut ops = self.operations.lock()?;
+            if ops.ops.contains_key(&op_id) {
+                return Err(CommandError::invalid_argument(format!(
+                    "Operation {op_id} is already in progress"
+                )));
+            }
+            let op = Operation {
+                name,
+                started: None,
+                cancelled: false,
+            };
+            let _ = self.app.emit("py/operation", progress(&op_id, &op, OperationStatus::Queued));
+            ops.ops.insert(op_id.clone(), op);
+        }
+
+        let job_op_id = op_id.clone();
+        let job: Job = Box::new(move |store| {
+            let result = match store {
+                Some(store) => job(store),
+                None => Err(CommandError::Cancelled { op_id: job_op_id }),
+            };
+            let succeeded = result.is_ok();
+            let _ = tx.send(result);
+            succeeded
+        });
+        self.sender
+            .send((op_id.clone(), job))
+            .map_err(|_| CommandError::WorkerUnavailable)?;
+
+        // The sender is only dropped without a result if the job panicked
+        match rx.await.map_err(|_| CommandError::WorkerUnavailable)? {
+            Err(e) if e.is_interrupt() => Err(CommandError::Cancelled { op_id }),
+            result => result,
+        }
+    }
+
+    /// Cancel a queued or running operation. A running one is interrupted by
+    /// raising `KeyboardInterrupt` on its worker thread.
+    /// Returns false if the operation is unknown or already done.
+    pub fn cancel(&self, op_id: &str) -> CommandResult<bool> {
+        let mut ops = self.operations.lock()?;
+        let thread_ident = ops.running.get(op_id).copied();
+
+        let Some(op) = ops.ops.get_mut(op_id) else {
+            return Ok(false);
+        };
+        op.cancelled = true;
+
+        if let Some(ident) = thread_ident {
+            // Holding the lock guarantees the worker is still inside this job
+            Python::with_gil(|_py| unsafe {
+                pyo3::ffi::PyThreadState_SetAsyncExc(ident, pyo3::ffi::PyExc_KeyboardInterrupt);
+            });
+        }
+        Ok(true)
+    }
+}
+
+fn run_job(
+    app: &AppHandle,
+    operations: &Mutex<Operations>,
+    thread_ident: c_long,
+    op_id: String,
+    job: Job,
+) {
+    {
+        let mut ops = operations.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(op) = ops.ops.get_mut(&op_id) else {
+            return;
+        };
+        if op.cancelled {
+            let op = ops.ops.remove(&op_id).expect("operation vanished");
+            let _ = app.emit("py/operation", progress(&op_id, &op, OperationStatus::Cancelled));
+            drop(ops);
+            job(None);
+            return;
+        }
+        op.started = Some(Instant::now());
+        let _ = app.emit("py/operation", progress(&op_id, op, OperationStatus::Running));
+        ops.running.insert(op_id.clone(), thread_ident);
+    }
+
+    let store = app.state::<ParseInfoStore>();
+    let succeeded = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(Some(&store))))
+        .unwrap_or(false);
+
+    let mut ops = operations.lock().unwrap_or_else(|e| e.into_inner());
+    ops.running.remove(&op_id);
+    // Drop an interrupt that arrived after the job's last Python call
+    Python::with_gil(|_py| unsafe {
+        pyo3::ffi::PyThreadState_SetAsyncExc(thread_ident, std::ptr::null_mut());
+    });
+    if let Some(op) = ops.ops.remove(&op_id) {
+        let status = match (succeeded, op.cancelled) {
+            (_, true) => OperationStatus::Cancelled,
+            (true, false) => OperationStatus::Finished,
+            (false, false) => OperationStatus::Failed,
+        };
+        let _ = app.emit("py/operation", progress(&op_id, &op, status));
+    }
+}
+
+fn python_thread_ident() -> c_long {
+    Python::with_gil(|py| {
+        py.import("threading")
+            .and_then(|t| t.call_method0("get_ident"))
+            .and_then(|ident| ident.extract::<u64>())
+            .map(|ident| ident as c_long)
+            .unwrap_or_default()
+    })
+}
+
+fn progress(op_id: &str, op: &Operation, status: OperationStatus) -> OperationProgress {
+    OperationProgress {
+        op_id: op_id.to_string(),
+        name: op.name,
+        status,
+        elapsed_ms: op
+            .started
+            .map(|s| s.elapsed().as_millis() as u64)
+            .unwrap_or_default(),
+    }
+}
@@ -191,6 +191,11 @@ function App() {
 
         await saveGrammarFiles();
 
+        // free the ParseInformation instance of the previously loaded grammar
+        if (parseInfo) {
+            await invoke("close_parse_info", {id: parseInfo});
+        }
+
         setParseInfo(await invoke("get_parse_info", {grammar: grammarFileLocation}));
     }
 
@@ -292,13 +297,19 @@ function App() {
 
     async function saveGrammarFiles(): Promise<void> {
         if (userGrammar) {
+            // The grammar as reloaded after the last successful save
+            let refreshed: UserGrammar | undefined;
             for (const key in userGrammar.grammar_files) {
                 if (Object.prototype.hasOwnProperty.call(userGrammar.grammar_files, key)) {
                     const file = userGrammar.grammar_files[key];
                     if (file.changed) {
                         try {
-                            // Save the file's content to its specified path.
-                            await writeTextFile(file.path, file.content);
+                            // Save the file's content to its specified path, keeping a backup.
+                            refreshed = await invoke<UserGrammar>("write_grammar_file", {
+                                id: parseInfo,
+                                path: file.path,
+                                content: file.content,
+                            });
                             // Mark the file as saved.
                             file.changed = false;
                         } catch (error) {
@@ -307,6 +318,20 @@ function App() {
                     }
                 }
             }
+
+            if (refreshed) {
+                const saved = refreshed;
+                // Use the reloaded rules and positions, but keep the edits of files that failed to save
+                setUserGrammar(prev => {
+                    const grammar_files = {...saved.grammar_files};
+                    for (const [key, file] of Object.entries(prev?.grammar_files ?? {})) {
+                        if (file.changed && grammar_files[key]) {
+                            grammar_files[key] = {...grammar_files[key], content: file.content, changed: true};
+                        }
+                    }
+                    return {...saved, grammar_files};
+                });
+            }
         }
     }
 
@@ -366,10 +391,11 @@ function App() {
     }
 
     async function get_next_parse_step_info() {
-        const _response = await invoke<ParseStepInfo>("get_next_parse_step_info", {
+        // null once the current step is the last one
+        const _response = await invoke<ParseStepInfo | null>("get_next_parse_step_info", {
             id: parseInfo,
         });
-        setNextInfo(_response);
+        setNextInfo(_response ?? undefined);
     }
 
     async function get_lexemes() {
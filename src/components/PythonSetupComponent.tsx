@@ -1,7 +1,7 @@
-import React, { useEffect } from 'react';
+import React, { useEffect, useState } from 'react';
 import {invoke} from "@tauri-apps/api/core";
 import { listen } from '@tauri-apps/api/event';
-import type {PySetupProgressType} from "../interfaces/PySetupProgressType.ts";
+import type {PySetupLog, PySetupProgressType} from "../interfaces/PySetupProgressType.ts";
 
 type PythonSetupProps = {
     pyProgress: PySetupProgressType;
@@ -10,6 +10,8 @@ type PythonSetupProps = {
 
 
 const PythonSetupComponent: React.FC<PythonSetupProps> = ({ pyProgress, setPyProgress }) => {
+    // Last line printed by the installer, so a slow download doesn't look like a hang
+    const [lastLog, setLastLog] = useState<PySetupLog | null>(null);
 
     const handleInitPython = async () => {
         try {
@@ -36,10 +38,15 @@ const PythonSetupComponent: React.FC<PythonSetupProps> = ({ pyProgress, setPyPro
             console.log(payload);
         });
 
+        const unlistenLogPromise = listen<PySetupLog>('py/setup-log', (evt) => {
+            setLastLog(evt.payload);
+        });
+
         handleInitPython();
 
         return () => {
             unlistenPromise.then((unlisten) => unlisten());
+            unlistenLogPromise.then((unlisten) => unlisten());
         };
     }, []);
 
@@ -66,10 +73,19 @@ const PythonSetupComponent: React.FC<PythonSetupProps> = ({ pyProgress, setPyPro
                     {pyProgress === 'Checking' && '🔍 Checking environment...'}
                     {pyProgress === 'CreatingVenv' && '⚙️ Creating virtual environment...'}
                     {pyProgress === 'InstallingPackages' && '📦 Installing packages...'}
+                    {typeof pyProgress === 'object' && 'InstallingPackage' in pyProgress && (
+                        `📦 Installing ${pyProgress.InstallingPackage.package}...` +
+                        (pyProgress.InstallingPackage.percent !== null ? ` ${pyProgress.InstallingPackage.percent}%` : '')
+                    )}
                     {pyProgress === 'Done' && '✅ Setup complete!'}
                     {typeof pyProgress === 'object' && 'Error' in pyProgress && <span style={{ whiteSpace: 'pre-wrap' }}>❌ An error occurred during setup: {pyProgress.Error} </span>}
                 </div>
             )}
+            {lastLog && pyProgress !== 'Done' && !isThereAnError() && (
+                <div className="text-xs text-gray-500 font-mono max-w-md truncate">
+                    {lastLog.source}: {lastLog.line}
+                </div>
+            )}
         </div>
     );
 };
@@ -6,6 +6,7 @@ import {
   DialogBody,
   DialogActions,
 } from "./ui/dialog"; // adjust the path to wherever your dialog.tsx lives
+import {isCommandError} from "../interfaces/CommandError.ts";
 
 /**
  * `UnhandledRejectionDialog` listens for the global `unhandledrejection` event and
@@ -16,6 +17,7 @@ const UnhandledRejectionDialog: React.FC = () => {
     id: number;
     message: string;
     stack?: string;
+    kind?: string;
   }
 
   const [queue, setQueue] = useState<RejectionInfo[]>([]);
@@ -32,8 +34,13 @@ const UnhandledRejectionDialog: React.FC = () => {
       // Most JS runtimes expose `.message` + `.stack` on `Error` objects,
       // but the rejection could be *anything*, so defensively coerce.
       const reason = event.reason ?? "Unknown rejection";
-      const message = typeof reason === "object" && "message" in reason ? (reason as any).message : String(reason);
+      // Errors returned by Tauri commands carry a `kind` and a `display` text (see src-tauri/src/error.rs)
+      const commandError = isCommandError(reason) ? reason : undefined;
+      const message = commandError
+          ? commandError.display
+          : typeof reason === "object" && "message" in reason ? (reason as any).message : String(reason);
       const stack = typeof reason === "object" && "stack" in reason ? (reason as any).stack : undefined;
+      const kind = commandError?.kind;
 
       setQueue((prev) => [
         ...prev,
@@ -41,6 +48,7 @@ const UnhandledRejectionDialog: React.FC = () => {
           id: Date.now(),
           message,
           stack,
+          kind,
         },
       ]);
     }
@@ -59,7 +67,9 @@ const UnhandledRejectionDialog: React.FC = () => {
   return (
       <Dialog open onClose={dismissCurrent} size="lg">
         <DialogTitle>Something went wrong</DialogTitle>
-        <DialogDescription>An unhandled promise rejection occurred.</DialogDescription>
+        <DialogDescription>
+          {current.kind ? `The backend reported an error (${current.kind}).` : "An unhandled promise rejection occurred."}
+        </DialogDescription>
 
         <DialogBody>
         <pre className="whitespace-pre-wrap break-words font-mono text-xs text-zinc-800 dark:text-zinc-200">
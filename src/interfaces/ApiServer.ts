@@ -0,0 +1,14 @@
+/** Returned by `start_api_server` and `get_api_server` */
+export interface ApiServerInfo {
+    /** e.g. `http://127.0.0.1:48213/rpc` */
+    url: string;
+    /** Send as `Authorization: Bearer <token>` */
+    token: string;
+}
+
+/** Payload of the `api/request` event, sent after each successful API call */
+export interface ApiRequest {
+    method: string;
+    /** The session the call was about, if any */
+    id: number | null;
+}
@@ -0,0 +1,41 @@
+import type {CommandError} from "./CommandError.ts";
+import type {ParseStepInfo} from "./UserGrammar.ts";
+
+/** Mirrors `BreakpointKind` in src-tauri/src/breakpoints.rs */
+export type BreakpointKind =
+    | { kind: 'RuleEnter'; rule: string }
+    | { kind: 'RuleExit'; rule: string }
+    | { kind: 'TokenType'; type_name: string }
+    | { kind: 'TokenIndex'; index: number }
+    | { kind: 'GrammarLine'; file: string; line: number }
+    | { kind: 'AnyStep' };
+
+/** Returned by `set_breakpoint` and `list_breakpoints` */
+export type Breakpoint = BreakpointKind & {
+    id: number;
+    enabled: boolean;
+    /** Expression that must hold as well, e.g. `lookahead_repr[0] == "KOMMA"` */
+    condition: string | null;
+};
+
+/** Argument of `set_breakpoint`; pass an `id` to replace that breakpoint */
+export type BreakpointSpec = BreakpointKind & {
+    id?: number;
+    enabled?: boolean;
+    condition?: string | null;
+};
+
+/** Returned by `continue_to_breakpoint` and `reverse_continue` */
+export interface ContinueResult {
+    step: ParseStepInfo;
+    /** Empty if the run reached the end (or the start) of the trace */
+    hit: number[];
+}
+
+/** Returned by `evaluate_watches`, one per expression */
+export interface WatchValue {
+    expression: string;
+    value: unknown;
+    /** Set instead of `value` if the expression doesn't parse */
+    error: CommandError | null;
+}
@@ -0,0 +1,52 @@
+export interface TracebackFrame {
+    file: string;
+    line: number | null;
+    function: string;
+    source: string | null;
+    locals: Record<string, string> | null;
+}
+
+/**
+ * Mirrors `CommandError` in src-tauri/src/error.rs, which every Tauri command rejects with.
+ * `display` is always present and already human-readable.
+ */
+export type CommandError = { display: string } & (
+    | { kind: 'UnknownSession'; id: number }
+    | {
+        kind: 'PythonException';
+        type_name: string;
+        /** The exception message, without the type */
+        message: string;
+        traceback: string | null;
+        frames: TracebackFrame[];
+        chain: { relation: 'cause' | 'context'; type_name: string; message: string; frames: TracebackFrame[] }[];
+      }
+    | { kind: 'GrammarGenerationFailed'; tool_output: string }
+    | { kind: 'NotParsedYet' }
+    | { kind: 'StepOutOfRange'; step_id: number; last_step: number }
+    | { kind: 'MalformedStep'; message: string }
+    | { kind: 'LockPoisoned'; what: string }
+    | { kind: 'InvalidArgument'; message: string }
+    | { kind: 'Cancelled'; op_id: string }
+    | { kind: 'WorkerUnavailable' }
+    | { kind: 'PythonSetup'; message: string }
+    | {
+        kind: 'IncompatiblePython';
+        /** From `.python-version`, e.g. `3.12` */
+        wanted: string;
+        /** The version the app was built against */
+        linked: string;
+        min: string;
+        max: string;
+        /** Interpreters found on PATH; `version` is null if one didn't run */
+        found: { path: string; version: string | null }[];
+      }
+    | { kind: 'Io'; path: string; message: string }
+    | { kind: 'StaleGrammarFile'; path: string; rule: string }
+    | { kind: 'InvalidProjectFile'; path: string; message: string }
+    | { kind: 'InvalidExpression'; expression: string; column: number; message: string }
+    );
+
+export function isCommandError(reason: unknown): reason is CommandError {
+    return typeof reason === "object" && reason !== null && "kind" in reason && "display" in reason;
+}
@@ -0,0 +1,34 @@
+/** Mirrors `CorpusReport` in src-tauri/src/corpus.rs, returned by `run_corpus` */
+export interface CorpusReport {
+    grammar: string;
+    pattern: string;
+    cases: CorpusCase[];
+    passed: number;
+    failed: number;
+    errored: number;
+    duration_ms: number;
+    started_at: string;
+}
+
+export interface CorpusCase {
+    input: string;
+    status: 'Passed' | 'Failed' | 'Errored';
+    steps: number;
+    error_count: number;
+    errors: {
+        step_id: number;
+        rule_name: string | null;
+        token: string;
+        context: string | null;
+    }[];
+    message: string | null;
+    duration_ms: number;
+}
+
+/** Payload of the `corpus/case` event */
+export interface CorpusProgress {
+    op_id: string | null;
+    index: number;
+    total: number;
+    case: CorpusCase;
+}
@@ -0,0 +1,19 @@
+export type CheckStatus = 'Ok' | 'Warning' | 'Failed';
+
+export interface DiagnosticsCheck {
+    name: string;
+    status: CheckStatus;
+    detail: string;
+}
+
+/** Returned by `run_diagnostics` */
+export interface DiagnosticsReport {
+    app_version: string;
+    os: string;
+    generated_at: string;
+    checks: DiagnosticsCheck[];
+    /** Variable and value, null if unset */
+    env: [string, string | null][];
+    /** All of the above as plain text, for bug tickets */
+    report: string;
+}
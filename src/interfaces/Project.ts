@@ -0,0 +1,18 @@
+import type {Breakpoint} from "./Breakpoint.ts";
+
+/** Mirrors `ProjectFile` in src-tauri/src/project.rs, the content of a `.paredros` file */
+export interface ProjectFile {
+    version: number;
+    grammar_path: string;
+    input: string | null;
+    step_id: number | null;
+    breakpoints: { next_id: number; list: Breakpoint[] };
+    ui_state: unknown;
+    saved_at: string;
+}
+
+/** Returned by `load_session` */
+export interface LoadedProject {
+    id: number;
+    project: ProjectFile;
+}
@@ -3,5 +3,34 @@ export type PySetupProgressType =
     | 'Checking'
     | 'CreatingVenv'
     | 'InstallingPackages'
+    | { InstallingPackage: { package: string; percent: number | null } }
     | 'Done'
-    | { Error: string };
\ No newline at end of file
+    | { Error: string };
+/** Payload of the `py/setup-log` event, one line printed by venv, pip, uv or antlr4 */
+export interface PySetupLog {
+    source: 'venv' | 'pip' | 'uv' | 'antlr4';
+    stream: 'stdout' | 'stderr';
+    line: string;
+    timestamp: string;
+}
+
+/** Returned by `build_wheelhouse` */
+export interface WheelhouseReport {
+    dir: string;
+    /** File names of the wheels */
+    wheels: string[];
+    /** File name of the ANTLR tool jar, if one was found to copy */
+    antlr_jar: string | null;
+}
+
+/** Which tool makes the Python environment; see `get_python_settings` */
+export type EnvProviderConfig =
+    | { kind: 'Venv' }
+    | { kind: 'Uv' }
+    | { kind: 'Conda'; prefix: string }
+    /** `allow_system` lets the app install into a Python outside of a venv */
+    | { kind: 'Interpreter'; python: string; allow_system?: boolean };
+
+export interface PythonSettings {
+    provider: EnvProviderConfig;
+}
@@ -0,0 +1,10 @@
+/** Formats of `export_parse_tree` */
+export type TreeFormat = 'Dot' | 'GraphMl' | 'SExpr' | 'Forest' | 'Qtree';
+
+/** Options of `export_parse_tree`; everything is off by default */
+export interface TreeExportOptions {
+    /** A rule whose only child is another rule becomes one node, e.g. `expr/term` */
+    collapse_chains?: boolean;
+    /** Leave out error nodes together with their children */
+    hide_errors?: boolean;
+}
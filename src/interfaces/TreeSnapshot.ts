@@ -0,0 +1,9 @@
+/** Mirrors `TreeChange` in src-tauri/src/snapshot.rs, returned by `compare_tree_snapshot` */
+export type TreeChange =
+    | { kind: 'Added'; path: string; node: string }
+    | { kind: 'Removed'; path: string; node: string }
+    | { kind: 'RuleRenamed'; path: string; from: string; to: string }
+    | { kind: 'TokenChanged'; path: string; from: string; to: string }
+    /** `error` is whether the node is an error node now */
+    | { kind: 'ErrorChanged'; path: string; node: string; error: boolean }
+    | { kind: 'Moved'; from: string; to: string; node: string };
@@ -1,3 +1,5 @@
+import type {CommandError} from "./CommandError.ts";
+
 export interface GrammarRule {
   name: string;
   content: string;
@@ -21,6 +23,18 @@ export interface UserGrammar {
   processed_files: string[];
 }
   
+/** Payload of the `grammar/reloaded` event, see `watch_grammar` */
+export interface GrammarReloaded {
+  id: number;
+  changed_files: string[];
+  added_rules: string[];
+  removed_rules: string[];
+  changed_rules: string[];
+  reparsed: boolean;
+  step_id: number | null;
+  error: CommandError | null;
+}
+
 export interface Transition {
   target_state: number;
   matches: string[];
@@ -57,6 +71,15 @@ export interface ParseStepInfo {
   step_type: "Decision" | "Rule entry" | "Rule exit" | "Token consume" | "Error";
 }
 
+/** Payload of the `trace/chunk` event, see `stream_full_trace` */
+export interface TraceChunk {
+  id: number;
+  op_id: string;
+  offset: number;
+  steps: ParseStepInfo[];
+  done: boolean;
+}
+
 export interface TokenInfo {
     text: string;
     typeName: string;
@@ -66,4 +89,7 @@ export interface TokenInfo {
     startIndex: number;
     stopIndex: number;
     tokenIndex: number;
-}
\ No newline at end of file
+    channel: number;
+}
+/** Formats of `export_tokens` and `parse_tokens`; `Tokens` can only be exported */
+export type TokenFormat = 'Csv' | 'Tsv' | 'Json' | 'JsonLines' | 'Tokens';